@@ -0,0 +1,449 @@
+// command line interface, used when the app is started with arguments:
+//  acc-app import <file>  writes a file into the spreadsheet when nothing has to be asked,
+//                         --force if the spreadsheet was edited outside the app. exits with 3
+//                         when the import went over a budget
+//  acc-app history        list of imports written into the spreadsheet
+//  acc-app history <id>   cells written by one import, with their previous values
+//  acc-app undo <id>      reverts cells written by an import, --force if they were changed since
+//  acc-app backups                  list of backups
+//  acc-app backups diff <name>      cells which differ between the backup and the current workbook
+//  acc-app backups open <name>      opens a read only copy of the backup
+//  acc-app backups restore <name>   makes the backup the current workbook
+//  acc-app chart <kind> [year] [--out <file>]   writes a chart as .svg or .png, into the
+//                                               charts folder without --out
+//  acc-app report [year] [--out <file>]         writes the statistics with the forecast as
+//                                               markdown, into the reports folder without --out
+// chart and report take --real for amounts in real terms, with the CPI table from config
+
+use acc_app::import::import_log::{load_import_log, ImportLogEntry};
+use acc_app::import::preview::ImportPreview;
+use acc_app::import::undo::{undo_import, UndoOutcome};
+use acc_app::stats::chart_export::export_chart;
+use acc_app::stats::charts::ChartKind;
+use acc_app::stats::report::export_report;
+use acc_app::utils::backup_utils::{diff_backup, list_backups, open_backup_read_only, restore_backup};
+use acc_app::utils::config::read_config;
+use acc_app::utils::folder_file_utils::open_with_default_app;
+use std::path::Path;
+
+// import was written, but went over a budget
+const BUDGET_ALERT_EXIT_CODE: i32 = 3;
+
+const USAGE: &str = "Usage:
+  acc-app import <file>  import a file (--force to write a spreadsheet edited outside the app),
+                         exits with 3 on budget alerts
+  acc-app history        list imports
+  acc-app history <id>   show cells written by an import
+  acc-app undo <id>      undo an import (--force to overwrite cells changed since)
+  acc-app backups                  list backups
+  acc-app backups diff <name>      compare a backup with the current workbook
+  acc-app backups open <name>      open a read only copy of a backup
+  acc-app backups restore <name>   make a backup the current workbook
+  acc-app chart <kind> [year] [--out <file.svg|file.png>] [--real]
+                         write a chart, kind is trend, months, shares, heatmap or years
+  acc-app report [year] [--out <file.md>] [--real]
+                         write statistics and forecast of a year as markdown
+                         --real restates amounts to prices of one month with the CPI table";
+
+// returns process exit code
+pub fn run(args: &[String], destination_path: &str) -> i32 {
+    match args.iter().map(|arg| arg.as_str()).collect::<Vec<&str>>().as_slice() {
+        ["import", file] | ["import", file, "--force"] | ["import", "--force", file] => {
+            import_command(destination_path, file, args.iter().any(|arg| arg == "--force"))
+        }
+        ["history"] => list_history(destination_path),
+        ["history", id] => match parse_import_id(id) {
+            Some(id) => show_import(destination_path, id),
+            None => 2,
+        },
+        ["undo", id] | ["undo", id, "--force"] | ["undo", "--force", id] => match parse_import_id(id) {
+            Some(id) => undo(destination_path, id, args.iter().any(|arg| arg == "--force")),
+            None => 2,
+        },
+        ["backups"] => list_backups_command(destination_path),
+        ["backups", "diff", name] => diff_backup_command(destination_path, name),
+        ["backups", "open", name] => match open_backup_read_only(destination_path, name) {
+            Ok(path) => {
+                println!("Read only copy: {}", path.display());
+                if let Err(e) = open_with_default_app(&path) {
+                    println!("Cannot open it: {}", e);
+                }
+                0
+            }
+            Err(e) => {
+                println!("{}", e);
+                1
+            }
+        },
+        ["backups", "restore", name] => match restore_backup(destination_path, name) {
+            Ok(()) => {
+                println!("Restored {}", name);
+                0
+            }
+            Err(e) => {
+                println!("{}", e);
+                1
+            }
+        },
+        ["chart", kind, options @ ..] => chart_command(destination_path, kind, options),
+        ["report", options @ ..] => report_command(destination_path, options),
+        _ => {
+            println!("{}", USAGE);
+            2
+        }
+    }
+}
+
+// accepts "3" and "#3"
+fn parse_import_id(id: &str) -> Option<u64> {
+    match id.trim_start_matches('#').parse::<u64>() {
+        Ok(id) => Some(id),
+        Err(_) => {
+            println!("Invalid import id: {}", id);
+            None
+        }
+    }
+}
+
+// nobody to ask here: uncategorised transactions stop the import, duplicates and credits that
+// look like income stay left out. recurring expenses are added as they are, months to skip go
+// into "skip" in config
+fn import_command(destination_path: &str, file: &str, force: bool) -> i32 {
+    let config = match read_config(destination_path) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        }
+    };
+    let preview = match ImportPreview::load(file, destination_path, &config) {
+        Ok(preview) => preview,
+        Err(e) => {
+            println!("{}", e);
+            return 1;
+        }
+    };
+    if !preview.is_resolved() {
+        println!("{} transactions need a category, import the file in the app", preview.unresolved().len());
+        return 1;
+    }
+    if !preview.duplicates().is_empty() {
+        println!("{} possible duplicates left out", preview.duplicates().len());
+    }
+    for index in preview.income_credits() {
+        let transaction = &preview.transactions[index];
+        println!(
+            "Credit {:.2} on {} left out, looks like income and the year has no Income area: {}",
+            transaction.amount,
+            transaction.booking_date.format("%d.%m.%Y"),
+            transaction.description
+        );
+    }
+    for error in preview.recurring_errors() {
+        println!("{}", error);
+    }
+    for index in preview.recurring() {
+        let transaction = &preview.transactions[index];
+        let state = if preview.is_excluded(index) { "left out, already in the file or imported" } else { "added" };
+        println!(
+            "Recurring {} {:.2} on {}: {}",
+            transaction.category.as_deref().unwrap_or_default(),
+            transaction.amount,
+            transaction.booking_date.format("%d.%m.%Y"),
+            state
+        );
+    }
+    for anomaly in preview.anomalies() {
+        println!("Unusual: {}", anomaly.describe());
+    }
+    for cell in preview.formula_cells(destination_path) {
+        println!("Formula left as it is: {}", cell);
+    }
+
+    match preview.commit(destination_path, force) {
+        Ok(outcome) if outcome.months == 0 => {
+            println!("Nothing to write, every transaction was left out");
+            0
+        }
+        Ok(outcome) => {
+            println!("Data is in spreadsheet now ({} months)", outcome.months);
+            for alert in &outcome.alerts {
+                println!("Budget alert: {}", alert.describe());
+            }
+            if outcome.alerts.is_empty() {
+                0
+            } else {
+                BUDGET_ALERT_EXIT_CODE
+            }
+        }
+        Err(e) => {
+            println!("{}", e);
+            1
+        }
+    }
+}
+
+fn list_history(destination_path: &str) -> i32 {
+    let log = load_import_log(destination_path);
+    if log.is_empty() {
+        println!("Nothing imported yet");
+    }
+    for entry in &log {
+        println!("{}", entry.summary());
+    }
+    0
+}
+
+fn show_import(destination_path: &str, id: u64) -> i32 {
+    let log = load_import_log(destination_path);
+    let Some(entry) = log.iter().find(|entry| entry.id == id) else {
+        println!("Import #{} not found", id);
+        return 1;
+    };
+
+    for line in import_details(entry) {
+        println!("{}", line);
+    }
+    0
+}
+
+fn undo(destination_path: &str, id: u64, force: bool) -> i32 {
+    match undo_import(destination_path, id, force) {
+        Ok(UndoOutcome::Undone { cells, .. }) => {
+            println!("Import #{} undone ({} cells)", id, cells);
+            0
+        }
+        Ok(UndoOutcome::ChangedSinceImport(changed)) => {
+            println!("Cells changed since import #{}:", id);
+            for cell in changed {
+                println!("  {}", cell.describe());
+            }
+            println!("Nothing was undone, rerun with --force to overwrite them");
+            1
+        }
+        Ok(UndoOutcome::EditedOutsideApp(reason)) => {
+            println!("{}", reason);
+            println!("Nothing was undone, rerun with --force to write anyway");
+            1
+        }
+        Err(e) => {
+            println!("{}", e);
+            1
+        }
+    }
+}
+
+fn list_backups_command(destination_path: &str) -> i32 {
+    let backups = list_backups(destination_path);
+    if backups.is_empty() {
+        println!("No backups yet");
+    }
+    for backup in &backups {
+        println!("{}", backup.summary());
+    }
+    0
+}
+
+fn diff_backup_command(destination_path: &str, name: &str) -> i32 {
+    match diff_backup(destination_path, name) {
+        Ok(diff) => {
+            if diff.is_empty() {
+                println!("{} is the same as the current workbook", name);
+            }
+            for cell in diff {
+                println!("{}", cell.describe());
+            }
+            0
+        }
+        Err(e) => {
+            println!("{}", e);
+            1
+        }
+    }
+}
+
+fn chart_command(destination_path: &str, kind: &str, options: &[&str]) -> i32 {
+    let Some(kind) = ChartKind::from_name(kind) else {
+        println!("Unknown chart: {}", kind);
+        println!("{}", USAGE);
+        return 2;
+    };
+
+    let Some(options) = parse_export_options(options) else {
+        return 2;
+    };
+    match export_chart(destination_path, kind, options.year, options.output, options.real_terms) {
+        Ok(path) => {
+            println!("Chart written to {}", path.display());
+            0
+        }
+        Err(e) => {
+            println!("{}", e);
+            1
+        }
+    }
+}
+
+fn report_command(destination_path: &str, options: &[&str]) -> i32 {
+    let Some(options) = parse_export_options(options) else {
+        return 2;
+    };
+    match export_report(destination_path, options.year, options.output, options.real_terms) {
+        Ok(path) => {
+            println!("Report written to {}", path.display());
+            0
+        }
+        Err(e) => {
+            println!("{}", e);
+            1
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ExportOptions<'a> {
+    year: Option<i64>,
+    output: Option<&'a Path>,
+    real_terms: bool,
+}
+
+// [year] [--out <file>] [--real] in any order, None after telling what is wrong
+fn parse_export_options<'a>(options: &[&'a str]) -> Option<ExportOptions<'a>> {
+    let mut parsed = ExportOptions::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match *option {
+            "--out" => match options.next() {
+                Some(path) => parsed.output = Some(Path::new(*path)),
+                None => {
+                    println!("{}", USAGE);
+                    return None;
+                }
+            },
+            "--real" => parsed.real_terms = true,
+            value => match value.parse::<i64>() {
+                Ok(value) => parsed.year = Some(value),
+                Err(_) => {
+                    println!("Invalid year: {}", value);
+                    return None;
+                }
+            },
+        }
+    }
+    Some(parsed)
+}
+
+fn import_details(entry: &ImportLogEntry) -> Vec<String> {
+    let mut lines = vec![
+        entry.summary(),
+        format!("source: {}", entry.source_file),
+        format!("hash: {}", entry.source_hash),
+        format!("backup: {}", entry.backup_file.as_deref().unwrap_or("none")),
+    ];
+    for cell in &entry.cells {
+        let previous = match cell.previous {
+            Some(value) => format!("{:.2}", value),
+            None => "empty".to_string(),
+        };
+        lines.push(format!("  {} {} {}: {} -> {:.2}", cell.month, cell.year, cell.category, previous, cell.value));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use acc_app::file_handlers::xls_handlers::RES_WORKBOOK_NAME;
+    use acc_app::stats::budgets::Budget;
+    use acc_app::utils::config::{save_config, AppConfig};
+    use acc_app::utils::external_edits::record_workbook_hash;
+    use acc_app::utils::structs::CellChange;
+    use acc_app::utils::transaction_utils::month_name_from_number;
+    use chrono::{TimeZone, Utc};
+    use std::fs;
+    use xlsxwriter::Workbook;
+
+    #[test]
+    fn test_import_details() {
+        let entry = ImportLogEntry {
+            id: 1,
+            timestamp: Utc.with_ymd_and_hms(2024, 6, 2, 18, 40, 0).unwrap(),
+            source_file: "statement.xml".to_string(),
+            source_hash: "abc".to_string(),
+            month_expenses: Vec::new(),
+            cells: vec![CellChange {
+                row: 6,
+                col: 2,
+                year: 2024,
+                month: "May".to_string(),
+                category: "Groceries".to_string(),
+                previous: Some(12.5),
+                value: 150.0,
+            }],
+            backup_file: None,
+            undo_of: None,
+        };
+
+        let lines = import_details(&entry);
+        assert_eq!(lines[3], "backup: none");
+        assert_eq!(lines[4], "  May 2024 Groceries: 12.50 -> 150.00");
+    }
+
+    #[test]
+    fn test_unknown_command() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        assert_eq!(run(&["nope".to_string()], destination), 2);
+        assert_eq!(run(&["history".to_string()], destination), 0);
+        assert_eq!(run(&["history".to_string(), "7".to_string()], destination), 1);
+        assert_eq!(run(&["undo".to_string(), "x".to_string()], destination), 2);
+        assert_eq!(run(&["backups".to_string()], destination), 0);
+        assert_eq!(run(&["backups".to_string(), "restore".to_string(), "backup_1.xlsx".to_string()], destination), 1);
+        assert_eq!(run(&["undo".to_string(), "7".to_string(), "--force".to_string()], destination), 1);
+        assert_eq!(run(&["chart".to_string(), "pie".to_string()], destination), 2);
+        assert_eq!(run(&["chart".to_string(), "trend".to_string(), "last".to_string()], destination), 2);
+        assert_eq!(run(&["chart".to_string(), "trend".to_string(), "--out".to_string()], destination), 2);
+        assert_eq!(run(&["chart".to_string(), "trend".to_string(), "2024".to_string()], destination), 1); // no workbook
+        assert_eq!(run(&["report".to_string(), "--out".to_string()], destination), 2);
+        assert_eq!(run(&["report".to_string()], destination), 1); // no workbook
+        assert_eq!(run(&["report".to_string(), "--real".to_string(), "2024".to_string()], destination), 1);
+    }
+
+    #[test]
+    fn test_parse_export_options() {
+        let options = parse_export_options(&["--real", "2023", "--out", "expenses.md"]).unwrap();
+        assert_eq!(options, ExportOptions { year: Some(2023), output: Some(Path::new("expenses.md")), real_terms: true });
+        assert_eq!(parse_export_options(&[]), Some(ExportOptions::default()));
+        assert_eq!(parse_export_options(&["last"]), None);
+    }
+
+    #[test]
+    fn test_import_budget_alert_exit_code() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let workbook = Workbook::new(temp_dir.path().join(RES_WORKBOOK_NAME).to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        sheet.write_number(1, 1, 2024.0, None).unwrap();
+        sheet.write_string(1, 2, "Sweets", None).unwrap();
+        for month in 1..=12 {
+            sheet.write_string(1 + month, 1, month_name_from_number(month).unwrap(), None).unwrap();
+        }
+        workbook.close().unwrap();
+        record_workbook_hash(destination, RES_WORKBOOK_NAME).unwrap();
+        let config = AppConfig {
+            budgets: vec![Budget { category: "Sweets".to_string(), monthly: 5.0, overrides: Default::default() }],
+            ..AppConfig::default()
+        };
+        save_config(destination, &config).unwrap();
+
+        let notes = temp_dir.path().join("notes.txt");
+        fs::write(&notes, "May 2024\nSweets\n10\n").unwrap();
+        let import = |file: &std::path::Path| run(&["import".to_string(), file.to_str().unwrap().to_string()], destination);
+        assert_eq!(import(&notes), BUDGET_ALERT_EXIT_CODE);
+        assert_eq!(import(&notes), 1); // already imported
+
+        let within_budget = temp_dir.path().join("june.txt");
+        fs::write(&within_budget, "June 2024\nSweets\n1\n").unwrap();
+        assert_eq!(import(&within_budget), 0);
+    }
+}
@@ -0,0 +1,256 @@
+use std::{fs, path::Path};
+use chrono::NaiveDate;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::utils::structs::{CreditDebit, MonthExpenses, Transaction};
+use crate::utils::transaction_utils::{parse_statement_amount, transactions_into_me_structs};
+
+// ISO 20022 camt.053 (bank to customer statement) importer.
+// Only the entry level (<Ntry>) is used, one entry = one transaction:
+//  Ntry
+//  ├── Amt                          amount, always positive
+//  ├── CdtDbtInd                    CRDT / DBIT
+//  ├── Sts (or Sts/Cd)              BOOK / PDNG, only booked entries are taken
+//  ├── BookgDt/Dt (or DtTm)         booking date
+//  ├── AddtlNtryInf                 fallback description
+//  └── NtryDtls/TxDtls
+//      ├── RmtInf/Ustrd             remittance info (can repeat)
+//      └── RltdPties/Cdtr|Dbtr/Nm   counterparty (also .../Pty/Nm in newer versions)
+
+// receives filepath of a camt.053 .xml file and groups its entries per month
+pub fn transform_camt_file_into_me_structs(filepath: &str) -> Option<Vec<MonthExpenses>> {
+    let transactions = transform_camt_file_into_transactions(filepath)?;
+    Some(transactions_into_me_structs(&transactions))
+}
+
+pub fn transform_camt_file_into_transactions(filepath: &str) -> Option<Vec<Transaction>> {
+    let data = read_xml_file_to_string(filepath)?;
+    parse_camt053(&data)
+}
+
+// entry fields collected while walking through one <Ntry> element
+#[derive(Default)]
+struct EntryBuilder {
+    amount: Option<f64>,
+    credit_debit: Option<CreditDebit>,
+    status: Option<String>,
+    booking_date: Option<NaiveDate>,
+    remittance_info: Vec<String>,
+    additional_info: Option<String>,
+    creditor_name: Option<String>,
+    debtor_name: Option<String>,
+}
+
+impl EntryBuilder {
+    fn build(self) -> Option<Transaction> {
+        // pending entries can still change, so they are not imported
+        if let Some(status) = &self.status {
+            if status != "BOOK" {
+                return None;
+            }
+        }
+
+        let credit_debit = self.credit_debit?;
+        let description = if self.remittance_info.is_empty() {
+            self.additional_info.unwrap_or_default()
+        } else {
+            self.remittance_info.join(" ")
+        };
+
+        // for spending the other party is the creditor, for refunds/income the debtor
+        let counterparty = match credit_debit {
+            CreditDebit::Debit => self.creditor_name,
+            CreditDebit::Credit => self.debtor_name,
+        };
+
+        Some(Transaction {
+            booking_date: self.booking_date?,
+            amount: self.amount?,
+            credit_debit,
+            description,
+            counterparty,
+            category: None,
+        })
+    }
+}
+
+// returns None if the xml is malformed, empty vector if there are no booked entries
+pub fn parse_camt053(data: &str) -> Option<Vec<Transaction>> {
+    let mut reader = Reader::from_str(data);
+    reader.trim_text(true);
+
+    let mut transactions: Vec<Transaction> = Vec::new();
+    let mut path: Vec<String> = Vec::new();
+    let mut entry: Option<EntryBuilder> = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(e)) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                if name == "Ntry" {
+                    entry = Some(EntryBuilder::default());
+                }
+                path.push(name);
+            }
+            Ok(Event::End(_)) => {
+                if path.pop().as_deref() == Some("Ntry") {
+                    if let Some(transaction) = entry.take().and_then(EntryBuilder::build) {
+                        transactions.push(transaction);
+                    }
+                }
+            }
+            Ok(Event::Text(text)) => {
+                if let Some(current) = entry.as_mut() {
+                    let value = text.unescape().ok()?.trim().to_string();
+                    collect_entry_field(current, &path, value);
+                }
+            }
+            Ok(Event::Eof) => break,
+            Ok(_) => {} // declarations, comments, empty elements are not needed
+            Err(e) => {
+                println!("Error parsing camt.053 file: {:?}", e);
+                return None;
+            }
+        }
+    }
+
+    Some(transactions)
+}
+
+// puts text of the current element into the entry depending on where we are in the tree
+fn collect_entry_field(entry: &mut EntryBuilder, path: &[String], value: String) {
+    // path relative to <Ntry>
+    let ntry_position = match path.iter().rposition(|name| name == "Ntry") {
+        Some(position) => position,
+        None => return,
+    };
+    let relative: Vec<&str> = path[ntry_position + 1..].iter().map(|s| s.as_str()).collect();
+
+    match relative.as_slice() {
+        ["Amt"] => entry.amount = parse_statement_amount(&value),
+        ["CdtDbtInd"] => {
+            entry.credit_debit = match value.as_str() {
+                "CRDT" => Some(CreditDebit::Credit),
+                "DBIT" => Some(CreditDebit::Debit),
+                _ => None,
+            }
+        }
+        ["Sts"] | ["Sts", "Cd"] => entry.status = Some(value),
+        ["BookgDt", "Dt"] | ["BookgDt", "DtTm"] => {
+            // DtTm looks like 2024-01-05T10:00:00, only the date part matters
+            entry.booking_date = value.get(..10).and_then(|date| NaiveDate::parse_from_str(date, "%Y-%m-%d").ok());
+        }
+        ["AddtlNtryInf"] => entry.additional_info = Some(value),
+        [.., "RmtInf", "Ustrd"] => entry.remittance_info.push(value),
+        [.., "RltdPties", "Cdtr", "Nm"] | [.., "RltdPties", "Cdtr", "Pty", "Nm"] => {
+            entry.creditor_name.get_or_insert(value);
+        }
+        [.., "RltdPties", "Dbtr", "Nm"] | [.., "RltdPties", "Dbtr", "Pty", "Nm"] => {
+            entry.debtor_name.get_or_insert(value);
+        }
+        _ => {}
+    }
+}
+
+// reads .xml file into a string
+fn read_xml_file_to_string(filepath: &str) -> Option<String> {
+    if Path::new(filepath).extension().and_then(|s| s.to_str()) != Some("xml") {
+        println!("File is not a .xml file");
+        return None;
+    }
+    match fs::read_to_string(filepath) {
+        Ok(data) => Some(data),
+        Err(e) => {
+            println!("Error reading or opening file: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const CAMT_SAMPLE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Document xmlns="urn:iso:std:iso:20022:tech:xsd:camt.053.001.02">
+  <BkToCstmrStmt>
+    <Stmt>
+      <Id>1</Id>
+      <Bal><Amt Ccy="EUR">1000.00</Amt><CdtDbtInd>CRDT</CdtDbtInd></Bal>
+      <Ntry>
+        <Amt Ccy="EUR">12.50</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>BOOK</Sts>
+        <BookgDt><Dt>2024-01-05</Dt></BookgDt>
+        <NtryDtls><TxDtls>
+          <AmtDtls><TxAmt><Amt Ccy="EUR">12.50</Amt></TxAmt></AmtDtls>
+          <RltdPties><Cdtr><Nm>Bakery &amp; Co</Nm></Cdtr></RltdPties>
+          <RmtInf><Ustrd>Card payment</Ustrd><Ustrd>Bakery</Ustrd></RmtInf>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">5.00</Amt>
+        <CdtDbtInd>CRDT</CdtDbtInd>
+        <Sts><Cd>BOOK</Cd></Sts>
+        <BookgDt><DtTm>2024-02-01T09:30:00</DtTm></BookgDt>
+        <AddtlNtryInf>Refund</AddtlNtryInf>
+        <NtryDtls><TxDtls>
+          <RltdPties><Dbtr><Pty><Nm>Shop</Nm></Pty></Dbtr></RltdPties>
+        </TxDtls></NtryDtls>
+      </Ntry>
+      <Ntry>
+        <Amt Ccy="EUR">99.00</Amt>
+        <CdtDbtInd>DBIT</CdtDbtInd>
+        <Sts>PDNG</Sts>
+        <BookgDt><Dt>2024-02-02</Dt></BookgDt>
+      </Ntry>
+    </Stmt>
+  </BkToCstmrStmt>
+</Document>"#;
+
+    #[test]
+    fn test_parse_camt053() {
+        let transactions = parse_camt053(CAMT_SAMPLE).unwrap();
+
+        // pending entry is skipped
+        assert_eq!(transactions.len(), 2);
+
+        let first = &transactions[0];
+        assert_eq!(first.amount, 12.5);
+        assert_eq!(first.credit_debit, CreditDebit::Debit);
+        assert_eq!(first.booking_date, NaiveDate::from_ymd_opt(2024, 1, 5).unwrap());
+        assert_eq!(first.description, "Card payment Bakery");
+        assert_eq!(first.counterparty.as_deref(), Some("Bakery & Co"));
+        assert!(first.category.is_none());
+
+        let second = &transactions[1];
+        assert_eq!(second.credit_debit, CreditDebit::Credit);
+        assert_eq!(second.booking_date, NaiveDate::from_ymd_opt(2024, 2, 1).unwrap());
+        assert_eq!(second.description, "Refund");
+        assert_eq!(second.counterparty.as_deref(), Some("Shop"));
+    }
+
+    #[test]
+    fn test_parse_camt053_malformed() {
+        assert!(parse_camt053("<Document><Ntry></Document>").is_none());
+    }
+
+    #[test]
+    fn test_transform_camt_file_into_me_structs() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("statement.xml");
+        fs::write(&file_path, CAMT_SAMPLE).unwrap();
+
+        let result = transform_camt_file_into_me_structs(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].month, "January");
+        assert_eq!(result[1].month, "February");
+
+        // wrong extension is rejected
+        let wrong_path = temp_dir.path().join("statement.txt");
+        fs::write(&wrong_path, CAMT_SAMPLE).unwrap();
+        assert!(transform_camt_file_into_me_structs(wrong_path.to_str().unwrap()).is_none());
+    }
+}
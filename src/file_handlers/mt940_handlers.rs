@@ -0,0 +1,283 @@
+use std::{fs, path::Path};
+use chrono::{Datelike, NaiveDate};
+
+use crate::utils::structs::{CreditDebit, MonthExpenses, Transaction};
+use crate::utils::transaction_utils::{parse_statement_amount, transactions_into_me_structs};
+
+// SWIFT MT940 statement importer.
+// Every booked entry is a :61: line optionally followed by a :86: line:
+//  :61:2401050105D12,50NMSCNONREF//123
+//      ^^^^^^                          value date YYMMDD
+//            ^^^^                      entry (booking) date MMDD, optional
+//                ^                     D / C / RD / RC mark
+//                 ^^^^^                amount with decimal comma
+//  :86:166?00CARD PAYMENT?20Bakery?32Bakery GmbH
+//      free text, or ?xx subfields (?20-?29 remittance info, ?32-?33 counterparty)
+// Fields can continue on the next lines until the next :tag:
+
+const MT940_EXTENSIONS: [&str; 3] = ["sta", "mt940", "940"];
+
+// receives filepath of a MT940 file and groups its entries per month
+pub fn transform_mt940_file_into_me_structs(filepath: &str) -> Option<Vec<MonthExpenses>> {
+    let transactions = transform_mt940_file_into_transactions(filepath)?;
+    Some(transactions_into_me_structs(&transactions))
+}
+
+pub fn transform_mt940_file_into_transactions(filepath: &str) -> Option<Vec<Transaction>> {
+    let data = read_mt940_file_to_string(filepath)?;
+    parse_mt940(&data)
+}
+
+// returns None if a :61: line can't be parsed
+pub fn parse_mt940(data: &str) -> Option<Vec<Transaction>> {
+    let mut transactions: Vec<Transaction> = Vec::new();
+
+    for (tag, value) in split_into_fields(data) {
+        match tag.as_str() {
+            "61" => {
+                let transaction = parse_statement_line(&value);
+                if transaction.is_none() {
+                    println!("Cannot parse MT940 statement line: {}", value);
+                }
+                transactions.push(transaction?);
+            }
+            "86" => {
+                // information to account owner belongs to the previous :61:
+                if let Some(last) = transactions.last_mut() {
+                    let (description, counterparty) = parse_information_to_owner(&value);
+                    last.description = description;
+                    last.counterparty = counterparty;
+                }
+            }
+            _ => {} // balances, account id etc. are not needed
+        }
+    }
+
+    Some(transactions)
+}
+
+// splits the statement into (tag, value) pairs, continuation lines are joined to the value
+fn split_into_fields(data: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+
+    for line in data.lines() {
+        let line = line.trim_end();
+        if let Some(rest) = line.strip_prefix(':') {
+            if let Some((tag, value)) = rest.split_once(':') {
+                fields.push((tag.to_string(), value.to_string()));
+                continue;
+            }
+        }
+        // "-" ends a message, SWIFT block headers are ignored as well
+        if line.is_empty() || line.starts_with('-') || line.starts_with('{') {
+            continue;
+        }
+        if let Some((_, value)) = fields.last_mut() {
+            value.push_str(line);
+        }
+    }
+
+    fields
+}
+
+// parses value of a :61: field
+fn parse_statement_line(value: &str) -> Option<Transaction> {
+    let value_date = NaiveDate::parse_from_str(value.get(..6)?, "%y%m%d").ok()?;
+    let mut rest = value.get(6..)?;
+
+    // optional booking date MMDD, year is taken from the value date
+    // (sliced with get, a non-ASCII character there must not panic the import)
+    let mut booking_date = value_date;
+    if let Some(booking) = rest.get(..4).filter(|booking| booking.chars().all(|c| c.is_ascii_digit())) {
+        let month: u32 = booking[..2].parse().ok()?;
+        let day: u32 = booking[2..].parse().ok()?;
+        let mut year = value_date.year();
+        // booked in December, valued in January (and the other way around)
+        if month == 12 && value_date.month() == 1 {
+            year -= 1;
+        } else if month == 1 && value_date.month() == 12 {
+            year += 1;
+        }
+        booking_date = NaiveDate::from_ymd_opt(year, month, day)?;
+        rest = &rest[4..];
+    }
+
+    // reversals flip the direction of the original entry
+    let (credit_debit, rest) = if let Some(rest) = rest.strip_prefix("RD") {
+        (CreditDebit::Credit, rest)
+    } else if let Some(rest) = rest.strip_prefix("RC") {
+        (CreditDebit::Debit, rest)
+    } else if let Some(rest) = rest.strip_prefix('D') {
+        (CreditDebit::Debit, rest)
+    } else if let Some(rest) = rest.strip_prefix('C') {
+        (CreditDebit::Credit, rest)
+    } else {
+        return None;
+    };
+
+    // optional funds code (third letter of the currency code)
+    let rest = match rest.chars().next() {
+        Some(c) if c.is_ascii_alphabetic() => &rest[1..],
+        _ => rest,
+    };
+
+    let amount_end = rest.find(|c: char| !(c.is_ascii_digit() || c == ',')).unwrap_or(rest.len());
+    let amount = parse_statement_amount(&rest[..amount_end])?;
+
+    Some(Transaction {
+        booking_date,
+        amount,
+        credit_debit,
+        description: String::new(),
+        counterparty: None,
+        category: None,
+    })
+}
+
+// returns (description, counterparty) from a :86: field
+fn parse_information_to_owner(value: &str) -> (String, Option<String>) {
+    // unstructured free text
+    if !value.contains('?') {
+        return (value.trim().to_string(), None);
+    }
+
+    let mut remittance_info: Vec<&str> = Vec::new();
+    let mut counterparty: Vec<&str> = Vec::new();
+
+    // first part before "?" is the transaction code
+    for subfield in value.split('?').skip(1) {
+        // code is two characters, by chars so umlauts right after "?" don't split a character
+        let code_end = subfield.char_indices().nth(2).map_or(subfield.len(), |(index, _)| index);
+        let (code, text) = subfield.split_at(code_end);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match code {
+            "20" | "21" | "22" | "23" | "24" | "25" | "26" | "27" | "28" | "29" => remittance_info.push(text),
+            "32" | "33" => counterparty.push(text),
+            _ => {}
+        }
+    }
+
+    let counterparty = if counterparty.is_empty() { None } else { Some(counterparty.concat()) };
+    (remittance_info.join(" "), counterparty)
+}
+
+// reads MT940 file (.sta, .mt940 or .940) into a string
+fn read_mt940_file_to_string(filepath: &str) -> Option<String> {
+    let extension = Path::new(filepath).extension().and_then(|s| s.to_str()).unwrap_or_default().to_lowercase();
+    if !MT940_EXTENSIONS.contains(&extension.as_str()) {
+        println!("File is not a MT940 file");
+        return None;
+    }
+    // banks still export these in latin-1, anything that isn't valid utf-8 is read as latin-1
+    match fs::read(filepath) {
+        Ok(bytes) => Some(String::from_utf8(bytes).unwrap_or_else(|e| e.into_bytes().iter().map(|&b| b as char).collect())),
+        Err(e) => {
+            println!("Error reading or opening file: {}", e);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    const MT940_SAMPLE: &str = ":20:STARTUMS
+:25:10020030/1234567
+:28C:00001/001
+:60F:C231229EUR1000,00
+:61:2401021229D12,50NMSCNONREF//123
+:86:166?00KARTENZAHLUNG?20Bakery?21Croissants?32Bakery GmbH
+:61:240115C2500,00NTRFNONREF
+:86:Salary January
+:61:240120RD3,10NMSCNONREF
+:86:166?00STORNO?20Card fee re
+versal
+:62F:C240131EUR3490,60
+-";
+
+    #[test]
+    fn test_parse_mt940() {
+        let transactions = parse_mt940(MT940_SAMPLE).unwrap();
+        assert_eq!(transactions.len(), 3);
+
+        // booked in December 2023, valued in January 2024
+        let first = &transactions[0];
+        assert_eq!(first.booking_date, NaiveDate::from_ymd_opt(2023, 12, 29).unwrap());
+        assert_eq!(first.credit_debit, CreditDebit::Debit);
+        assert_eq!(first.amount, 12.5);
+        assert_eq!(first.description, "Bakery Croissants");
+        assert_eq!(first.counterparty.as_deref(), Some("Bakery GmbH"));
+
+        let second = &transactions[1];
+        assert_eq!(second.booking_date, NaiveDate::from_ymd_opt(2024, 1, 15).unwrap());
+        assert_eq!(second.credit_debit, CreditDebit::Credit);
+        assert_eq!(second.amount, 2500.0);
+        assert_eq!(second.description, "Salary January");
+        assert!(second.counterparty.is_none());
+
+        // reversal of a debit is money coming back, continuation line is joined
+        let third = &transactions[2];
+        assert_eq!(third.credit_debit, CreditDebit::Credit);
+        assert_eq!(third.amount, 3.1);
+        assert_eq!(third.description, "Card fee reversal");
+    }
+
+    #[test]
+    fn test_parse_mt940_broken_line() {
+        assert!(parse_mt940(":61:24XX02D12,50NMSC").is_none());
+    }
+
+    #[test]
+    fn test_parse_mt940_non_ascii() {
+        // umlauts where the booking date and the subfield code are sliced off
+        assert!(parse_mt940(":61:240102012äD12,50NMSC").is_none());
+        let transactions = parse_mt940(":61:240102D12,50NMSC\n:86:166?2ü?20Bäckerei").unwrap();
+        assert_eq!(transactions[0].description, "Bäckerei");
+    }
+
+    #[test]
+    fn test_read_latin1_file() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("statement.sta");
+        // "Bäckerei Müller" and "Straße" in latin-1
+        let mut data = b":61:240102D12,50NMSC\n:86:166?20Stra".to_vec();
+        data.extend([0xdf]);
+        data.extend(b"e 1?32B");
+        data.extend([0xe4]);
+        data.extend(b"ckerei M");
+        data.extend([0xfc]);
+        data.extend(b"ller\n");
+        fs::write(&file_path, data).unwrap();
+
+        let transactions = transform_mt940_file_into_transactions(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(transactions[0].description, "Straße 1");
+        assert_eq!(transactions[0].counterparty.as_deref(), Some("Bäckerei Müller"));
+
+        // utf-8 files are read as they are
+        fs::write(&file_path, ":61:240102D12,50NMSC\n:86:Straße").unwrap();
+        let transactions = transform_mt940_file_into_transactions(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(transactions[0].description, "Straße");
+    }
+
+    #[test]
+    fn test_transform_mt940_file_into_me_structs() {
+        let temp_dir = tempdir().unwrap();
+        let file_path = temp_dir.path().join("statement.sta");
+        fs::write(&file_path, MT940_SAMPLE).unwrap();
+
+        let result = transform_mt940_file_into_me_structs(file_path.to_str().unwrap()).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!((result[0].year, result[0].month.as_str()), (2023, "December"));
+        assert_eq!((result[1].year, result[1].month.as_str()), (2024, "January"));
+
+        // wrong extension is rejected
+        let wrong_path = temp_dir.path().join("statement.pdf");
+        fs::write(&wrong_path, MT940_SAMPLE).unwrap();
+        assert!(transform_mt940_file_into_me_structs(wrong_path.to_str().unwrap()).is_none());
+    }
+}
@@ -1,15 +1,26 @@
 use std::{collections::HashMap, fs::File, io::Read, path::Path};
-use crate::utils::structs::MonthExpenses;
+use chrono::NaiveDate;
+use crate::utils::structs::{CreditDebit, MonthExpenses, Transaction};
+use crate::utils::transaction_utils::month_number_from_name;
+
+// income in notes: everything after an "Income" line until an "Expenses" line, or a single
+// category written as "+Salary". numbers under it are money in
+const INCOME_SECTION: &str = "Income";
+const EXPENSES_SECTION: &str = "Expenses";
+const INCOME_PREFIX: char = '+';
 
 pub fn show_debug_data_from_file(filepath: &str) -> String {
     if let Some(data) = transform_file_into_me_struct(filepath) {
         let sum: f64 = data.expenses_data.values().sum();
 
-        let result = format!(
+        let mut result = format!(
             "Total is {:.2}\nMetaData is {} {}\nBy category:\n{:?}", sum,
             data.year, data.month,
             serde_json::to_string_pretty(&data.expenses_data)
         );
+        if !data.income_data.is_empty() {
+            result.push_str(&format!("\nIncome:\n{:?}", serde_json::to_string_pretty(&data.income_data)));
+        }
 
         return result;
     }
@@ -31,11 +42,12 @@ pub fn transform_file_into_me_struct(filepath: &str) -> Option<MonthExpenses> {
             let month = parts[0].to_string();
             let year = parts[1].parse::<i64>().ok()?;
 
-            if let Some(expenses_data) = put_data_into_hashmap(&data){
+            if let Some((expenses_data, income_data)) = put_data_into_hashmap(&data){
                 return Some(MonthExpenses {
                     year,
                     month,
                     expenses_data,
+                    income_data,
                 });
             }
         }
@@ -44,46 +56,92 @@ pub fn transform_file_into_me_struct(filepath: &str) -> Option<MonthExpenses> {
     None
 }
 
+// same as transform_file_into_me_struct, but keeps every number as a separate transaction
+// notes don't have dates, so every entry is booked on the first day of the month
+// category is already known from the note, negative numbers are refunds
+// income entries are credits (negative ones debits), the preview tells income sources apart
+pub fn transform_file_into_transactions(filepath: &str) -> Option<Vec<Transaction>> {
+    let me = transform_file_into_me_struct(filepath)?;
+    let month = month_number_from_name(&me.month)?;
+    let booking_date = NaiveDate::from_ymd_opt(me.year as i32, month, 1)?;
+
+    let data = read_txt_file_to_string(filepath)?;
+    let mut transactions: Vec<Transaction> = Vec::new();
+    for_each_entry(&data, |category, amount, income| {
+        let money_in = if income { amount > 0.0 } else { amount < 0.0 };
+        transactions.push(Transaction {
+            booking_date,
+            amount: amount.abs(),
+            credit_debit: if money_in { CreditDebit::Credit } else { CreditDebit::Debit },
+            description: category.to_string(),
+            counterparty: None,
+            category: Some(category.to_string()),
+        });
+    });
+
+    Some(transactions)
+}
+
 // receives string slice with expected structure:
 //  first line meta_data (month, year) not important
 //  other lines should have format:
 //      string (category) or float (expenses) or empty line (delimiter)
 // there can be multiple entries of expenses followed by delimiter and next category after it
-fn put_data_into_hashmap(data: &str) -> Option<HashMap<String, f64>> {
+// returns expenses and income by category, None if there are neither
+fn put_data_into_hashmap(data: &str) -> Option<(HashMap<String, f64>, HashMap<String, f64>)> {
     let mut expenses_by_category: HashMap<String, f64> = HashMap::new();
-    let mut lines = data.lines();
+    let mut income_by_source: HashMap<String, f64> = HashMap::new();
+
+    for_each_entry(data, |category, amount, income| {
+        let totals = if income { &mut income_by_source } else { &mut expenses_by_category };
+        *totals.entry(category.to_string()).or_insert(0.0) += amount;
+    });
+
+    // Round each total to two decimal places
+    for value in expenses_by_category.values_mut().chain(income_by_source.values_mut()) {
+        *value = (*value * 100.0).round() / 100.0;
+    }
 
-    // Skip the first line (month and year)
-    let _meta_data = lines.next();
+    if expenses_by_category.is_empty() && income_by_source.is_empty() {
+        None
+    } else {
+        Some((expenses_by_category, income_by_source))
+    }
+}
 
+// every number of the notes with its category and whether it is income,
+// the first line (month and year) is skipped
+fn for_each_entry(data: &str, mut entry: impl FnMut(&str, f64, bool)) {
     let mut current_category = String::new();
-    for line in lines {
+    let mut current_is_income = false;
+    let mut in_income_section = false;
+
+    for line in data.lines().skip(1) {
         let trimmed_line = line.trim();
         if trimmed_line.is_empty() {
             continue; // Skip empty lines
         }
 
         if let Ok(amount) = trimmed_line.parse::<f64>() {
-            // If the line is a number, add it to the current category total
+            // If the line is a number, it belongs to the current category
             if !current_category.is_empty() {
-                *expenses_by_category.entry(current_category.clone()).or_insert(0.0) += amount;
+                entry(&current_category, amount, current_is_income);
             }
+        } else if trimmed_line.eq_ignore_ascii_case(INCOME_SECTION) {
+            in_income_section = true;
+            current_category.clear();
+        } else if trimmed_line.eq_ignore_ascii_case(EXPENSES_SECTION) {
+            in_income_section = false;
+            current_category.clear();
+        } else if let Some(source) = trimmed_line.strip_prefix(INCOME_PREFIX) {
+            current_category = source.trim().to_string();
+            current_is_income = true;
         } else {
             // If the line is not a number, it’s a new category
             current_category = trimmed_line.to_string();
+            current_is_income = in_income_section;
         }
     }
-
-    // Round each total to two decimal places
-    for value in expenses_by_category.values_mut() {
-        *value = (*value * 100.0).round() / 100.0;
-    }
-
-    if expenses_by_category.is_empty() {
-        None
-    } else {
-        Some(expenses_by_category)
-    }
 }
 
 
@@ -182,4 +240,41 @@ mod tests {
 
         assert_eq!(show_debug_data_from_file(test_file_path), "")
     }
+
+    #[test]
+    fn test_transform_file_into_transactions() {
+        let test_file_path = "test3.txt";
+        write(test_file_path, "March 2024\n\nGroceries\n12.5\n-2.5\n\nSweets\n4\n").expect("File write is failed");
+
+        let transactions = transform_file_into_transactions(test_file_path).unwrap();
+        std::fs::remove_file(test_file_path).expect("Failed to delete test file");
+
+        assert_eq!(transactions.len(), 3);
+        assert_eq!(transactions[0].booking_date, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(transactions[0].category.as_deref(), Some("Groceries"));
+        assert_eq!(transactions[1].credit_debit, CreditDebit::Credit);
+        assert_eq!(transactions[1].amount, 2.5);
+        assert_eq!(transactions[2].category.as_deref(), Some("Sweets"));
+    }
+
+    #[test]
+    fn test_income_in_notes() {
+        let test_file_path = "test4.txt";
+        let contents = "March 2024\n\nGroceries\n12.5\n\n+Interest\n1.2\n\nIncome\nSalary\n3000\n-40\n\nExpenses\nSweets\n4\n";
+        write(test_file_path, contents).expect("File write is failed");
+
+        let me = transform_file_into_me_struct(test_file_path).unwrap();
+        let transactions = transform_file_into_transactions(test_file_path).unwrap();
+        std::fs::remove_file(test_file_path).expect("Failed to delete test file");
+
+        assert_eq!(me.expenses_data, HashMap::from([("Groceries".to_string(), 12.5), ("Sweets".to_string(), 4.0)]));
+        assert_eq!(me.income_data, HashMap::from([("Interest".to_string(), 1.2), ("Salary".to_string(), 2960.0)]));
+
+        let income: Vec<(&str, CreditDebit)> = transactions
+            .iter()
+            .filter(|t| t.category.as_deref() != Some("Groceries") && t.category.as_deref() != Some("Sweets"))
+            .map(|t| (t.description.as_str(), t.credit_debit))
+            .collect();
+        assert_eq!(income, vec![("Interest", CreditDebit::Credit), ("Salary", CreditDebit::Credit), ("Salary", CreditDebit::Debit)]);
+    }
 }
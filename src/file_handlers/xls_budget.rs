@@ -0,0 +1,164 @@
+// optional "Budget" sheet, written next to Sheet1 on every update when budget_sheet is set in
+// the config and there are budgets. every year block gets three rows per budgeted category,
+// months in columns:
+//  budget of the month, actual as a formula over Sheet1, variance (actual - budget, positive = overspent)
+// months without an actual are empty, so the Year column is the budget of the whole year next to
+// what was spent and the variance so far.
+// like the summary sheet it is written from scratch every time
+
+use xlsxwriter::prelude::*;
+
+use crate::file_handlers::xls_handlers::{xls_year_blocks, SheetCells};
+use crate::file_handlers::xls_summary::{block_row, cell_name, write_formula, DATA_SHEET_NAME};
+use crate::stats::budgets::{budget_for, Budget};
+use crate::stats::series::YearMonth;
+use crate::utils::config::load_config;
+use crate::utils::transaction_utils::month_name_from_number;
+
+pub const BUDGET_SHEET_NAME: &str = "Budget";
+const YEAR_COLUMN: u16 = 13; // right of December
+
+// budget sheet if the config asks for it, a failing budget sheet never stops the update
+pub fn xls_add_configured_budget_sheet(workbook: &Workbook, destination_path: &str, cells: &SheetCells) {
+    let config = load_config(destination_path);
+    if !config.budget_sheet || config.budgets.is_empty() {
+        return;
+    }
+    if let Err(e) = xls_write_budget_sheet(workbook, cells, &config.budgets) {
+        println!("Failed to write budget sheet: {:?}", e);
+    }
+}
+
+// cells are the ones of Sheet1 as it is written into the same workbook
+pub fn xls_write_budget_sheet(workbook: &Workbook, cells: &SheetCells, budgets: &[Budget]) -> Result<(), XlsxError> {
+    let mut sheet = workbook.add_worksheet(Some(BUDGET_SHEET_NAME))?;
+    let mut bold = Format::new();
+    bold.set_bold();
+
+    let mut row = 0;
+    for (index, block) in xls_year_blocks(cells).iter().enumerate() {
+        let budgeted: Vec<(usize, &Budget)> = block
+            .categories
+            .iter()
+            .enumerate()
+            .filter_map(|(category_index, category)| Some((category_index, budget_for(budgets, category)?)))
+            .collect();
+        if budgeted.is_empty() {
+            continue;
+        }
+
+        // 2024 | January ... December | Year
+        sheet.write_number(row, 0, block.year as f64, Some(&bold))?;
+        for month in 1..=12 {
+            sheet.write_string(row, month as u16, month_name_from_number(month).unwrap_or_default(), Some(&bold))?;
+        }
+        sheet.write_string(row, YEAR_COLUMN, "Year", Some(&bold))?;
+        row += 1;
+
+        for (category_index, budget) in budgeted {
+            let (budget_row, actual_row, variance_row) = (row, row + 1, row + 2);
+            sheet.write_string(budget_row, 0, &format!("{} budget", budget.category), None)?;
+            sheet.write_string(actual_row, 0, &format!("{} actual", budget.category), None)?;
+            sheet.write_string(variance_row, 0, &format!("{} variance", budget.category), None)?;
+
+            let data_col = block.category_cols[category_index];
+            let (mut actual_total, mut variance_total) = (0.0, 0.0);
+            for month in 1..=12 {
+                let col = month as u16;
+                let amount = budget.amount_for(YearMonth::new(block.year, month));
+                let actual = block.months[month as usize - 1][category_index];
+                sheet.write_number(budget_row, col, amount, None)?;
+
+                let source = format!("{}!{}", DATA_SHEET_NAME, cell_name(block_row(index) + month, data_col));
+                write_formula(&mut sheet, actual_row, col, Some(format!("=IF({0}=\"\",\"\",{0})", source)), actual)?;
+                let (actual_cell, budget_cell) = (cell_name(actual_row, month), cell_name(budget_row, month));
+                let variance = format!("=IF({0}=\"\",\"\",{0}-{1})", actual_cell, budget_cell);
+                write_formula(&mut sheet, variance_row, col, Some(variance), actual.map(|actual| actual - amount))?;
+
+                if let Some(actual) = actual {
+                    actual_total += actual;
+                    variance_total += actual - amount;
+                }
+            }
+
+            let year_total = [
+                (budget_row, budget.amount_for_year(block.year)),
+                (actual_row, actual_total),
+                (variance_row, variance_total),
+            ];
+            for (total_row, value) in year_total {
+                let formula = format!("=SUM({}:{})", cell_name(total_row, 1), cell_name(total_row, 12));
+                write_formula(&mut sheet, total_row, YEAR_COLUMN, Some(formula), Some(value))?;
+            }
+            row += 3;
+        }
+        row += 1; // blank row between years
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::{xls_perform_cells_update, CellValue, RES_WORKBOOK_NAME};
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::utils::config::{save_config, AppConfig};
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn groceries() -> Budget {
+        Budget { category: "Groceries".to_string(), monthly: 90.0, overrides: BTreeMap::new() }
+    }
+
+    #[test]
+    fn test_xls_write_budget_sheet() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("budget.xlsx");
+        let mut cells = SheetCells::new();
+        cells.insert((1, 1), CellValue::Number(2024.0));
+        cells.insert((1, 2), CellValue::Text("Rent".to_string()));
+        cells.insert((1, 3), CellValue::Text("Groceries".to_string()));
+        cells.insert((2, 3), CellValue::Number(100.0)); // January groceries
+
+        let workbook = Workbook::new(path.to_str().unwrap()).unwrap();
+        workbook.add_worksheet(Some("Sheet1")).unwrap();
+        xls_write_budget_sheet(&workbook, &cells, &[groceries()]).unwrap();
+        workbook.close().unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        let formulas = workbook.worksheet_formula(BUDGET_SHEET_NAME).unwrap().unwrap();
+        let values = workbook.worksheet_range(BUDGET_SHEET_NAME).unwrap().unwrap();
+        let formula = |row: u32, col: u32| formulas.get_value((row, col)).cloned().unwrap_or_default();
+
+        // only Groceries has a budget, its column in Sheet1 is D
+        assert_eq!(values.get_value((1, 0)), Some(&DataType::String("Groceries budget".to_string())));
+        assert_eq!(values.get_value((1, 1)), Some(&DataType::Float(90.0)));
+        assert_eq!(formula(2, 1), "IF(Sheet1!D3=\"\",\"\",Sheet1!D3)");
+        assert_eq!(formula(3, 1), "IF(B3=\"\",\"\",B3-B2)");
+        assert_eq!(values.get_value((3, 1)), Some(&DataType::Float(10.0)));
+        assert_eq!(values.get_value((1, 13)), Some(&DataType::Float(1080.0)));
+        assert_eq!(values.get_value((3, 13)), Some(&DataType::Float(10.0))); // so far
+        assert_eq!(values.get_value((4, 0)), None);
+    }
+
+    #[test]
+    fn test_budget_sheet_follows_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        let sheet_names = || {
+            let workbook: Xlsx<_> = open_workbook(temp_dir.path().join(RES_WORKBOOK_NAME)).unwrap();
+            workbook.sheet_names().to_vec()
+        };
+
+        // needs budgets too
+        save_config(destination, &AppConfig { budget_sheet: true, ..AppConfig::default() }).unwrap();
+        xls_perform_cells_update(&HashMap::from([((2, 3), Some(5.0))]), destination).unwrap();
+        assert_eq!(sheet_names(), vec!["Sheet1"]);
+
+        let config = AppConfig { budget_sheet: true, budgets: vec![groceries()], ..AppConfig::default() };
+        save_config(destination, &config).unwrap();
+        xls_perform_cells_update(&HashMap::from([((3, 2), Some(7.0))]), destination).unwrap();
+        assert_eq!(sheet_names(), vec!["Sheet1", BUDGET_SHEET_NAME]);
+    }
+}
@@ -0,0 +1,446 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use zip::ZipArchive;
+
+use crate::file_handlers::xls_budget::BUDGET_SHEET_NAME;
+use crate::file_handlers::xls_summary::SUMMARY_SHEET_NAME;
+
+// what of the workbook survives an update. xlsxwriter can't edit a file, every import and undo
+// writes the workbook anew: values and formulas of every sheet as calamine reads them, and the
+// number formats of their cells (dates, currencies, 0.00), read here from the xml. anything else
+// the user added would be lost:
+//  other formatting (fonts, fills, borders, alignment), column widths, row heights, merged
+//  cells, conditional formatting, data validation, charts and pictures, comments, links,
+//  tables, filters, frozen panes, named ranges, chart sheets ...
+// such a workbook is not written at all, see xls_check_carry_over
+// Summary and Budget are the app's own sheets, they are written anew anyway
+
+// dates calamine reads without a number format of their own
+pub const DEFAULT_DATE_FORMAT: &str = "yyyy-mm-dd";
+
+#[derive(Clone, Debug, Default)]
+pub struct CarryOver {
+    pub lost: Vec<String>, // "Sheet1: column widths", empty when the workbook can be written
+    number_formats: HashMap<String, HashMap<(u32, u32), String>>, // by sheet, then (row, col)
+}
+
+impl CarryOver {
+    // number format of a cell, None for General
+    pub fn number_format(&self, sheet: &str, row: u32, col: u32) -> Option<&str> {
+        self.number_formats.get(sheet)?.get(&(row, col)).map(String::as_str)
+    }
+}
+
+// how a cell style comes through an update
+#[derive(Clone, Debug, PartialEq)]
+enum CellStyle {
+    Plain,
+    NumberFormat(String), // written again with the cell
+    Formatted,
+}
+
+// the workbook as far as an update keeps it, Err when it can't be written without losing something
+pub fn xls_check_carry_over(path_to_workbook: &str) -> Result<CarryOver, String> {
+    let carry_over = xls_read_carry_over(path_to_workbook)?;
+    if carry_over.lost.is_empty() {
+        return Ok(carry_over);
+    }
+    Err(format!(
+        "The spreadsheet has content the app can't keep when writing it ({}), remove it or keep it in another workbook",
+        carry_over.lost.join("; ")
+    ))
+}
+
+pub fn xls_read_carry_over(path_to_workbook: &str) -> Result<CarryOver, String> {
+    let file = File::open(path_to_workbook).map_err(|e| format!("Cannot open the spreadsheet: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(|e| format!("Cannot read the spreadsheet: {}", e))?;
+
+    let workbook_xml = zip_text(&mut archive, "xl/workbook.xml").ok_or("Cannot read the spreadsheet, no workbook.xml")?;
+    let relations = read_relations(&zip_text(&mut archive, "xl/_rels/workbook.xml.rels").unwrap_or_default());
+    let styles = read_cell_styles(&zip_text(&mut archive, "xl/styles.xml").unwrap_or_default());
+
+    let mut carry_over = CarryOver::default();
+    let (sheets, named_ranges) = read_sheets(&workbook_xml);
+    if named_ranges {
+        carry_over.lost.push("named ranges".to_string());
+    }
+    let shared_strings = zip_text(&mut archive, "xl/sharedStrings.xml").unwrap_or_default();
+    if has_element(&shared_strings, b"rPr") {
+        carry_over.lost.push("formatted text".to_string());
+    }
+
+    for sheet in sheets {
+        if sheet.name == SUMMARY_SHEET_NAME || sheet.name == BUDGET_SHEET_NAME {
+            continue;
+        }
+        let Some((kind, target)) = relations.get(&sheet.relation) else {
+            continue;
+        };
+        let mut lost = Vec::new();
+        if sheet.hidden {
+            lost.push("hidden sheet");
+        }
+        if kind != "worksheet" {
+            lost.push("not a worksheet");
+        } else {
+            let sheet_xml = zip_text(&mut archive, target).ok_or(format!("Cannot read sheet {} of the spreadsheet", sheet.name))?;
+            let number_formats = scan_sheet(&sheet_xml, &styles, &mut lost);
+            carry_over.number_formats.insert(sheet.name.clone(), number_formats);
+        }
+        if !lost.is_empty() {
+            carry_over.lost.push(format!("{}: {}", sheet.name, lost.join(", ")));
+        }
+    }
+    Ok(carry_over)
+}
+
+struct SheetEntry {
+    name: String,
+    relation: String, // r:id in workbook.xml
+    hidden: bool,
+}
+
+fn zip_text(archive: &mut ZipArchive<File>, name: &str) -> Option<String> {
+    let mut file = archive.by_name(name).ok()?;
+    let mut text = String::new();
+    file.read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+// attributes by their local name, "r:id" is "id"
+fn attributes(element: &BytesStart) -> HashMap<String, String> {
+    element
+        .attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            let value = unescape(&String::from_utf8_lossy(&attribute.value)).ok()?.into_owned();
+            Some((key, value))
+        })
+        .collect()
+}
+
+// tags of a part, <a> and <a/> both open, only the first one has content
+enum Tag<'a> {
+    Open(&'a [u8], HashMap<String, String>, bool),
+    Close(&'a [u8]),
+}
+
+fn for_each_tag(xml: &str, mut visit: impl FnMut(Tag)) {
+    let mut reader = Reader::from_str(xml);
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(element)) => visit(Tag::Open(element.local_name().as_ref(), attributes(&element), true)),
+            Ok(Event::Empty(element)) => visit(Tag::Open(element.local_name().as_ref(), attributes(&element), false)),
+            Ok(Event::End(element)) => visit(Tag::Close(element.local_name().as_ref())),
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+    }
+}
+
+fn has_element(xml: &str, name: &[u8]) -> bool {
+    let mut found = false;
+    for_each_tag(xml, |tag| found |= matches!(tag, Tag::Open(element, _, _) if element == name));
+    found
+}
+
+fn note(lost: &mut Vec<&'static str>, what: &'static str) {
+    if !lost.contains(&what) {
+        lost.push(what);
+    }
+}
+
+fn is_set(value: Option<&String>) -> bool {
+    matches!(value.map(String::as_str), Some("1") | Some("true"))
+}
+
+// sheets in their order, and whether the workbook has named ranges
+fn read_sheets(workbook_xml: &str) -> (Vec<SheetEntry>, bool) {
+    let mut sheets = Vec::new();
+    let mut named_ranges = false;
+    for_each_tag(workbook_xml, |tag| match tag {
+        Tag::Open(b"sheet", attributes, _) => sheets.push(SheetEntry {
+            name: attributes.get("name").cloned().unwrap_or_default(),
+            relation: attributes.get("id").cloned().unwrap_or_default(),
+            hidden: attributes.get("state").is_some_and(|state| state != "visible"),
+        }),
+        Tag::Open(b"definedName", _, _) => named_ranges = true,
+        _ => {}
+    });
+    (sheets, named_ranges)
+}
+
+// relation id -> (kind, path in the archive), kind is the end of its type ("worksheet", "chartsheet")
+fn read_relations(relations_xml: &str) -> HashMap<String, (String, String)> {
+    let mut relations = HashMap::new();
+    for_each_tag(relations_xml, |tag| {
+        let Tag::Open(b"Relationship", attributes, _) = tag else {
+            return;
+        };
+        let (Some(id), Some(kind), Some(target)) = (attributes.get("Id"), attributes.get("Type"), attributes.get("Target")) else {
+            return;
+        };
+        let kind = kind.rsplit('/').next().unwrap_or_default().to_string();
+        // targets are relative to xl/, unless they start at the root
+        let path = match target.strip_prefix('/') {
+            Some(path) => path.to_string(),
+            None => format!("xl/{}", target),
+        };
+        relations.insert(id.clone(), (kind, path));
+    });
+    relations
+}
+
+// styles of cells by their s="" index
+fn read_cell_styles(styles_xml: &str) -> Vec<CellStyle> {
+    let mut number_formats: HashMap<String, String> = HashMap::new(); // custom ones by id
+    let mut xfs: Vec<(String, bool)> = Vec::new(); // number format id, formatted otherwise
+    let mut in_cell_xfs = false;
+    let mut in_xf = false;
+    for_each_tag(styles_xml, |tag| match tag {
+        Tag::Open(b"numFmt", attributes, _) => {
+            if let (Some(id), Some(code)) = (attributes.get("numFmtId"), attributes.get("formatCode")) {
+                number_formats.insert(id.clone(), code.clone());
+            }
+        }
+        Tag::Open(b"cellXfs", _, has_content) => in_cell_xfs = has_content,
+        Tag::Open(b"xf", attributes, has_content) if in_cell_xfs => {
+            in_xf = has_content;
+            let formatted = ["fontId", "fillId", "borderId"]
+                .iter()
+                .any(|key| attributes.get(*key).is_some_and(|id| id != "0"));
+            xfs.push((attributes.get("numFmtId").cloned().unwrap_or_else(|| "0".to_string()), formatted));
+        }
+        // LibreOffice writes them with the defaults
+        Tag::Open(b"alignment" | b"protection", attributes, _)
+            if in_xf && !attributes.iter().all(|(key, value)| is_default_attribute(key, value)) =>
+        {
+            if let Some(xf) = xfs.last_mut() {
+                xf.1 = true;
+            }
+        }
+        Tag::Close(b"cellXfs") => in_cell_xfs = false,
+        Tag::Close(b"xf") => in_xf = false,
+        _ => {}
+    });
+
+    xfs.into_iter()
+        .map(|(id, formatted)| {
+            if formatted {
+                return CellStyle::Formatted;
+            }
+            // ids without a code of their own are built in, some of them depend on the locale
+            match number_formats.get(&id).map(String::as_str).or_else(|| builtin_number_format(&id)) {
+                Some(code) if code.eq_ignore_ascii_case("General") => CellStyle::Plain,
+                Some(code) => CellStyle::NumberFormat(code.to_string()),
+                None => CellStyle::Formatted,
+            }
+        })
+        .collect()
+}
+
+fn is_default_attribute(key: &str, value: &str) -> bool {
+    matches!((key, value), ("horizontal", "general") | ("vertical", "bottom") | ("locked", "1") | ("locked", "true"))
+        || matches!(value, "0" | "false")
+}
+
+fn builtin_number_format(id: &str) -> Option<&'static str> {
+    let code = match id {
+        "0" => "General",
+        "1" => "0",
+        "2" => "0.00",
+        "3" => "#,##0",
+        "4" => "#,##0.00",
+        "9" => "0%",
+        "10" => "0.00%",
+        "11" => "0.00E+00",
+        "12" => "# ?/?",
+        "13" => "# ??/??",
+        "14" => "mm-dd-yy",
+        "15" => "d-mmm-yy",
+        "16" => "d-mmm",
+        "17" => "mmm-yy",
+        "18" => "h:mm AM/PM",
+        "19" => "h:mm:ss AM/PM",
+        "20" => "h:mm",
+        "21" => "h:mm:ss",
+        "22" => "m/d/yy h:mm",
+        "37" => "#,##0 ;(#,##0)",
+        "38" => "#,##0 ;[Red](#,##0)",
+        "39" => "#,##0.00;(#,##0.00)",
+        "40" => "#,##0.00;[Red](#,##0.00)",
+        "45" => "mm:ss",
+        "46" => "[h]:mm:ss",
+        "47" => "mmss.0",
+        "48" => "##0.0E+0",
+        "49" => "@",
+        _ => return None,
+    };
+    Some(code)
+}
+
+// one cell while its children are read
+struct OpenCell {
+    position: Option<(u32, u32)>,
+    style: CellStyle,
+    value: bool,
+    formula: bool,
+}
+
+// notes what of the sheet is lost, returns number formats of its cells
+fn scan_sheet(sheet_xml: &str, styles: &[CellStyle], lost: &mut Vec<&'static str>) -> HashMap<(u32, u32), String> {
+    let mut number_formats = HashMap::new();
+    let mut cell: Option<OpenCell> = None;
+    let mut cells = Vec::new();
+    for_each_tag(sheet_xml, |tag| {
+        let (element, attributes, has_content) = match tag {
+            Tag::Open(element, attributes, has_content) => (element, attributes, has_content),
+            Tag::Close(b"c") => {
+                cells.extend(cell.take());
+                return;
+            }
+            Tag::Close(_) => return,
+        };
+        let what = match element {
+            b"c" => {
+                let style = attributes
+                    .get("s")
+                    .and_then(|index| index.parse::<usize>().ok())
+                    .and_then(|index| styles.get(index).cloned())
+                    .unwrap_or(CellStyle::Plain);
+                let open = OpenCell { position: attributes.get("r").and_then(|r| cell_position(r)), style, value: false, formula: false };
+                if has_content {
+                    cell = Some(open);
+                } else {
+                    cells.push(open);
+                }
+                return;
+            }
+            b"v" | b"is" => {
+                if let Some(cell) = cell.as_mut() {
+                    cell.value = true;
+                }
+                return;
+            }
+            b"f" => {
+                if let Some(cell) = cell.as_mut() {
+                    cell.formula = true;
+                }
+                return;
+            }
+            b"col" if attributes.get("style").is_some_and(|style| style != "0") => "formatted cells",
+            b"col" if is_set(attributes.get("customWidth")) => "column widths",
+            b"col" if is_set(attributes.get("hidden")) => "hidden columns",
+            b"row" if is_set(attributes.get("customFormat")) => "formatted cells",
+            b"row" if is_set(attributes.get("customHeight")) => "row heights",
+            b"row" if is_set(attributes.get("hidden")) => "hidden rows",
+            b"mergeCell" => "merged cells",
+            b"conditionalFormatting" => "conditional formatting",
+            b"dataValidation" => "data validation",
+            b"drawing" => "charts or pictures",
+            b"legacyDrawing" => "comments",
+            b"hyperlink" => "links",
+            b"tablePart" => "tables",
+            b"autoFilter" => "filters",
+            b"pane" => "frozen panes",
+            b"sheetProtection" => "protection",
+            b"tabColor" => "tab colour",
+            b"sparklineGroup" => "sparklines",
+            b"rPr" => "formatted text",
+            _ => return,
+        };
+        note(lost, what);
+    });
+
+    for cell in cells {
+        match (cell.style, cell.position) {
+            (CellStyle::Plain, _) => {}
+            // blank cells aren't written
+            (CellStyle::NumberFormat(code), Some(position)) if cell.value || cell.formula => {
+                number_formats.insert(position, code);
+            }
+            _ => note(lost, "formatted cells"),
+        }
+    }
+    number_formats
+}
+
+// "C3" -> (2, 2)
+fn cell_position(reference: &str) -> Option<(u32, u32)> {
+    let letters: String = reference.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    let row: u32 = reference[letters.len()..].parse().ok()?;
+    let col = letters
+        .chars()
+        .try_fold(0u32, |col, letter| Some(col * 26 + (letter.to_ascii_uppercase() as u32).checked_sub('A' as u32)? + 1))?;
+    Some((row.checked_sub(1)?, col.checked_sub(1)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_sheet;
+    use xlsxwriter::prelude::*;
+
+    #[test]
+    fn test_formatting_is_not_carried_over() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("expenses.xlsx");
+        let workbook = Workbook::new(path.to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        let mut summary = workbook.add_worksheet(Some(SUMMARY_SHEET_NAME)).unwrap();
+        let mut bold = Format::new();
+        bold.set_bold();
+        write_test_sheet(&mut sheet, &[], &[]);
+        sheet.set_column(1, 1, 20.0, None).unwrap();
+        sheet.write_string(0, 0, "Expenses", Some(&bold)).unwrap();
+        // the app's own sheet is written anew, its formatting doesn't count
+        summary.write_string(0, 0, "Summary", Some(&bold)).unwrap();
+        workbook.close().unwrap();
+
+        let carry_over = xls_read_carry_over(path.to_str().unwrap()).unwrap();
+        assert_eq!(carry_over.lost, vec!["Sheet1: column widths, formatted cells"]);
+        let error = xls_check_carry_over(path.to_str().unwrap()).unwrap_err();
+        assert!(error.contains("Sheet1: column widths, formatted cells"), "{}", error);
+    }
+
+    #[test]
+    fn test_read_cell_styles() {
+        // as LibreOffice writes them, with the default alignment spelled out
+        let styles = r##"<styleSheet>
+            <numFmts count="2"><numFmt numFmtId="164" formatCode="General"/><numFmt numFmtId="165" formatCode="#,##0.00&quot; €&quot;"/></numFmts>
+            <cellStyleXfs count="1"><xf numFmtId="0" fontId="2" fillId="0" borderId="0"/></cellStyleXfs>
+            <cellXfs count="6">
+                <xf numFmtId="164" fontId="0" fillId="0" borderId="0"><alignment horizontal="general" vertical="bottom" wrapText="false"/><protection locked="true" hidden="false"/></xf>
+                <xf numFmtId="165" fontId="0" fillId="0" borderId="0"/>
+                <xf numFmtId="14" fontId="0" fillId="0" borderId="0"/>
+                <xf numFmtId="0" fontId="1" fillId="0" borderId="0"/>
+                <xf numFmtId="0" fontId="0" fillId="0" borderId="0"><alignment horizontal="center"/></xf>
+                <xf numFmtId="5" fontId="0" fillId="0" borderId="0"/>
+            </cellXfs>
+        </styleSheet>"##;
+        assert_eq!(
+            read_cell_styles(styles),
+            vec![
+                CellStyle::Plain,
+                CellStyle::NumberFormat("#,##0.00\" €\"".to_string()),
+                CellStyle::NumberFormat("mm-dd-yy".to_string()),
+                CellStyle::Formatted,
+                CellStyle::Formatted,
+                CellStyle::Formatted, // currency of the locale
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cell_position() {
+        assert_eq!(cell_position("A1"), Some((0, 0)));
+        assert_eq!(cell_position("C3"), Some((2, 2)));
+        assert_eq!(cell_position("AB10"), Some((9, 27)));
+        assert_eq!(cell_position("3"), None);
+    }
+}
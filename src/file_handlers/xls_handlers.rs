@@ -1,16 +1,35 @@
 use calamine::{open_workbook, DataType, Reader, Xlsx};
 use xlsxwriter::prelude::*;
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
-use crate::utils::folder_file_utils::{ prepare_folder_structure, get_latest_backup};
-use crate::utils::structs::MonthExpenses;
+use crate::file_handlers::xls_budget::{xls_add_configured_budget_sheet, BUDGET_SHEET_NAME};
+use crate::file_handlers::xls_carry_over::{xls_check_carry_over, CarryOver, DEFAULT_DATE_FORMAT};
+use crate::file_handlers::xls_summary::{cell_name, column_ranges, xls_add_configured_summary, DATA_SHEET_NAME, SUMMARY_SHEET_NAME};
+use crate::utils::folder_file_utils::{ prepare_folder_structure, partial_file_path, replace_with_partial_file};
+use crate::utils::external_edits::record_workbook_hash;
+use crate::utils::workbook_lock::WorkbookLock;
+use crate::utils::structs::{CellChange, MonthExpenses};
+use crate::utils::transaction_utils::month_name_from_number;
 
-const YEAR_MONTH_COLUMN: u32 = 1; // index of column (A = 0 B = 1)
-const STARTING_ROW: u32 = 1; // starting position of a table. (row 1 = pos 0, row 2 = pos 1)
+pub(crate) const YEAR_MONTH_COLUMN: u32 = 1; // index of column (A = 0 B = 1)
+pub(crate) const STARTING_ROW: u32 = 1; // starting position of a table. (row 1 = pos 0, row 2 = pos 1)
 
 const TMP_FOLDER: &str = "tmp";
 const TMP_WORKBOOK_NAME: &str = "tmp_mask.xlsx"; // hardcoded for test
-const RES_WORKBOOK_NAME: &str = "expensesGenerated.xlsx"; // hardcoded for test
+pub const RES_WORKBOOK_NAME: &str = "expenses.xlsx"; // the workbook, edited by the user and updated in place
+pub const TOTAL_LABEL: &str = "Total"; // header of the totals column and the row under December
+// row under the totals row written by older versions, that row is kept blank between years now
+pub const AVERAGE_LABEL: &str = "Average";
+// header of the income area right of the expenses, income sources follow it. its column holds
+// the income of every month
+pub const INCOME_LABEL: &str = "Income";
+
+// result of a workbook update
+pub struct WorkbookUpdate {
+    pub cells: Vec<CellChange>,
+    pub backup_name: String, // backup holding the workbook as it was before the update
+}
 
 // TODO! replace hardcoded workbook path with a variable in xls_insert_monthly_expenses
 
@@ -19,125 +38,312 @@ const RES_WORKBOOK_NAME: &str = "expensesGenerated.xlsx"; // hardcoded for test
 // excel spreadsheet
 
 // returns true if success
-pub fn xls_perform_workbook_update(me: MonthExpenses, destination_path: &str) -> bool{
+pub fn xls_perform_workbook_update(me: MonthExpenses, destination_path: &str) -> bool {
+    let _lock = match WorkbookLock::acquire(destination_path) {
+        Ok(lock) => lock,
+        Err(e) => {
+            println!("{}", e);
+            return false;
+        }
+    };
+    xls_perform_logged_workbook_update(me, destination_path).is_some()
+}
 
-    if let Err(e) = prepare_folder_structure(destination_path, RES_WORKBOOK_NAME) {
-        println!("Error preparing folder structure: {:?}", e);
-        return false;
-    }
+// same as xls_perform_workbook_update, but tells which cells were written and
+// which backup holds the workbook as it was before the update (for the import log)
+// the caller holds the WorkbookLock, an import writes several months under one lock
+pub fn xls_perform_logged_workbook_update(me: MonthExpenses, destination_path: &str) -> Option<WorkbookUpdate> {
+
+    // snapshot of the workbook before it is changed
+    let (backup_name, carry_over) = xls_prepare_update(destination_path)?;
 
-    // Prepare paths for backup and temp workbooks
+    // Prepare paths for the workbook and temp workbooks
+    // merged workbook is written next to the workbook and only replaces it once complete
     let result_workbook_path = Path::new(destination_path).join(RES_WORKBOOK_NAME);
-    let backup_workbook_path = get_latest_backup(destination_path).expect("Failed to get latest backup");
+    let partial_workbook_path = partial_file_path(&result_workbook_path);
     let tmp_workbook_path = Path::new(destination_path).join(TMP_FOLDER).join(TMP_WORKBOOK_NAME);
 
 
     // convert path to &str
-    let backup_workbook_path_str = backup_workbook_path.to_str().unwrap();
-    let tmp_workbook_path_str = tmp_workbook_path.to_str().unwrap();
     let result_workbook_path_str = result_workbook_path.to_str().unwrap();
+    let tmp_workbook_path_str = tmp_workbook_path.to_str().unwrap();
+    let partial_workbook_path_str = partial_workbook_path.to_str().unwrap();
+
+    // remember what is going to be overwritten
+    let cells = xls_cells_to_write(&me, result_workbook_path_str)?;
 
     // Insert the new expense data into the "mask" workbook
-    if !xls_insert_monthly_expense_entry_in_a_new_workbook(me.clone(), backup_workbook_path_str, tmp_workbook_path_str) {
+    if !xls_insert_monthly_expense_entry_in_a_new_workbook(me.clone(), result_workbook_path_str, tmp_workbook_path_str) {
         println!("Failed to create 'mask' workbook with new data.");
-        return false;
+        return None;
     }
 
 
     // Open the existing workbook
-    let mut existing_workbook: Xlsx<_> = open_workbook(backup_workbook_path_str).expect("Cannot open existing workbook");
+    let mut existing_workbook: Xlsx<_> = open_workbook(result_workbook_path_str).expect("Cannot open existing workbook");
 
     // Open the newly created "mask" workbook
     let mut new_workbook: Xlsx<_> = open_workbook(tmp_workbook_path_str).expect("Cannot open 'mask' workbook");
 
     // Create a new workbook to store the merged data
-    let write_workbook_result: Result<Workbook, XlsxError> = Workbook::new(result_workbook_path_str);
+    let write_workbook_result: Result<Workbook, XlsxError> = Workbook::new(partial_workbook_path_str);
 
     match write_workbook_result {
         Ok(merged_workbook) => {
+            // other sheets of the user keep their place around Sheet1
+            if let Err(e) = xls_copy_other_sheets(&merged_workbook, result_workbook_path_str, &carry_over, true) {
+                println!("{}", e);
+                return None;
+            }
             // Create a new sheet in the merged workbook
             let sheet_result = merged_workbook.add_worksheet(Some("Sheet1"));
             match sheet_result {
                 Ok(mut sheet) => {
+                    if let Err(e) = xls_copy_other_sheets(&merged_workbook, result_workbook_path_str, &carry_over, false) {
+                        println!("{}", e);
+                        return None;
+                    }
+                    // formulas are copied as formulas, with the values they had
+                    let mut existing_formulas = xls_read_formulas(result_workbook_path_str);
+                    let mut merged_cells = xls_read_sheet(result_workbook_path_str).unwrap_or_default();
+                    let old_averages = xls_old_average_cells(&merged_cells, &existing_formulas);
+                    existing_formulas.retain(|position, _| !old_averages.contains(position));
+                    merged_cells.retain(|position, _| !old_averages.contains(position));
+
                     // Iterate over the Existing data and copy it to the new workbook
                     if let Some(Ok(existing_range)) = existing_workbook.worksheet_range("Sheet1") {
-                        for row in 0..existing_range.height() {
-                            for col in 0..existing_range.width() {
-                                if let Some(cell) = existing_range.get_value((row as u32, col as u32)) {
+                        // get_value takes absolute positions, the used range doesn't have to start at A1
+                        let (start_row, start_col) = existing_range.start().unwrap_or((0, 0));
+                        let (end_row, end_col) = existing_range.end().unwrap_or((0, 0));
+                        for row in start_row..=end_row {
+                            for col in start_col..=end_col {
+                                if existing_formulas.contains_key(&(row, col)) || old_averages.contains(&(row, col)) {
+                                    continue;
+                                }
+                                if let Some(cell) = existing_range.get_value((row, col)) {
                                     // Copy cell from existing workbook
-                                    match cell {
-                                        DataType::String(val) => {
-                                            sheet.write_string(row as u32, col as u16, val, None).expect("Failed to write string");
-                                        }
-                                        DataType::Float(val) => {
-                                            sheet.write_number(row as u32, col as u16, *val, None).expect("Failed to write number");
-                                        }
-                                        DataType::Int(val) => {
-                                            sheet.write_number(row as u32, col as u16, *val as f64, None).expect("Failed to write int");
-                                        }
-                                        _ => {} // Handle other data types as necessary
-                                    }
+                                    let number_format = carry_over.number_format(DATA_SHEET_NAME, row, col);
+                                    xls_copy_cell(&mut sheet, row, col, cell, number_format).expect("Failed to copy cell");
                                 }
                             }
                         }
                     }
 
+                    xls_copy_formulas(&mut sheet, &existing_formulas, &merged_cells, &carry_over).expect("Failed to copy formulas");
+
                     // Merge the "mask" workbook data into the new workbook
                     if let Some(Ok(new_range)) = new_workbook.worksheet_range("Sheet1") {
-                        // careful hardcoded
-                        for row in 0..10000 {
-                            // max amount of categories is around 27
-                            for col in 0..30 {
-                                if let Some(cell) = new_range.get_value((row as u32, col as u32)) {
+                        // the income area can be further right than any category
+                        let (start_row, start_col) = new_range.start().unwrap_or((0, 0));
+                        let (end_row, end_col) = new_range.end().unwrap_or((0, 0));
+                        for row in start_row..=end_row {
+                            for col in start_col..=end_col {
+                                // a formula of the user stays, see xls_cells_to_write
+                                if existing_formulas.contains_key(&(row, col)) {
+                                    continue;
+                                }
+                                if let Some(cell) = new_range.get_value((row, col)) {
                                     // If the cell contains new data from the "mask", insert it
-                                    match cell {
-                                        DataType::String(val) => {
-                                            sheet.write_string(row as u32, col as u16, val, None).expect("Failed to write string from mask");
-                                        }
-                                        DataType::Float(val) => {
-                                            sheet.write_number(row as u32, col as u16, *val, None).expect("Failed to write number from mask");
-                                        }
-                                        DataType::Int(val) => {
-                                            sheet.write_number(row as u32, col as u16, *val as f64, None).expect("Failed to write int from mask");
-                                        }
-                                        _ => {} // Handle other data types as necessary
-                                    }
+                                    let number_format = carry_over.number_format(DATA_SHEET_NAME, row, col);
+                                    xls_copy_cell(&mut sheet, row, col, cell, number_format).expect("Failed to write cell from mask");
                                 }
                             }
                         }
                     }
 
+                    // totals, summary and budget sheets are calculated from Sheet1 as it was just written
+                    let mask_cells = xls_read_sheet(tmp_workbook_path_str).unwrap_or_default();
+                    merged_cells.extend(mask_cells.into_iter().filter(|(position, _)| !existing_formulas.contains_key(position)));
+                    xls_write_year_block_totals(&mut sheet, &merged_cells, &existing_formulas, &carry_over).expect("Failed to write totals");
+                    xls_add_configured_summary(&merged_workbook, destination_path, &merged_cells);
+                    xls_add_configured_budget_sheet(&merged_workbook, destination_path, &merged_cells);
+
                     // Save the merged workbook with changes
                     merged_workbook.close().expect("Cannot save merged workbook");
-                    return true;
+                    if let Err(e) = replace_with_partial_file(&partial_workbook_path, &result_workbook_path) {
+                        println!("Failed to replace the workbook: {:?}", e);
+                        return None;
+                    }
+                    if let Err(e) = record_workbook_hash(destination_path, RES_WORKBOOK_NAME) {
+                        println!("Error recording workbook hash: {:?}", e);
+                    }
+                    return Some(WorkbookUpdate { cells, backup_name });
                 }
                 Err(e) => {
                     println!("Failed to add sheet to the merged workbook: {:?}", e);
-                    return false;
+                    return None;
                 }
             }
         }
         Err(e) => {
             println!("Failed to create new merged workbook: {:?}", e);
-            return false;
+            return None;
         }
     }
 }
 
+// folders ready and the workbook backed up, returns name of the backup and what of the
+// workbook is copied besides the values. a workbook with content the update can't copy is left
+// as it is (see xls_carry_over), so is a workbook migrated from an older version, the app wrote it
+fn xls_prepare_update(destination_path: &str) -> Option<(String, CarryOver)> {
+    let workbook_path = Path::new(destination_path).join(RES_WORKBOOK_NAME);
+    let carry_over = if workbook_path.exists() {
+        match xls_check_carry_over(workbook_path.to_str()?) {
+            Ok(carry_over) => carry_over,
+            Err(e) => {
+                println!("{}", e);
+                return None;
+            }
+        }
+    } else {
+        CarryOver::default()
+    };
+    match prepare_folder_structure(destination_path, RES_WORKBOOK_NAME) {
+        Ok(Some(backup_name)) => Some((backup_name, carry_over)),
+        Ok(None) => {
+            println!("No spreadsheet found in destination folder");
+            None
+        }
+        Err(e) => {
+            println!("Error preparing folder structure: {:?}", e);
+            None
+        }
+    }
+}
+
+// writes single cells into the workbook, None clears the cell, everything else is copied as it is
+// (used to undo an import), returns name of the backup holding the workbook as it was before
+// the caller holds the WorkbookLock
+pub fn xls_perform_cells_update(cells: &HashMap<(u32, u32), Option<f64>>, destination_path: &str) -> Option<String> {
+    let (backup_name, carry_over) = xls_prepare_update(destination_path)?;
+
+    let result_workbook_path = Path::new(destination_path).join(RES_WORKBOOK_NAME);
+    let partial_workbook_path = partial_file_path(&result_workbook_path);
+
+    let mut existing_workbook: Xlsx<_> = open_workbook(&result_workbook_path).ok()?;
+    let existing_range = existing_workbook.worksheet_range("Sheet1")?.ok()?;
+
+    let result_workbook = match Workbook::new(partial_workbook_path.to_str()?) {
+        Ok(workbook) => workbook,
+        Err(e) => {
+            println!("Failed to create new workbook: {:?}", e);
+            return None;
+        }
+    };
+    if let Err(e) = xls_copy_other_sheets(&result_workbook, result_workbook_path.to_str()?, &carry_over, true) {
+        println!("{}", e);
+        return None;
+    }
+    let mut sheet = match result_workbook.add_worksheet(Some("Sheet1")) {
+        Ok(sheet) => sheet,
+        Err(e) => {
+            println!("Failed to add sheet: {:?}", e);
+            return None;
+        }
+    };
+    if let Err(e) = xls_copy_other_sheets(&result_workbook, result_workbook_path.to_str()?, &carry_over, false) {
+        println!("{}", e);
+        return None;
+    }
+
+    let (start_row, start_col) = existing_range.start().unwrap_or((0, 0));
+    let (end_row, end_col) = existing_range.end().unwrap_or((0, 0));
+    // formulas stay formulas, unless the cell is overwritten
+    let mut formulas = xls_read_formulas(result_workbook_path.to_str()?);
+    let mut updated_cells = xls_read_sheet(result_workbook_path.to_str()?).unwrap_or_default();
+    let old_averages = xls_old_average_cells(&updated_cells, &formulas);
+    formulas.retain(|position, _| !cells.contains_key(position) && !old_averages.contains(position));
+    updated_cells.retain(|position, _| !old_averages.contains(position));
+
+    for row in start_row..=end_row {
+        for col in start_col..=end_col {
+            if cells.contains_key(&(row, col)) || formulas.contains_key(&(row, col)) || old_averages.contains(&(row, col)) {
+                continue;
+            }
+            let Some(cell) = existing_range.get_value((row, col)) else {
+                continue;
+            };
+            if let Err(e) = xls_copy_cell(&mut sheet, row, col, cell, carry_over.number_format(DATA_SHEET_NAME, row, col)) {
+                println!("Failed to copy cell: {:?}", e);
+                return None;
+            }
+        }
+    }
+
+    if let Err(e) = xls_copy_formulas(&mut sheet, &formulas, &updated_cells, &carry_over) {
+        println!("Failed to copy formulas: {:?}", e);
+        return None;
+    }
+
+    for (&(row, col), value) in cells {
+        if let Some(value) = value {
+            let format = xls_number_format(carry_over.number_format(DATA_SHEET_NAME, row, col));
+            if let Err(e) = sheet.write_number(row, col as u16, *value, format.as_ref()) {
+                println!("Failed to write cell: {:?}", e);
+                return None;
+            }
+        }
+    }
+
+    for (&position, value) in cells {
+        match value {
+            Some(value) => updated_cells.insert(position, CellValue::Number(*value)),
+            None => updated_cells.remove(&position),
+        };
+    }
+    if let Err(e) = xls_write_year_block_totals(&mut sheet, &updated_cells, &formulas, &carry_over) {
+        println!("Failed to write totals: {:?}", e);
+        return None;
+    }
+    xls_add_configured_summary(&result_workbook, destination_path, &updated_cells);
+    xls_add_configured_budget_sheet(&result_workbook, destination_path, &updated_cells);
+
+    if let Err(e) = result_workbook.close() {
+        println!("Cannot save workbook: {:?}", e);
+        return None;
+    }
+    if let Err(e) = replace_with_partial_file(&partial_workbook_path, &result_workbook_path) {
+        println!("Failed to replace the workbook: {:?}", e);
+        return None;
+    }
+    if let Err(e) = record_workbook_hash(destination_path, RES_WORKBOOK_NAME) {
+        println!("Error recording workbook hash: {:?}", e);
+    }
+    Some(backup_name)
+}
+
+// current numeric values of the given cells, None for empty or text cells
+pub fn xls_read_cell_values(positions: &[(u32, u32)], path_to_workbook: &str) -> Option<Vec<Option<f64>>> {
+    let mut workbook: Xlsx<_> = open_workbook(path_to_workbook).ok()?;
+    let range = workbook.worksheet_range("Sheet1")?.ok()?;
+
+    Some(
+        positions
+            .iter()
+            .map(|position| match range.get_value(*position) {
+                Some(DataType::Float(val)) => Some(*val),
+                Some(DataType::Int(val)) => Some(*val as f64),
+                _ => None,
+            })
+            .collect(),
+    )
+}
+
 // function which inserts data in a correct position in a new "mask" workbook
-// due to xlsxwriter restrictions
+// due to xlsxwriter restrictions (plain numbers only, totals formulas are added in the merge)
 // returns true if file was created successfully
 pub fn xls_insert_monthly_expense_entry_in_a_new_workbook(me: MonthExpenses, path_to_back_up_workbook: &str, path_to_tmp_workbook: &str) -> bool {
     let year_to_find = me.year;
     let month_to_find = me.month;
     let expenses_data = me.expenses_data;
+    let income_data = me.income_data;
 
     // Find the row for the given year
     if let Some(year_row) = xls_find_year_entry_row_number(YEAR_MONTH_COLUMN, year_to_find, path_to_back_up_workbook) {
         // Find the correct row for the month
         if let Some(month_row) = xls_find_month_entry_row_number(year_row, month_to_find) {
             // Get categories from the same row
-            if let Some(categories) = xls_categories_to_vec(year_row, path_to_back_up_workbook) {
+            if let Some(categories) = xls_category_columns(year_row, path_to_back_up_workbook) {
                 println!("categories: {:?}", categories);
                 // Create a new workbook for writing (xlsxwriter cannot modify existing files directly)
                 let write_workbook_result: Result<Workbook, XlsxError> = Workbook::new(path_to_tmp_workbook);
@@ -149,17 +355,23 @@ pub fn xls_insert_monthly_expense_entry_in_a_new_workbook(me: MonthExpenses, pat
                         match sheet_result {
                             Ok(mut sheet) => {
                                 // Loop through the categories and insert data from the hashmap
-                                for (col, category) in categories.iter().enumerate() {
+                                for (col, category) in categories.iter() {
                                     if let Some(expense) = expenses_data.get(category) {
                                         // Insert expense into the corresponding column
                                         println!("Row {} Col {}, category {}",month_row, col, category);
                                         sheet.write_number(
                                             month_row as u32,
-                                        col as u16 + YEAR_MONTH_COLUMN as u16 + 1,
+                                        *col as u16,
                                     *expense, None).expect("Cannot write expense");
                                     }
                                 }
 
+                                // income goes into the income area of the same year
+                                let sheet_cells = xls_read_sheet(path_to_back_up_workbook).unwrap_or_default();
+                                for (row, col, _, income) in xls_income_cells(year_to_find, month_row, &income_data, &sheet_cells) {
+                                    sheet.write_number(row, col as u16, income, None).expect("Cannot write income");
+                                }
+
                                 // Save the new workbook with changes
                                 workbook_result.close().expect("Cannot save file");
                                 return true;
@@ -183,11 +395,500 @@ pub fn xls_insert_monthly_expense_entry_in_a_new_workbook(me: MonthExpenses, pat
 
 
 
+// returns the workbook, backups are never read as the current data
+pub fn xls_current_workbook_path(destination_path: &str) -> Option<PathBuf> {
+    let result_workbook_path = Path::new(destination_path).join(RES_WORKBOOK_NAME);
+    result_workbook_path.exists().then_some(result_workbook_path)
+}
+
+// cells the month expenses are going to be written into, with their current values
+// same positions as in xls_insert_monthly_expense_entry_in_a_new_workbook. cells holding a
+// formula of the user are left out, the merge doesn't write over them
+pub fn xls_cells_to_write(me: &MonthExpenses, path_to_workbook: &str) -> Option<Vec<CellChange>> {
+    let formulas = xls_read_formulas(path_to_workbook);
+    let mut cells = xls_target_cells(me, path_to_workbook)?;
+    cells.retain(|cell| !formulas.contains_key(&(cell.row, cell.col)));
+    Some(cells)
+}
+
+// cells the month expenses would be written into that hold a formula, they are left as they are
+pub fn xls_formula_cells_to_skip(me: &MonthExpenses, path_to_workbook: &str) -> Vec<CellChange> {
+    let formulas = xls_read_formulas(path_to_workbook);
+    let mut cells = xls_target_cells(me, path_to_workbook).unwrap_or_default();
+    cells.retain(|cell| formulas.contains_key(&(cell.row, cell.col)));
+    cells
+}
+
+fn xls_target_cells(me: &MonthExpenses, path_to_workbook: &str) -> Option<Vec<CellChange>> {
+    let year_row = xls_find_year_entry_row_number(YEAR_MONTH_COLUMN, me.year, path_to_workbook)?;
+    let month_row = xls_find_month_entry_row_number(year_row, me.month.clone())?;
+    let categories = xls_category_columns(year_row, path_to_workbook)?;
+
+    let mut workbook: Xlsx<_> = open_workbook(path_to_workbook).expect("Cannot open file");
+    let range = workbook.worksheet_range("Sheet1")?.ok()?;
+
+    let mut cells = Vec::new();
+    for (col, category) in categories {
+        if let Some(expense) = me.expenses_data.get(&category) {
+            let previous = match range.get_value((month_row, col)) {
+                Some(DataType::Float(val)) => Some(*val),
+                Some(DataType::Int(val)) => Some(*val as f64),
+                _ => None,
+            };
+            cells.push(CellChange {
+                row: month_row,
+                col,
+                year: me.year,
+                month: me.month.clone(),
+                category,
+                previous,
+                value: *expense,
+            });
+        }
+    }
+
+    let sheet_cells = xls_read_sheet(path_to_workbook)?;
+    for (row, col, source, income) in xls_income_cells(me.year, month_row, &me.income_data, &sheet_cells) {
+        let previous = match sheet_cells.get(&(row, col)) {
+            Some(CellValue::Number(value)) => Some(*value),
+            _ => None,
+        };
+        cells.push(CellChange { row, col, year: me.year, month: me.month.clone(), category: source, previous, value: income });
+    }
+    Some(cells)
+}
+
+// returns categories from the header row of the given year
+pub fn xls_categories_for_year(year: i64, path_to_workbook: &str) -> Option<Vec<String>> {
+    let year_row = xls_find_year_entry_row_number(YEAR_MONTH_COLUMN, year, path_to_workbook)?;
+    xls_categories_to_vec(year_row, path_to_workbook)
+}
+
+// income sources of the given year, empty when the year has no income area
+pub fn xls_income_sources_for_year(year: i64, path_to_workbook: &str) -> Option<Vec<String>> {
+    let cells = xls_read_sheet(path_to_workbook)?;
+    let block = xls_year_blocks(&cells).into_iter().find(|block| block.year == year)?;
+    Some(block.income_sources)
+}
+
+// (row, col, source, value) of every income with a source in the income area of the year,
+// income of unknown sources is left out
+fn xls_income_cells(year: i64, month_row: u32, income_data: &HashMap<String, f64>, cells: &SheetCells) -> Vec<(u32, u32, String, f64)> {
+    let Some(block) = xls_year_blocks(cells).into_iter().find(|block| block.year == year) else {
+        return Vec::new();
+    };
+    block
+        .income_sources
+        .iter()
+        .zip(&block.income_cols)
+        .filter_map(|(source, &col)| {
+            let income = income_data.get(source)?;
+            Some((month_row, col, source.clone(), *income))
+        })
+        .collect()
+}
+
+// value of a non empty cell, as read from a workbook
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellValue {
+    Number(f64),
+    Text(String),
+}
+
+// every non empty cell of Sheet1, by (row, col)
+pub type SheetCells = BTreeMap<(u32, u32), CellValue>;
+
+// reads the whole sheet, the workbook itself is never written
+pub fn xls_read_sheet(path_to_workbook: &str) -> Option<SheetCells> {
+    let mut workbook: Xlsx<_> = open_workbook(path_to_workbook).ok()?;
+    let range = workbook.worksheet_range("Sheet1")?.ok()?;
+
+    let mut cells = SheetCells::new();
+    let (start_row, start_col) = range.start().unwrap_or((0, 0));
+    let (end_row, end_col) = range.end().unwrap_or((0, 0));
+    for row in start_row..=end_row {
+        for col in start_col..=end_col {
+            let value = match range.get_value((row, col)) {
+                Some(DataType::String(val)) if !val.trim().is_empty() => CellValue::Text(val.trim().to_string()),
+                Some(DataType::Float(val)) => CellValue::Number(*val),
+                Some(DataType::Int(val)) => CellValue::Number(*val as f64),
+                _ => continue,
+            };
+            cells.insert((row, col), value);
+        }
+    }
+    Some(cells)
+}
+
+// year of the block the row belongs to, with the position inside the block
+// (0 = header row with categories, 1 - 12 = months)
+fn xls_year_block_of_row(cells: &SheetCells, row: u32) -> Option<(i64, u32)> {
+    if row < STARTING_ROW {
+        return None;
+    }
+    let offset = (row - STARTING_ROW) % 15;
+    if offset > 12 {
+        return None; // blank rows between years
+    }
+    match cells.get(&(row - offset, YEAR_MONTH_COLUMN))? {
+        CellValue::Number(year) => Some((*year as i64, offset)),
+        CellValue::Text(_) => None,
+    }
+}
+
+// totals column and Total / Average rows of a year block, calculated from the other cells
+pub fn xls_is_total_cell(cells: &SheetCells, row: u32, col: u32) -> bool {
+    if row < STARTING_ROW || col < YEAR_MONTH_COLUMN {
+        return false;
+    }
+    let offset = (row - STARTING_ROW) % 15;
+    let year_row = row - offset;
+    if !matches!(cells.get(&(year_row, YEAR_MONTH_COLUMN)), Some(CellValue::Number(_))) {
+        return false;
+    }
+    offset > 12 || matches!(cells.get(&(year_row, col)), Some(CellValue::Text(header)) if header == TOTAL_LABEL || header == INCOME_LABEL)
+}
+
+// human readable position of a cell: "March 2024 Groceries", None outside of year blocks
+pub fn xls_cell_label(cells: &SheetCells, row: u32, col: u32) -> Option<String> {
+    let (year, offset) = xls_year_block_of_row(cells, row)?;
+    let category = match cells.get(&(row - offset, col))? {
+        CellValue::Text(category) if col > YEAR_MONTH_COLUMN => category,
+        _ => return None,
+    };
+
+    match month_name_from_number(offset) {
+        Some(month) => Some(format!("{} {} {}", month, year, category)),
+        None => Some(format!("{} {}", year, category)), // header row itself
+    }
+}
+
+// years in the sheet with the months that have at least one number filled in
+// (only categories count, the totals column holds 0 for empty months)
+pub fn xls_months_with_data(cells: &SheetCells) -> BTreeMap<i64, Vec<String>> {
+    let mut months: BTreeMap<i64, Vec<String>> = BTreeMap::new();
+    for block in xls_year_blocks(cells) {
+        let year_months = months.entry(block.year).or_default();
+        for (month, values) in (1..=12).zip(&block.months) {
+            if values.iter().any(Option::is_some) {
+                year_months.push(month_name_from_number(month).unwrap_or_default().to_string());
+            }
+        }
+    }
+    months
+}
+
+// one year block of the sheet: categories of the header row and the 12 month rows under it,
+// with the income area right of them
+#[derive(Clone, Debug, PartialEq)]
+pub struct YearBlock {
+    pub year: i64,
+    pub categories: Vec<String>,
+    pub category_cols: Vec<u32>, // one per category, the totals column can be between them
+    pub total_col: Option<u32>, // "Total" header, None until the totals are written
+    pub months: Vec<Vec<Option<f64>>>, // January first, one value per category
+    pub income_col: Option<u32>, // "Income" header, None without an income area
+    pub income_sources: Vec<String>, // right of the "Income" header
+    pub income_cols: Vec<u32>, // one per income source
+    pub income: Vec<Vec<Option<f64>>>, // January first, one value per income source
+}
+
+// every year block, same layout as xls_find_year_entry_row_number and xls_categories_to_vec:
+// year in column B every 15 rows, categories right of it until the first empty cell or the
+// income area, the totals column is skipped. the income area is the first "Income" header
+// right of the categories, its sources go until the first empty cell
+pub fn xls_year_blocks(cells: &SheetCells) -> Vec<YearBlock> {
+    let mut blocks = Vec::new();
+    let mut year_row = STARTING_ROW;
+    while let Some(CellValue::Number(year)) = cells.get(&(year_row, YEAR_MONTH_COLUMN)) {
+        let (category_cols, categories): (Vec<u32>, Vec<String>) = header_texts(cells, year_row, YEAR_MONTH_COLUMN + 1).into_iter().unzip();
+        let header_end = header_end(cells, year_row, YEAR_MONTH_COLUMN + 1);
+        let total_col = (YEAR_MONTH_COLUMN + 1..header_end)
+            .find(|col| matches!(cells.get(&(year_row, *col)), Some(CellValue::Text(header)) if header == TOTAL_LABEL));
+        let income_col = cells
+            .range((year_row, header_end)..(year_row + 1, 0))
+            .find(|(_, value)| matches!(value, CellValue::Text(header) if header == INCOME_LABEL))
+            .map(|(&(_, col), _)| col);
+        let (income_cols, income_sources): (Vec<u32>, Vec<String>) =
+            income_col.map(|col| header_texts(cells, year_row, col + 1)).unwrap_or_default().into_iter().unzip();
+
+        let values = |cols: &[u32]| -> Vec<Vec<Option<f64>>> {
+            (1..=12)
+                .map(|month| {
+                    cols.iter()
+                        .map(|col| match cells.get(&(year_row + month, *col)) {
+                            Some(CellValue::Number(value)) => Some(*value),
+                            _ => None,
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        let months = values(&category_cols);
+        let income = values(&income_cols);
+
+        blocks.push(YearBlock { year: *year as i64, categories, category_cols, total_col, months, income_col, income_sources, income_cols, income });
+        year_row += 15;
+    }
+    blocks
+}
+
+// (col, text) of the header texts from the column on, until an empty cell or the income area.
+// the totals column is no category, it is skipped
+fn header_texts(cells: &SheetCells, year_row: u32, first_col: u32) -> Vec<(u32, String)> {
+    (first_col..header_end(cells, year_row, first_col))
+        .filter_map(|col| match cells.get(&(year_row, col)) {
+            Some(CellValue::Text(text)) if text != TOTAL_LABEL => Some((col, text.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+// first column from first_col on that is empty or the income area
+fn header_end(cells: &SheetCells, year_row: u32, first_col: u32) -> u32 {
+    let mut col = first_col;
+    while matches!(cells.get(&(year_row, col)), Some(CellValue::Text(text)) if text != INCOME_LABEL) {
+        col += 1;
+    }
+    col
+}
+
+// formulas of Sheet1 by (row, col), "=SUM(C3:D3)"
+fn xls_read_formulas(path_to_workbook: &str) -> HashMap<(u32, u32), String> {
+    xls_read_sheet_formulas(path_to_workbook, DATA_SHEET_NAME)
+}
+
+fn xls_read_sheet_formulas(path_to_workbook: &str, sheet_name: &str) -> HashMap<(u32, u32), String> {
+    let mut formulas = HashMap::new();
+    let Ok(mut workbook) = open_workbook::<Xlsx<_>, _>(path_to_workbook) else {
+        return formulas;
+    };
+    if let Some(Ok(range)) = workbook.worksheet_formula(sheet_name) {
+        let (start_row, start_col) = range.start().unwrap_or((0, 0));
+        let (end_row, end_col) = range.end().unwrap_or((0, 0));
+        for row in start_row..=end_row {
+            for col in start_col..=end_col {
+                match range.get_value((row, col)) {
+                    Some(formula) if !formula.is_empty() => {
+                        formulas.insert((row, col), format!("={}", formula.trim_start_matches('=')));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    formulas
+}
+
+// writes the formulas back, with the numbers they had as cached values
+fn xls_copy_formulas(sheet: &mut Worksheet, formulas: &HashMap<(u32, u32), String>, cells: &SheetCells, carry_over: &CarryOver) -> Result<(), XlsxError> {
+    for (&(row, col), formula) in formulas {
+        let value = match cells.get(&(row, col)) {
+            Some(CellValue::Number(value)) => Some(*value),
+            _ => None,
+        };
+        xls_write_formula(sheet, row, col, formula, value, carry_over.number_format(DATA_SHEET_NAME, row, col))?;
+    }
+    Ok(())
+}
+
+fn xls_write_formula(sheet: &mut Worksheet, row: u32, col: u32, formula: &str, value: Option<f64>, number_format: Option<&str>) -> Result<(), XlsxError> {
+    let format = xls_number_format(number_format);
+    match value {
+        Some(value) => sheet.write_formula_num(row, col as u16, formula, format.as_ref(), value),
+        None => sheet.write_formula(row, col as u16, formula, format.as_ref()),
+    }
+}
+
+fn xls_number_format(code: Option<&str>) -> Option<Format> {
+    let mut format = Format::new();
+    format.set_num_format(code?);
+    Some(format)
+}
+
+// a cell as calamine read it, with the number format it had (see xls_carry_over)
+fn xls_copy_cell(sheet: &mut Worksheet, row: u32, col: u32, cell: &DataType, number_format: Option<&str>) -> Result<(), XlsxError> {
+    let number_format = match cell {
+        DataType::DateTime(_) => number_format.or(Some(DEFAULT_DATE_FORMAT)),
+        _ => number_format,
+    };
+    let format = xls_number_format(number_format);
+    let format = format.as_ref();
+    match cell {
+        DataType::String(val) => sheet.write_string(row, col as u16, val, format),
+        DataType::Float(val) | DataType::DateTime(val) => sheet.write_number(row, col as u16, *val, format),
+        DataType::Int(val) => sheet.write_number(row, col as u16, *val as f64, format),
+        DataType::Bool(val) => sheet.write_boolean(row, col as u16, *val, format),
+        // an error typed in as a value, "#N/A"
+        DataType::Error(error) => sheet.write_formula(row, col as u16, &format!("={}", error), format),
+        DataType::Empty => Ok(()),
+    }
+}
+
+// sheets of the user other than Sheet1, the ones before it or the ones after it, with their
+// values and formulas. Summary and Budget are left out, they are written anew
+fn xls_copy_other_sheets(workbook: &Workbook, path_to_workbook: &str, carry_over: &CarryOver, before_data_sheet: bool) -> Result<(), String> {
+    let mut existing: Xlsx<_> = open_workbook(path_to_workbook).map_err(|e| format!("Cannot open the spreadsheet: {:?}", e))?;
+    let names = existing.sheet_names().to_vec();
+    let data_sheet_index = names.iter().position(|name| name == DATA_SHEET_NAME).unwrap_or(names.len());
+    for (index, name) in names.iter().enumerate() {
+        if name == DATA_SHEET_NAME || name == SUMMARY_SHEET_NAME || name == BUDGET_SHEET_NAME || (index < data_sheet_index) != before_data_sheet {
+            continue;
+        }
+        let range = match existing.worksheet_range(name) {
+            Some(Ok(range)) => range,
+            _ => return Err(format!("Cannot read sheet {} of the spreadsheet", name)),
+        };
+        let mut sheet = workbook.add_worksheet(Some(name)).map_err(|e| format!("Failed to add sheet {}: {:?}", name, e))?;
+        let formulas = xls_read_sheet_formulas(path_to_workbook, name);
+        let (start_row, start_col) = range.start().unwrap_or((0, 0));
+        let (end_row, end_col) = range.end().unwrap_or((0, 0));
+        for row in start_row..=end_row {
+            for col in start_col..=end_col {
+                let Some(cell) = range.get_value((row, col)) else {
+                    continue;
+                };
+                if formulas.contains_key(&(row, col)) {
+                    continue;
+                }
+                xls_copy_cell(&mut sheet, row, col, cell, carry_over.number_format(name, row, col))
+                    .map_err(|e| format!("Failed to copy sheet {}: {:?}", name, e))?;
+            }
+        }
+        for (&(row, col), formula) in &formulas {
+            let value = match range.get_value((row, col)) {
+                Some(DataType::Float(value)) => Some(*value),
+                Some(DataType::Int(value)) => Some(*value as f64),
+                _ => None,
+            };
+            xls_write_formula(&mut sheet, row, col, formula, value, carry_over.number_format(name, row, col)).map_err(|e| format!("Failed to copy sheet {}: {:?}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+// one cell of the totals around a year block
+#[derive(Clone, Debug, PartialEq)]
+enum TotalCell {
+    Label(&'static str),
+    Formula(String, Option<f64>), // with the value it has for the cells as they are
+}
+
+// totals of every year block with categories:
+//  a Total column, sums of the months. it is found by its header, a new one goes right of
+//  the categories
+//  a Total row under December, for every category and the totals column. the row under it
+//  stays blank between the years
+// the income area gets the same: income of the month in the "Income" column, a Total row
+// under it and every source
+fn xls_year_block_totals(cells: &SheetCells) -> Vec<((u32, u32), TotalCell)> {
+    let mut totals = Vec::new();
+    for (index, block) in xls_year_blocks(cells).iter().enumerate() {
+        let year_row = STARTING_ROW + 15 * index as u32;
+        let has_income = block.income_col.is_some() && !block.income_sources.is_empty();
+        if block.categories.is_empty() && !has_income {
+            continue;
+        }
+        totals.push(((year_row + 13, YEAR_MONTH_COLUMN), TotalCell::Label(TOTAL_LABEL)));
+
+        if let Some(&last_col) = block.category_cols.last() {
+            let sum_col = match block.total_col {
+                Some(total_col) => Some(total_col),
+                // an income area right after the categories leaves no room for the totals column
+                None if block.income_col == Some(last_col + 1) => None,
+                None => {
+                    totals.push(((year_row, last_col + 1), TotalCell::Label(TOTAL_LABEL)));
+                    Some(last_col + 1)
+                }
+            };
+            area_totals(&mut totals, year_row, &block.category_cols, &block.months, sum_col);
+        }
+        if let (Some(income_col), true) = (block.income_col, has_income) {
+            area_totals(&mut totals, year_row, &block.income_cols, &block.income, Some(income_col));
+        }
+    }
+    totals
+}
+
+// totals of the columns, one per value of the month rows, and the sums of the months in
+// sum_col. empty months give "" so that they don't count
+fn area_totals(totals: &mut Vec<((u32, u32), TotalCell)>, year_row: u32, cols: &[u32], months: &[Vec<Option<f64>>], sum_col: Option<u32>) {
+    let mut month_totals = Vec::new();
+    if let Some(sum_col) = sum_col {
+        for (month, values) in (1..=12).zip(months) {
+            let row = year_row + month;
+            let values: Vec<f64> = values.iter().flatten().copied().collect();
+            let total = (!values.is_empty()).then(|| values.iter().fold(0.0, |total, value| total + value));
+            let range = column_ranges(row, row, cols).join(",");
+            let formula = format!("=IF(COUNT({0})=0,\"\",SUM({0}))", range);
+            totals.push(((row, sum_col), TotalCell::Formula(formula, total)));
+            month_totals.extend(total);
+        }
+    }
+
+    let mut columns: Vec<(u32, Vec<f64>)> = cols
+        .iter()
+        .enumerate()
+        .map(|(index, &col)| (col, months.iter().filter_map(|values| values[index]).collect()))
+        .collect();
+    columns.extend(sum_col.map(|sum_col| (sum_col, month_totals)));
+    for (col, values) in columns {
+        let total = values.iter().fold(0.0, |total, value| total + value);
+        let range = format!("{}:{}", cell_name(year_row + 1, col), cell_name(year_row + 12, col));
+        totals.push(((year_row + 13, col), TotalCell::Formula(format!("=SUM({})", range), Some(total))));
+    }
+}
+
+// Average row under the Total row of older versions, with the label. it is dropped on the
+// next write, so that a blank row is left between the years again
+fn xls_old_average_cells(cells: &SheetCells, formulas: &HashMap<(u32, u32), String>) -> HashSet<(u32, u32)> {
+    let mut old = HashSet::new();
+    let mut year_row = STARTING_ROW;
+    while let Some(CellValue::Number(_)) = cells.get(&(year_row, YEAR_MONTH_COLUMN)) {
+        let row = year_row + 14;
+        if matches!(cells.get(&(row, YEAR_MONTH_COLUMN)), Some(CellValue::Text(label)) if label == AVERAGE_LABEL) {
+            old.insert((row, YEAR_MONTH_COLUMN));
+            for (&(formula_row, col), formula) in formulas {
+                let range = format!("{}:{}", cell_name(year_row + 1, col), cell_name(year_row + 12, col));
+                if formula_row == row && *formula == format!("=IFERROR(AVERAGE({}),\"\")", range) {
+                    old.insert((row, col));
+                }
+            }
+        }
+        year_row += 15;
+    }
+    old
+}
+
+// writes the totals of every year block. cells that hold anything else already, typed in or
+// a formula of the user, are left as they are. totals written before get fresh values
+fn xls_write_year_block_totals(sheet: &mut Worksheet, cells: &SheetCells, formulas: &HashMap<(u32, u32), String>, carry_over: &CarryOver) -> Result<(), XlsxError> {
+    for (position, total) in xls_year_block_totals(cells) {
+        let (row, col) = position;
+        let number_format = carry_over.number_format(DATA_SHEET_NAME, row, col);
+        match (total, formulas.get(&position)) {
+            (TotalCell::Formula(formula, value), Some(existing)) if *existing == formula => {
+                xls_write_formula(sheet, row, col, &formula, value, number_format)?;
+            }
+            (_, Some(_)) => {}
+            (_, None) if cells.contains_key(&position) => {}
+            (TotalCell::Label(label), None) => sheet.write_string(row, col as u16, label, None)?,
+            (TotalCell::Formula(formula, value), None) => xls_write_formula(sheet, row, col, &formula, value, number_format)?,
+        }
+    }
+    Ok(())
+}
+
 // To improve the performance I could do indexing first to avoid String comparisons
 
 // Function to extract categories from a specific row in the Excel file
 fn xls_categories_to_vec(row: u32, workbook_backup_path: &str) -> Option<Vec<String>> {
-    let mut categories: Vec<String> = Vec::new();
+    xls_category_columns(row, workbook_backup_path).map(|categories| categories.into_iter().map(|(_, category)| category).collect())
+}
+
+// (col, category) of the header row, the totals column can be between the categories
+fn xls_category_columns(row: u32, workbook_backup_path: &str) -> Option<Vec<(u32, String)>> {
+    let mut categories: Vec<(u32, String)> = Vec::new();
     let mut workbook: Xlsx<_> = open_workbook(workbook_backup_path).expect("Cannot open file");
 
     if let Some(Ok(range)) = workbook.worksheet_range("Sheet1") {
@@ -195,9 +896,16 @@ fn xls_categories_to_vec(row: u32, workbook_backup_path: &str) -> Option<Vec<Str
         let mut col = YEAR_MONTH_COLUMN + 1;
         while let Some(cell) = range.get_value((row, col)) {
             match cell {
+                DataType::String(category) if category.trim() == INCOME_LABEL => {
+                    // the income area comes after the last category
+                    break;
+                }
+                DataType::String(category) if category.trim() == TOTAL_LABEL => {
+                    // totals column, categories added later can follow it
+                }
                 DataType::String(category) => {
                     // Add category to the vector
-                    categories.push(category.to_string().trim().to_string().clone());
+                    categories.push((col, category.to_string().trim().to_string().clone()));
                 }
                 DataType::Empty => {
                     // Stop when an empty cell is encountered
@@ -305,10 +1013,68 @@ fn xls_find_month_entry_row_number(year_row: u32, month_to_find: String) -> Opti
 }
 
 
+// workbook fixtures for tests in other modules
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+
+    // small workbook with one year block: 2024 | Groceries | Sweets, January groceries = 100
+    pub fn write_test_workbook(path: &Path) {
+        write_test_workbook_with_formulas(path, &[]);
+    }
+
+    // same workbook with an income area after a blank column: Income | Salary | Interest
+    pub fn write_test_workbook_with_income(path: &Path) {
+        write_test_workbook_with_headers(path, &[(5, INCOME_LABEL), (6, "Salary"), (7, "Interest")], &[]);
+    }
+
+    // same workbook with (row, col, formula, cached value) cells on top
+    pub fn write_test_workbook_with_formulas(path: &Path, formulas: &[(u32, u32, &str, f64)]) {
+        write_test_workbook_with_headers(path, &[], formulas);
+    }
+
+    // same workbook with more (col, text) cells in the header row
+    pub fn write_test_workbook_with_headers(path: &Path, headers: &[(u16, &str)], formulas: &[(u32, u32, &str, f64)]) {
+        let workbook = Workbook::new(path.to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        write_test_sheet(&mut sheet, headers, formulas);
+        workbook.close().unwrap();
+        record_test_workbook(path);
+    }
+
+    // the year block of write_test_workbook_with_headers, for workbooks with more in them
+    pub fn write_test_sheet(sheet: &mut Worksheet, headers: &[(u16, &str)], formulas: &[(u32, u32, &str, f64)]) {
+        sheet.write_number(STARTING_ROW, YEAR_MONTH_COLUMN as u16, 2024.0, None).unwrap();
+        sheet.write_string(STARTING_ROW, YEAR_MONTH_COLUMN as u16 + 1, "Groceries", None).unwrap();
+        sheet.write_string(STARTING_ROW, YEAR_MONTH_COLUMN as u16 + 2, "Sweets", None).unwrap();
+        for month in 1..=12 {
+            let month_name = month_name_from_number(month).unwrap();
+            sheet.write_string(STARTING_ROW + month, YEAR_MONTH_COLUMN as u16, month_name, None).unwrap();
+        }
+        sheet.write_number(STARTING_ROW + 1, YEAR_MONTH_COLUMN as u16 + 1, 100.0, None).unwrap();
+        for &(col, header) in headers {
+            sheet.write_string(STARTING_ROW, col, header, None).unwrap();
+        }
+        for &(row, col, formula, value) in formulas {
+            sheet.write_formula_num(row, col as u16, formula, None, value).unwrap();
+        }
+    }
+
+    // as if the app wrote it, so it isn't an unknown workbook to ask about
+    pub fn record_test_workbook(path: &Path) {
+        if path.file_name().is_some_and(|name| name == RES_WORKBOOK_NAME) {
+            record_workbook_hash(path.parent().unwrap().to_str().unwrap(), RES_WORKBOOK_NAME).unwrap();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
+    use super::test_support::{record_test_workbook, write_test_sheet, write_test_workbook, write_test_workbook_with_formulas, write_test_workbook_with_headers, write_test_workbook_with_income};
+    use crate::file_handlers::xls_carry_over::xls_read_carry_over;
+    use crate::utils::folder_file_utils::BACKUP_FOLDER;
+    use crate::utils::backup_utils::find_backup;
 
     // used for manual tests
     // const WORKBOOK_PATH: &str = "src/data/test_file_result.xlsx";
@@ -418,6 +1184,7 @@ mod tests {
                 data.insert("Sweets".to_string(), 50.00);
                 data
             },
+            income_data: HashMap::new(),
         };
 
         // Call the function to insert monthly expense entry
@@ -448,6 +1215,7 @@ mod tests {
                 data.insert("Restaurants".to_string(), 98.30);
                 data
             },
+            income_data: HashMap::new(),
         };
 
         let destination = "src/data/final_test";
@@ -458,4 +1226,346 @@ mod tests {
         // Check if the function returned true (success)
         assert!(result, "xls_perform_workbook_update should return true on success");
     }
+
+    fn january_expenses() -> MonthExpenses {
+        let mut data = HashMap::new();
+        data.insert("Groceries".to_string(), 150.0);
+        data.insert("Sweets".to_string(), 20.0);
+        data.insert("Other".to_string(), 5.0); // not in the header row, not written
+        MonthExpenses { year: 2024, month: "January".to_string(), expenses_data: data, income_data: HashMap::new() }
+    }
+
+    #[test]
+    fn test_xls_cells_to_write() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workbook_path = temp_dir.path().join("workbook.xlsx");
+        write_test_workbook(&workbook_path);
+
+        let cells = xls_cells_to_write(&january_expenses(), workbook_path.to_str().unwrap()).unwrap();
+        assert_eq!(cells.len(), 2);
+        assert_eq!((cells[0].row, cells[0].col), (2, 2));
+        assert_eq!(cells[0].category, "Groceries");
+        assert_eq!(cells[0].previous, Some(100.0));
+        assert_eq!(cells[0].value, 150.0);
+        assert_eq!(cells[1].category, "Sweets");
+        assert_eq!(cells[1].previous, None);
+    }
+
+    #[test]
+    fn test_xls_perform_logged_workbook_update() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let update = xls_perform_logged_workbook_update(january_expenses(), destination).unwrap();
+        assert_eq!(update.cells.len(), 2);
+        let backup_path = find_backup(destination, &update.backup_name).unwrap();
+
+        // the backup still has the old value, the new workbook the written one
+        let backup_cells = xls_cells_to_write(&january_expenses(), backup_path.to_str().unwrap()).unwrap();
+        assert_eq!(backup_cells[0].previous, Some(100.0));
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_cells = xls_cells_to_write(&january_expenses(), result_path.to_str().unwrap()).unwrap();
+        assert_eq!(result_cells[0].previous, Some(150.0));
+        assert_eq!(result_cells[1].previous, Some(20.0));
+    }
+
+    #[test]
+    fn test_xls_perform_cells_update() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        // January groceries is cleared, January sweets gets a value
+        let mut cells = HashMap::new();
+        cells.insert((2, 2), None);
+        cells.insert((2, 3), Some(7.5));
+        let backup = xls_perform_cells_update(&cells, destination).unwrap();
+
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let values = xls_read_cell_values(&[(2, 2), (2, 3)], result_path.to_str().unwrap()).unwrap();
+        assert_eq!(values, vec![None, Some(7.5)]);
+        let backup_path = find_backup(destination, &backup).unwrap();
+        let old_values = xls_read_cell_values(&[(2, 2), (2, 3)], backup_path.to_str().unwrap()).unwrap();
+        assert_eq!(old_values, vec![Some(100.0), None]);
+
+        // the rest of the sheet is untouched
+        assert_eq!(xls_categories_for_year(2024, result_path.to_str().unwrap()).unwrap(), vec!["Groceries", "Sweets"]);
+    }
+
+    #[test]
+    fn test_xls_year_blocks() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workbook_path = temp_dir.path().join("workbook.xlsx");
+        write_test_workbook(&workbook_path);
+
+        let blocks = xls_year_blocks(&xls_read_sheet(workbook_path.to_str().unwrap()).unwrap());
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].year, 2024);
+        assert_eq!(blocks[0].categories, vec!["Groceries", "Sweets"]);
+        assert_eq!(blocks[0].months.len(), 12);
+        assert_eq!(blocks[0].months[0], vec![Some(100.0), None]);
+        assert_eq!(blocks[0].months[1], vec![None, None]);
+    }
+
+    #[test]
+    fn test_xls_read_sheet_and_labels() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workbook_path = temp_dir.path().join("workbook.xlsx");
+        write_test_workbook(&workbook_path);
+
+        let cells = xls_read_sheet(workbook_path.to_str().unwrap()).unwrap();
+        assert_eq!(cells.get(&(1, 1)), Some(&CellValue::Number(2024.0)));
+        assert_eq!(cells.get(&(1, 3)), Some(&CellValue::Text("Sweets".to_string())));
+        assert_eq!(cells.get(&(2, 3)), None);
+
+        assert_eq!(xls_cell_label(&cells, 2, 2).as_deref(), Some("January 2024 Groceries"));
+        assert_eq!(xls_cell_label(&cells, 13, 3).as_deref(), Some("December 2024 Sweets"));
+        assert_eq!(xls_cell_label(&cells, 15, 3), None);
+
+        let months = xls_months_with_data(&cells);
+        assert_eq!(months.get(&2024), Some(&vec!["January".to_string()]));
+    }
+
+    #[test]
+    fn test_xls_year_block_totals() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        xls_perform_logged_workbook_update(january_expenses(), destination).unwrap();
+
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+        let formulas = xls_read_formulas(result_path);
+        let cells = xls_read_sheet(result_path).unwrap();
+
+        // totals column next to Sweets, a Total row under December and a blank row under it
+        assert_eq!(cells.get(&(1, 4)), Some(&CellValue::Text("Total".to_string())));
+        assert_eq!(cells.get(&(14, 1)), Some(&CellValue::Text("Total".to_string())));
+        assert!(cells.range((15, 0)..(16, 0)).next().is_none());
+        assert_eq!(formulas.get(&(2, 4)).map(String::as_str), Some("=IF(COUNT(C3:D3)=0,\"\",SUM(C3:D3))"));
+        assert_eq!(cells.get(&(2, 4)), Some(&CellValue::Number(170.0)));
+        assert_eq!(formulas.get(&(14, 2)).map(String::as_str), Some("=SUM(C3:C14)"));
+        assert_eq!(cells.get(&(14, 2)), Some(&CellValue::Number(150.0)));
+        assert_eq!(formulas.get(&(14, 4)).map(String::as_str), Some("=SUM(E3:E14)"));
+
+        // the totals column is not a category
+        assert_eq!(xls_categories_for_year(2024, result_path).unwrap(), vec!["Groceries", "Sweets"]);
+        assert_eq!(xls_year_blocks(&cells)[0].months[0], vec![Some(150.0), Some(20.0)]);
+        assert_eq!(xls_months_with_data(&cells).get(&2024), Some(&vec!["January".to_string()]));
+        assert!(xls_is_total_cell(&cells, 2, 4) && xls_is_total_cell(&cells, 14, 2) && xls_is_total_cell(&cells, 14, 1));
+        assert!(!xls_is_total_cell(&cells, 2, 3) && !xls_is_total_cell(&cells, 1, 1));
+
+        // next update keeps the formulas and refreshes their values
+        let mut update = HashMap::new();
+        update.insert((2, 3), None);
+        xls_perform_cells_update(&update, destination).unwrap();
+        assert_eq!(xls_read_formulas(result_path), formulas);
+        assert_eq!(xls_read_sheet(result_path).unwrap().get(&(2, 4)), Some(&CellValue::Number(150.0)));
+    }
+
+    #[test]
+    fn test_xls_income_area() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook_with_income(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+        assert_eq!(xls_categories_for_year(2024, result_path).unwrap(), vec!["Groceries", "Sweets"]);
+        assert_eq!(xls_income_sources_for_year(2024, result_path).unwrap(), vec!["Salary", "Interest"]);
+
+        let mut me = january_expenses();
+        me.income_data.insert("Salary".to_string(), 3000.0);
+        me.income_data.insert("Lottery".to_string(), 5.0); // not in the income area, not written
+        let update = xls_perform_logged_workbook_update(me, destination).unwrap();
+        assert_eq!(update.cells.len(), 3);
+        assert_eq!((update.cells[2].category.as_str(), update.cells[2].col), ("Salary", 6));
+
+        let cells = xls_read_sheet(result_path).unwrap();
+        let formulas = xls_read_formulas(result_path);
+        let block = &xls_year_blocks(&cells)[0];
+        assert_eq!(block.categories, vec!["Groceries", "Sweets"]);
+        assert_eq!(block.income_col, Some(5));
+        assert_eq!(block.income[0], vec![Some(3000.0), None]);
+
+        // totals column still goes next to the categories, the income column sums the sources
+        assert_eq!(cells.get(&(1, 4)), Some(&CellValue::Text("Total".to_string())));
+        assert_eq!(cells.get(&(2, 4)), Some(&CellValue::Number(170.0)));
+        assert_eq!(formulas.get(&(2, 5)).map(String::as_str), Some("=IF(COUNT(G3:H3)=0,\"\",SUM(G3:H3))"));
+        assert_eq!(cells.get(&(2, 5)), Some(&CellValue::Number(3000.0)));
+        assert_eq!(formulas.get(&(14, 6)).map(String::as_str), Some("=SUM(G3:G14)"));
+        assert!(xls_is_total_cell(&cells, 2, 5) && !xls_is_total_cell(&cells, 2, 6));
+    }
+
+    #[test]
+    fn test_xls_formulas_are_preserved() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        // February groceries and January total typed in as formulas by the user
+        write_test_workbook_with_formulas(&temp_dir.path().join(RES_WORKBOOK_NAME), &[(3, 2, "=40+2", 42.0), (2, 4, "=C3*2", 200.0)]);
+
+        xls_perform_logged_workbook_update(january_expenses(), destination).unwrap();
+
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let formulas = xls_read_formulas(result_path.to_str().unwrap());
+        assert_eq!(formulas.get(&(3, 2)).map(String::as_str), Some("=40+2"));
+        assert_eq!(formulas.get(&(2, 4)).map(String::as_str), Some("=C3*2"));
+        assert_eq!(formulas.get(&(3, 4)).map(String::as_str), Some("=IF(COUNT(C4:D4)=0,\"\",SUM(C4:D4))"));
+        let values = xls_read_cell_values(&[(2, 2), (3, 2), (3, 4)], result_path.to_str().unwrap()).unwrap();
+        assert_eq!(values, vec![Some(150.0), Some(42.0), Some(42.0)]);
+    }
+
+    #[test]
+    fn test_xls_category_after_totals_column() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        // Rent typed in right of the totals column of an earlier update
+        write_test_workbook_with_headers(&temp_dir.path().join(RES_WORKBOOK_NAME), &[(4, TOTAL_LABEL), (5, "Rent")], &[]);
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+        assert_eq!(xls_categories_for_year(2024, result_path).unwrap(), vec!["Groceries", "Sweets", "Rent"]);
+
+        let mut me = january_expenses();
+        me.expenses_data.insert("Rent".to_string(), 700.0);
+        let update = xls_perform_logged_workbook_update(me, destination).unwrap();
+        assert_eq!(update.cells.iter().find(|cell| cell.category == "Rent").map(|cell| cell.col), Some(5));
+
+        let cells = xls_read_sheet(result_path).unwrap();
+        let formulas = xls_read_formulas(result_path);
+        let block = &xls_year_blocks(&cells)[0];
+        assert_eq!((block.category_cols.clone(), block.total_col), (vec![2, 3, 5], Some(4)));
+        assert_eq!(block.months[0], vec![Some(150.0), Some(20.0), Some(700.0)]);
+        // the totals column stays where it is and sums around itself
+        assert_eq!(cells.get(&(1, 6)), None);
+        assert_eq!(formulas.get(&(2, 4)).map(String::as_str), Some("=IF(COUNT(C3:D3,F3:F3)=0,\"\",SUM(C3:D3,F3:F3))"));
+        assert_eq!(cells.get(&(2, 4)), Some(&CellValue::Number(870.0)));
+        assert_eq!(formulas.get(&(14, 5)).map(String::as_str), Some("=SUM(F3:F14)"));
+    }
+
+    #[test]
+    fn test_xls_old_average_row_is_dropped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let average = "=IFERROR(AVERAGE(C3:C14),\"\")";
+        write_test_workbook_with_formulas(&temp_dir.path().join(RES_WORKBOOK_NAME), &[(15, 2, average, 100.0), (15, 3, "=1+1", 2.0)]);
+        let mut update = HashMap::new();
+        update.insert((3, 2), Some(50.0));
+        xls_perform_cells_update(&update, destination).unwrap();
+        // no label yet, the formula is the user's
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+        assert_eq!(xls_read_formulas(result_path).get(&(15, 2)).map(String::as_str), Some(average));
+
+        // as written by older versions: with the label, only the generated averages go
+        let cells = xls_read_sheet(result_path).unwrap();
+        let mut formulas = xls_read_formulas(result_path);
+        formulas.insert((15, 2), average.to_string());
+        let mut labelled = cells.clone();
+        labelled.insert((15, 1), CellValue::Text(AVERAGE_LABEL.to_string()));
+        let old = xls_old_average_cells(&labelled, &formulas);
+        assert_eq!(old, HashSet::from([(15, 1), (15, 2)]));
+    }
+
+    #[test]
+    fn test_xls_formula_cells_are_not_written() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        // January groceries typed in as a formula
+        write_test_workbook_with_formulas(&temp_dir.path().join(RES_WORKBOOK_NAME), &[(2, 2, "=350*2", 700.0)]);
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+
+        let skipped = xls_formula_cells_to_skip(&january_expenses(), result_path);
+        assert_eq!(skipped.iter().map(|cell| cell.category.as_str()).collect::<Vec<_>>(), vec!["Groceries"]);
+        let update = xls_perform_logged_workbook_update(january_expenses(), destination).unwrap();
+        assert_eq!(update.cells.iter().map(|cell| cell.category.as_str()).collect::<Vec<_>>(), vec!["Sweets"]);
+
+        assert_eq!(xls_read_formulas(result_path).get(&(2, 2)).map(String::as_str), Some("=350*2"));
+        let cells = xls_read_sheet(result_path).unwrap();
+        assert_eq!(cells.get(&(2, 2)), Some(&CellValue::Number(700.0)));
+        assert_eq!(cells.get(&(2, 4)), Some(&CellValue::Number(720.0)));
+    }
+
+    // a sheet of the user before Sheet1: text, a checkbox, a date, an amount and a formula
+    fn write_workbook_with_notes(path: &Path) {
+        let workbook = Workbook::new(path.to_str().unwrap()).unwrap();
+        let mut notes = workbook.add_worksheet(Some("Notes")).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        let mut date = Format::new();
+        date.set_num_format("dd.mm.yyyy");
+        let mut amount = Format::new();
+        amount.set_num_format("#,##0.00");
+        notes.write_string(0, 0, "Savings goal", None).unwrap();
+        notes.write_boolean(0, 1, true, None).unwrap();
+        notes.write_number(1, 1, 45292.0, Some(&date)).unwrap();
+        notes.write_number(2, 1, 1234.5, Some(&amount)).unwrap();
+        notes.write_formula_num(3, 1, "=B3*2", Some(&amount), 2469.0).unwrap();
+        write_test_sheet(&mut sheet, &[], &[]);
+        sheet.write_boolean(0, 0, true, None).unwrap();
+        sheet.write_number(0, 5, 0.5, Some(&amount)).unwrap();
+        workbook.close().unwrap();
+        record_test_workbook(path);
+    }
+
+    // dates come back as numbers, calamine only sees their format in some workbooks
+    fn number_of(cell: Option<&DataType>) -> Option<f64> {
+        match cell {
+            Some(DataType::Float(value)) | Some(DataType::DateTime(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_xls_other_sheets_and_cell_types_are_kept() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let result_path = result_path.to_str().unwrap();
+        write_workbook_with_notes(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        xls_perform_logged_workbook_update(january_expenses(), destination).unwrap();
+        let mut cells = HashMap::new();
+        cells.insert((2, 3), Some(7.5));
+        xls_perform_cells_update(&cells, destination).unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(result_path).unwrap();
+        assert_eq!(workbook.sheet_names()[..2], ["Notes".to_string(), "Sheet1".to_string()]);
+        let notes = workbook.worksheet_range("Notes").unwrap().unwrap();
+        assert_eq!(notes.get_value((0, 0)), Some(&DataType::String("Savings goal".to_string())));
+        assert_eq!(notes.get_value((0, 1)), Some(&DataType::Bool(true)));
+        assert_eq!(number_of(notes.get_value((1, 1))), Some(45292.0));
+        assert_eq!(number_of(notes.get_value((2, 1))), Some(1234.5));
+        assert_eq!(xls_read_sheet_formulas(result_path, "Notes").get(&(3, 1)).map(String::as_str), Some("=B3*2"));
+        let sheet = workbook.worksheet_range("Sheet1").unwrap().unwrap();
+        assert_eq!(sheet.get_value((0, 0)), Some(&DataType::Bool(true)));
+
+        let carry_over = xls_read_carry_over(result_path).unwrap();
+        assert!(carry_over.lost.is_empty(), "{:?}", carry_over.lost);
+        assert_eq!(carry_over.number_format("Notes", 1, 1), Some("dd.mm.yyyy"));
+        assert_eq!(carry_over.number_format("Notes", 2, 1), Some("#,##0.00"));
+        assert_eq!(carry_over.number_format("Notes", 3, 1), Some("#,##0.00"));
+        assert_eq!(carry_over.number_format(DATA_SHEET_NAME, 0, 5), Some("#,##0.00"));
+    }
+
+    #[test]
+    fn test_xls_workbook_with_lost_content_is_not_written() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let result_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        let workbook = Workbook::new(result_path.to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        write_test_sheet(&mut sheet, &[], &[]);
+        sheet.set_column(1, 1, 20.0, None).unwrap();
+        workbook.close().unwrap();
+        record_test_workbook(&result_path);
+        let before = std::fs::read(&result_path).unwrap();
+
+        assert!(xls_perform_logged_workbook_update(january_expenses(), destination).is_none());
+        let mut cells = HashMap::new();
+        cells.insert((2, 3), Some(7.5));
+        assert!(xls_perform_cells_update(&cells, destination).is_none());
+
+        assert_eq!(std::fs::read(&result_path).unwrap(), before);
+        assert!(!temp_dir.path().join(BACKUP_FOLDER).exists());
+    }
 }
@@ -0,0 +1,291 @@
+// optional "Summary" sheet, written next to Sheet1 on every update when summary_sheet is set in
+// the config:
+//  per year totals, monthly totals of every year and per category averages, all as formulas
+//  over the year blocks of Sheet1, so they follow cells edited by hand
+//  a line chart of the monthly totals, a column chart of the years and a pie of the categories
+//  of the latest year
+// formulas are written with the values they have at the time, for viewers that don't calculate.
+// the sheet is written from scratch every time, changes made to it by hand are lost
+
+use xlsxwriter::prelude::*;
+
+use crate::file_handlers::xls_handlers::{xls_year_blocks, SheetCells, YearBlock, STARTING_ROW};
+use crate::utils::config::load_config;
+use crate::utils::transaction_utils::month_name_from_number;
+
+pub const SUMMARY_SHEET_NAME: &str = "Summary";
+pub(crate) const DATA_SHEET_NAME: &str = "Sheet1";
+const CHART_COLUMN: u16 = 8; // charts right of the tables, column I
+const CHART_ROWS: u32 = 16; // a chart is about 15 rows high
+
+// summary sheet if the config asks for it, a failing summary never stops the update
+pub fn xls_add_configured_summary(workbook: &Workbook, destination_path: &str, cells: &SheetCells) {
+    if !load_config(destination_path).summary_sheet {
+        return;
+    }
+    if let Err(e) = xls_write_summary_sheet(workbook, cells) {
+        println!("Failed to write summary sheet: {:?}", e);
+    }
+}
+
+// cells are the ones of Sheet1 as it is written into the same workbook
+pub fn xls_write_summary_sheet(workbook: &Workbook, cells: &SheetCells) -> Result<(), XlsxError> {
+    let blocks = xls_year_blocks(cells);
+    let mut sheet = workbook.add_worksheet(Some(SUMMARY_SHEET_NAME))?;
+    let mut bold = Format::new();
+    bold.set_bold();
+
+    // Year | Total
+    sheet.write_string(0, 0, "Year", Some(&bold))?;
+    sheet.write_string(0, 1, "Total", Some(&bold))?;
+    for (index, block) in blocks.iter().enumerate() {
+        let row = 1 + index as u32;
+        sheet.write_number(row, 0, block.year as f64, None)?;
+        let values: Vec<f64> = block.months.iter().flatten().flatten().copied().collect();
+        write_formula(&mut sheet, row, 1, block_range(index, block, 1, 12).map(|range| format!("=SUM({})", range)), Some(sum(&values)))?;
+    }
+
+    // Month | one column per year, months without data are #N/A so lines stop there
+    let months_top = blocks.len() as u32 + 2;
+    sheet.write_string(months_top, 0, "Month", Some(&bold))?;
+    for month in 1..=12 {
+        sheet.write_string(months_top + month, 0, month_name_from_number(month).unwrap_or_default(), None)?;
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        let col = 1 + index as u16;
+        sheet.write_number(months_top, col, block.year as f64, Some(&bold))?;
+        for month in 1..=12 {
+            let values: Vec<f64> = block.months[month as usize - 1].iter().flatten().copied().collect();
+            let formula = block_range(index, block, month, month).map(|range| format!("=IF(COUNT({r})=0,NA(),SUM({r}))", r = range));
+            write_formula(&mut sheet, months_top + month, col, formula, (!values.is_empty()).then(|| sum(&values)))?;
+        }
+    }
+
+    // Category | average of the filled in months of every year | total of the latest year
+    let categories_top = months_top + 14;
+    let mut categories: Vec<&String> = Vec::new();
+    for category in blocks.iter().flat_map(|block| &block.categories) {
+        if !categories.contains(&category) {
+            categories.push(category);
+        }
+    }
+    sheet.write_string(categories_top, 0, "Category", Some(&bold))?;
+    for (index, block) in blocks.iter().enumerate() {
+        sheet.write_string(categories_top, 1 + index as u16, &format!("Average {}", block.year), Some(&bold))?;
+    }
+    let total_col = 1 + blocks.len() as u16;
+    if let Some(latest) = blocks.last() {
+        sheet.write_string(categories_top, total_col, &format!("Total {}", latest.year), Some(&bold))?;
+    }
+
+    for (position, category) in categories.iter().enumerate() {
+        let row = categories_top + 1 + position as u32;
+        sheet.write_string(row, 0, category, None)?;
+        for (index, block) in blocks.iter().enumerate() {
+            let Some(category_index) = block.categories.iter().position(|name| name == *category) else {
+                continue; // no such category that year
+            };
+            let values: Vec<f64> = block.months.iter().filter_map(|month| month[category_index]).collect();
+            let range = category_range(index, block.category_cols[category_index]);
+            let average = (!values.is_empty()).then(|| sum(&values) / values.len() as f64);
+            write_formula(&mut sheet, row, 1 + index as u16, Some(format!("=IFERROR(AVERAGE({}),\"\")", range)), average)?;
+            if index + 1 == blocks.len() {
+                write_formula(&mut sheet, row, total_col, Some(format!("=SUM({})", range)), Some(sum(&values)))?;
+            }
+        }
+    }
+
+    let Some(latest) = blocks.last() else {
+        return Ok(()); // nothing to draw
+    };
+
+    let mut monthly = workbook.add_chart(ChartType::Line);
+    for (index, block) in blocks.iter().enumerate() {
+        let months = summary_range(months_top + 1, 0, months_top + 12);
+        let values = summary_range(months_top + 1, 1 + index as u16, months_top + 12);
+        monthly.add_series(Some(&months), Some(&values))?.set_name(&block.year.to_string())?;
+    }
+    sheet.insert_chart(0, CHART_COLUMN, &monthly)?;
+
+    let mut yearly = workbook.add_chart(ChartType::Column);
+    let years = summary_range(1, 0, blocks.len() as u32);
+    yearly.add_series(Some(&years), Some(&summary_range(1, 1, blocks.len() as u32)))?.set_name("Total")?;
+    sheet.insert_chart(CHART_ROWS, CHART_COLUMN, &yearly)?;
+
+    if !categories.is_empty() {
+        let last_row = categories_top + categories.len() as u32;
+        let mut shares = workbook.add_chart(ChartType::Pie);
+        let names = summary_range(categories_top + 1, 0, last_row);
+        shares.add_series(Some(&names), Some(&summary_range(categories_top + 1, total_col, last_row)))?.set_name(&latest.year.to_string())?;
+        sheet.insert_chart(2 * CHART_ROWS, CHART_COLUMN, &shares)?;
+    }
+    Ok(())
+}
+
+// formula with the value it has now, no formula at all is 0
+pub(crate) fn write_formula(sheet: &mut Worksheet, row: u32, col: u16, formula: Option<String>, value: Option<f64>) -> Result<(), XlsxError> {
+    match (formula, value) {
+        (Some(formula), Some(value)) => sheet.write_formula_num(row, col, &formula, None, value),
+        (Some(formula), None) => sheet.write_formula(row, col, &formula, None),
+        (None, _) => sheet.write_number(row, col, 0.0, None),
+    }
+}
+
+// months (1 - 12) of every category of the block, None for a block without categories
+//  Sheet1!C3:H14, or Sheet1!C3:D14,Sheet1!F3:F14 around a totals column
+fn block_range(index: usize, block: &YearBlock, first_month: u32, last_month: u32) -> Option<String> {
+    if block.categories.is_empty() {
+        return None;
+    }
+    let year_row = block_row(index);
+    let ranges: Vec<String> = column_ranges(year_row + first_month, year_row + last_month, &block.category_cols)
+        .iter()
+        .map(|range| format!("{}!{}", DATA_SHEET_NAME, range))
+        .collect();
+    Some(ranges.join(","))
+}
+
+// all months of one category column of the block
+//  Sheet1!D3:D14
+fn category_range(index: usize, col: u32) -> String {
+    let year_row = block_row(index);
+    format!("{}!{}:{}", DATA_SHEET_NAME, cell_name(year_row + 1, col), cell_name(year_row + 12, col))
+}
+
+// cells of one column of the summary sheet, as charts want them
+//  =Summary!$B$2:$B$4
+fn summary_range(first_row: u32, col: u16, last_row: u32) -> String {
+    let column = column_name(col as u32);
+    format!("={}!${}${}:${}${}", SUMMARY_SHEET_NAME, column, first_row + 1, column, last_row + 1)
+}
+
+// header row of the n-th year block, blocks are 15 rows apart
+pub(crate) fn block_row(index: usize) -> u32 {
+    STARTING_ROW + 15 * index as u32
+}
+
+// (2, 3) -> D3
+pub(crate) fn cell_name(row: u32, col: u32) -> String {
+    format!("{}{}", column_name(col), row + 1)
+}
+
+// ranges of the columns from first_row to last_row, neighbouring columns go into one range
+//  [2, 3, 5] -> C3:D14, F3:F14
+pub(crate) fn column_ranges(first_row: u32, last_row: u32, cols: &[u32]) -> Vec<String> {
+    let mut runs: Vec<(u32, u32)> = Vec::new();
+    for &col in cols {
+        match runs.last_mut() {
+            Some((_, last)) if *last + 1 == col => *last = col,
+            _ => runs.push((col, col)),
+        }
+    }
+    runs.iter()
+        .map(|&(first, last)| format!("{}:{}", cell_name(first_row, first), cell_name(last_row, last)))
+        .collect()
+}
+
+// 0 -> A, 25 -> Z, 26 -> AA
+fn column_name(col: u32) -> String {
+    let mut name = String::new();
+    let mut col = col + 1;
+    while col > 0 {
+        let letter = (col - 1) % 26;
+        name.insert(0, (b'A' + letter as u8) as char);
+        col = (col - 1) / 26;
+    }
+    name
+}
+
+// 0 for no values, an empty float sum would be -0
+fn sum(values: &[f64]) -> f64 {
+    values.iter().fold(0.0, |total, value| total + value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::{xls_perform_cells_update, CellValue, RES_WORKBOOK_NAME};
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::utils::config::{save_config, AppConfig};
+    use calamine::{open_workbook, DataType, Reader, Xlsx};
+    use std::collections::HashMap;
+
+    // 2023 with Groceries, 2024 with Groceries and Rent
+    fn cells() -> SheetCells {
+        let mut cells = SheetCells::new();
+        cells.insert((1, 1), CellValue::Number(2023.0));
+        cells.insert((1, 2), CellValue::Text("Groceries".to_string()));
+        cells.insert((2, 2), CellValue::Number(80.0)); // January 2023
+        cells.insert((16, 1), CellValue::Number(2024.0));
+        cells.insert((16, 2), CellValue::Text("Groceries".to_string()));
+        cells.insert((16, 3), CellValue::Text("Rent".to_string()));
+        cells.insert((17, 2), CellValue::Number(100.0)); // January 2024
+        cells.insert((17, 3), CellValue::Number(500.0));
+        cells.insert((18, 2), CellValue::Number(300.0)); // February 2024
+        cells
+    }
+
+    #[test]
+    fn test_xls_write_summary_sheet() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("summary.xlsx");
+        let workbook = Workbook::new(path.to_str().unwrap()).unwrap();
+        workbook.add_worksheet(Some("Sheet1")).unwrap();
+        xls_write_summary_sheet(&workbook, &cells()).unwrap();
+        workbook.close().unwrap();
+
+        let mut workbook: Xlsx<_> = open_workbook(&path).unwrap();
+        let formulas = workbook.worksheet_formula(SUMMARY_SHEET_NAME).unwrap().unwrap();
+        let values = workbook.worksheet_range(SUMMARY_SHEET_NAME).unwrap().unwrap();
+        let formula = |row: u32, col: u32| formulas.get_value((row, col)).cloned().unwrap_or_default();
+
+        // per year totals
+        assert_eq!(formula(1, 1), "SUM(Sheet1!C3:C14)");
+        assert_eq!(formula(2, 1), "SUM(Sheet1!C18:D29)");
+        assert_eq!(values.get_value((2, 1)), Some(&DataType::Float(900.0)));
+
+        // January 2024 of the monthly totals, table starts at row 4
+        assert_eq!(values.get_value((4, 2)), Some(&DataType::Float(2024.0)));
+        assert_eq!(formula(5, 2), "IF(COUNT(Sheet1!C18:D18)=0,NA(),SUM(Sheet1!C18:D18))");
+        assert_eq!(values.get_value((5, 2)), Some(&DataType::Float(600.0)));
+
+        // Groceries averages and latest total, Rent only in 2024
+        assert_eq!(values.get_value((19, 0)), Some(&DataType::String("Groceries".to_string())));
+        assert_eq!(formula(19, 2), "IFERROR(AVERAGE(Sheet1!C18:C29),\"\")");
+        assert_eq!(values.get_value((19, 2)), Some(&DataType::Float(200.0)));
+        assert_eq!(formula(19, 3), "SUM(Sheet1!C18:C29)");
+        assert_eq!(formula(20, 1), "");
+        assert_eq!(formula(20, 3), "SUM(Sheet1!D18:D29)");
+    }
+
+    #[test]
+    fn test_summary_follows_config() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        let sheet_names = || {
+            let workbook: Xlsx<_> = open_workbook(temp_dir.path().join(RES_WORKBOOK_NAME)).unwrap();
+            workbook.sheet_names().to_vec()
+        };
+
+        // off by default
+        xls_perform_cells_update(&HashMap::from([((2, 3), Some(5.0))]), destination).unwrap();
+        assert_eq!(sheet_names(), vec!["Sheet1"]);
+
+        save_config(destination, &AppConfig { summary_sheet: true, ..AppConfig::default() }).unwrap();
+        xls_perform_cells_update(&HashMap::from([((3, 2), Some(7.0))]), destination).unwrap();
+        assert_eq!(sheet_names(), vec!["Sheet1", SUMMARY_SHEET_NAME]);
+
+        // regenerated on the next update, not added twice
+        xls_perform_cells_update(&HashMap::from([((3, 2), None)]), destination).unwrap();
+        assert_eq!(sheet_names(), vec!["Sheet1", SUMMARY_SHEET_NAME]);
+    }
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(cell_name(2, 3), "D3");
+    }
+}
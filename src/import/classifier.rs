@@ -0,0 +1,221 @@
+// category suggestions learned from the transaction history
+// multinomial naive Bayes over description + counterparty tokens, no extra dependencies:
+//  score(c) = log P(c) + sum over tokens log P(token | c)   (Laplace smoothing)
+// confidence is the posterior probability of the best category among every allowed category
+// seen in training, not only the ones sharing a token. a suggestion is only applied without
+// asking when its tokens were seen often enough in the category (one past transaction is not enough)
+
+use std::collections::{HashMap, HashSet};
+
+use crate::utils::structs::Transaction;
+
+// times the transaction's tokens were seen in the suggested category before it is applied unasked
+pub const MIN_AUTO_APPLY_EVIDENCE: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub category: String,
+    pub confidence: f64, // 0.0 - 1.0
+    pub evidence: usize, // occurrences of the transaction's tokens in the category's history
+}
+
+impl Suggestion {
+    pub fn can_auto_apply(&self, auto_apply_confidence: f64) -> bool {
+        self.confidence >= auto_apply_confidence && self.evidence >= MIN_AUTO_APPLY_EVIDENCE
+    }
+}
+
+#[derive(Default)]
+struct CategoryStats {
+    documents: usize,
+    tokens_total: usize,
+    token_counts: HashMap<String, usize>,
+}
+
+#[derive(Default)]
+pub struct CategoryClassifier {
+    categories: HashMap<String, CategoryStats>,
+    vocabulary: HashSet<String>,
+    documents_total: usize,
+}
+
+impl CategoryClassifier {
+    // trains on categorised transactions, uncategorised ones are ignored
+    pub fn train(history: &[Transaction]) -> CategoryClassifier {
+        let mut classifier = CategoryClassifier::default();
+
+        for transaction in history {
+            let Some(category) = &transaction.category else {
+                continue;
+            };
+            let tokens = transaction_tokens(transaction);
+            if tokens.is_empty() {
+                continue;
+            }
+
+            let stats = classifier.categories.entry(category.clone()).or_default();
+            stats.documents += 1;
+            stats.tokens_total += tokens.len();
+            for token in tokens {
+                *stats.token_counts.entry(token.clone()).or_insert(0) += 1;
+                classifier.vocabulary.insert(token);
+            }
+            classifier.documents_total += 1;
+        }
+
+        classifier
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents_total == 0
+    }
+
+    // best category among the allowed ones (header row of the year)
+    // None if the transaction shares no token with anything seen before
+    pub fn suggest(&self, transaction: &Transaction, allowed_categories: &[String]) -> Option<Suggestion> {
+        // words never seen in training say nothing about any category
+        let tokens: Vec<String> = transaction_tokens(transaction)
+            .into_iter()
+            .filter(|token| self.vocabulary.contains(token))
+            .collect();
+        if tokens.is_empty() {
+            return None;
+        }
+
+        let vocabulary_size = self.vocabulary.len() as f64;
+        // (category, score, evidence) of every allowed category seen in training
+        let mut scores: Vec<(&String, f64, usize)> = Vec::new();
+
+        for category in allowed_categories {
+            let Some(stats) = self.categories.get(category) else {
+                continue;
+            };
+
+            let mut score = (stats.documents as f64 / self.documents_total as f64).ln();
+            let mut evidence = 0;
+            for token in &tokens {
+                let count = *stats.token_counts.get(token).unwrap_or(&0);
+                evidence += count;
+                score += ((count as f64 + 1.0) / (stats.tokens_total as f64 + vocabulary_size)).ln();
+            }
+            scores.push((category, score, evidence));
+        }
+
+        // softmax over log scores, shifted by the max to avoid underflow
+        let max_score = scores.iter().map(|(_, score, _)| *score).fold(f64::NEG_INFINITY, f64::max);
+        let normaliser: f64 = scores.iter().map(|(_, score, _)| (score - max_score).exp()).sum();
+
+        // a category that never saw any of these tokens can't be suggested
+        scores
+            .into_iter()
+            .filter(|(_, _, evidence)| *evidence > 0)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(category, score, evidence)| Suggestion {
+                category: category.clone(),
+                confidence: (score - max_score).exp() / normaliser,
+                evidence,
+            })
+    }
+}
+
+// lower case words of description and counterparty, numbers (card numbers, dates, references) are dropped
+fn transaction_tokens(transaction: &Transaction) -> Vec<String> {
+    let counterparty = transaction.counterparty.as_deref().unwrap_or_default();
+    format!("{} {}", transaction.description, counterparty)
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|token| token.chars().count() > 1 && !token.chars().all(|c| c.is_ascii_digit()))
+        .map(|token| token.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::structs::CreditDebit;
+    use chrono::NaiveDate;
+
+    fn transaction(description: &str, counterparty: Option<&str>, category: Option<&str>) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            amount: 1.0,
+            credit_debit: CreditDebit::Debit,
+            description: description.to_string(),
+            counterparty: counterparty.map(|c| c.to_string()),
+            category: category.map(|c| c.to_string()),
+        }
+    }
+
+    fn history() -> Vec<Transaction> {
+        // a year and a half of weekly shopping
+        let mut history = Vec::new();
+        for _ in 0..80 {
+            history.push(transaction("Card payment 4411", Some("Lidl Berlin"), Some("Groceries")));
+            history.push(transaction("Card payment", Some("Candy Store"), Some("Sweets")));
+            history.push(transaction("Card payment", Some("Pizza Place"), Some("Restaurants")));
+        }
+        history.push(transaction("uncategorised", None, None));
+        history
+    }
+
+    fn categories() -> Vec<String> {
+        vec!["Groceries".to_string(), "Sweets".to_string(), "Restaurants".to_string()]
+    }
+
+    #[test]
+    fn test_tokens() {
+        let tokens = transaction_tokens(&transaction("Card payment 4411 / 05.01", Some("Lidl-Berlin"), None));
+        assert_eq!(tokens, vec!["card", "payment", "lidl", "berlin"]);
+    }
+
+    #[test]
+    fn test_suggest_known_counterparty() {
+        let classifier = CategoryClassifier::train(&history());
+        let suggestion = classifier.suggest(&transaction("Card payment 9999", Some("LIDL Hamburg"), None), &categories()).unwrap();
+
+        assert_eq!(suggestion.category, "Groceries");
+        assert!(suggestion.confidence > 0.9, "confidence was {}", suggestion.confidence);
+        assert!(suggestion.can_auto_apply(0.9));
+    }
+
+    #[test]
+    fn test_suggest_weak_evidence() {
+        let mut history = history();
+        history.push(transaction("Direct debit", Some("Gym"), Some("Sport")));
+        let mut categories = categories();
+        categories.push("Sport".to_string());
+        let classifier = CategoryClassifier::train(&history);
+
+        // one past transaction shares "gym", the other categories still count against it
+        let suggestion = classifier.suggest(&transaction("Gym shop", None, None), &categories).unwrap();
+        assert_eq!((suggestion.category.as_str(), suggestion.evidence), ("Sport", 1));
+        assert!(suggestion.confidence < 1.0);
+        assert!(!suggestion.can_auto_apply(0.0));
+    }
+
+    #[test]
+    fn test_suggest_ambiguous_has_low_confidence() {
+        let classifier = CategoryClassifier::train(&history());
+        // only "card payment" is known, and it appears in every category
+        let suggestion = classifier.suggest(&transaction("Card payment", Some("Unknown shop"), None), &categories()).unwrap();
+        assert!(suggestion.confidence < 0.9, "confidence was {}", suggestion.confidence);
+    }
+
+    #[test]
+    fn test_suggest_unknown_tokens_or_categories() {
+        let classifier = CategoryClassifier::train(&history());
+        assert!(classifier.suggest(&transaction("Netflix", None, None), &categories()).is_none());
+
+        // Groceries is not in this year's header row, so only the other categories count
+        let allowed = vec!["Sweets".to_string()];
+        let suggestion = classifier.suggest(&transaction("Lidl", Some("Candy Store"), None), &allowed).unwrap();
+        assert_eq!(suggestion.category, "Sweets");
+    }
+
+    #[test]
+    fn test_empty_classifier() {
+        let classifier = CategoryClassifier::train(&[]);
+        assert!(classifier.is_empty());
+        assert!(classifier.suggest(&transaction("Lidl", None, None), &categories()).is_none());
+    }
+}
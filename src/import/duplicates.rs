@@ -0,0 +1,211 @@
+// duplicate detection for imports
+// a transaction is fingerprinted on date, signed amount and normalised description:
+//  2024-01-05|-1250|card payment bakery
+// exact fingerprint match = duplicate, same amount within a few days with a similar
+// description = likely duplicate (bank books the same payment on a different day).
+// notes have no dates or descriptions (booked on the 1st, described by the category), so
+// against a notes entry the same category and amount in the same month is a duplicate
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use chrono::Datelike;
+use sha2::{Digest, Sha256};
+
+use crate::utils::structs::{CreditDebit, Transaction};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DuplicateKind {
+    Exact,
+    Likely,
+    SameMonth, // same category and amount in the month of a notes entry
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DuplicateMatch {
+    pub kind: DuplicateKind,
+    pub matched: Transaction, // earlier transaction it collides with
+}
+
+// sha256 of the file content, used to skip files that were already imported
+pub fn file_content_hash(filepath: &str) -> io::Result<String> {
+    let data = fs::read(filepath)?;
+    Ok(format!("{:x}", Sha256::digest(&data)))
+}
+
+// lower case words only, numbers and punctuation (card numbers, references) are dropped
+pub fn normalise_description(description: &str) -> String {
+    description
+        .to_lowercase()
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+pub fn fingerprint(transaction: &Transaction) -> String {
+    format!(
+        "{}|{}|{}",
+        transaction.booking_date.format("%Y-%m-%d"),
+        signed_cents(transaction),
+        normalise_description(&transaction.description)
+    )
+}
+
+// looks for the transaction among earlier ones, exact matches win over likely ones
+pub fn find_duplicate(transaction: &Transaction, earlier: &[Transaction], date_window_days: i64) -> Option<DuplicateMatch> {
+    let own_fingerprint = fingerprint(transaction);
+    if let Some(matched) = earlier.iter().find(|other| fingerprint(other) == own_fingerprint) {
+        return Some(DuplicateMatch { kind: DuplicateKind::Exact, matched: matched.clone() });
+    }
+
+    let own_words = description_words(transaction);
+    earlier
+        .iter()
+        .filter(|other| signed_cents(other) == signed_cents(transaction))
+        .filter(|other| (other.booking_date - transaction.booking_date).num_days().abs() <= date_window_days)
+        .find(|other| descriptions_similar(&own_words, &description_words(other)))
+        .map(|matched| DuplicateMatch { kind: DuplicateKind::Likely, matched: matched.clone() })
+        .or_else(|| {
+            earlier
+                .iter()
+                .filter(|other| is_from_notes(transaction) || is_from_notes(other))
+                .find(|other| {
+                    transaction.category.is_some()
+                        && other.category == transaction.category
+                        && signed_cents(other) == signed_cents(transaction)
+                        && (other.booking_date.year(), other.booking_date.month())
+                            == (transaction.booking_date.year(), transaction.booking_date.month())
+                })
+                .map(|matched| DuplicateMatch { kind: DuplicateKind::SameMonth, matched: matched.clone() })
+        })
+}
+
+// entries of notes are booked on the 1st, described by their category and have no counterparty
+pub fn is_from_notes(transaction: &Transaction) -> bool {
+    transaction.booking_date.day() == 1
+        && transaction.counterparty.is_none()
+        && transaction.category.as_deref() == Some(transaction.description.as_str())
+}
+
+// amounts are compared in cents to avoid float noise, refunds are negative
+fn signed_cents(transaction: &Transaction) -> i64 {
+    let cents = (transaction.amount * 100.0).round() as i64;
+    match transaction.credit_debit {
+        CreditDebit::Debit => cents,
+        CreditDebit::Credit => -cents,
+    }
+}
+
+fn description_words(transaction: &Transaction) -> HashSet<String> {
+    let counterparty = transaction.counterparty.as_deref().unwrap_or_default();
+    normalise_description(&format!("{} {}", transaction.description, counterparty))
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+// at least half of the words in common (Jaccard index)
+fn descriptions_similar(a: &HashSet<String>, b: &HashSet<String>) -> bool {
+    if a.is_empty() && b.is_empty() {
+        return true;
+    }
+    let common = a.intersection(b).count() as f64;
+    let all = a.union(b).count() as f64;
+    common / all >= 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn transaction(day: u32, amount: f64, description: &str) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 1, day).unwrap(),
+            amount,
+            credit_debit: CreditDebit::Debit,
+            description: description.to_string(),
+            counterparty: None,
+            category: None,
+        }
+    }
+
+    #[test]
+    fn test_normalise_description() {
+        assert_eq!(normalise_description("  Card-Payment 4411/05.01  BAKERY "), "card payment bakery");
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let mut refund = transaction(5, 12.5, "Bakery 123");
+        assert_eq!(fingerprint(&refund), "2024-01-05|1250|bakery");
+        refund.credit_debit = CreditDebit::Credit;
+        assert_eq!(fingerprint(&refund), "2024-01-05|-1250|bakery");
+    }
+
+    #[test]
+    fn test_find_exact_duplicate() {
+        let earlier = vec![transaction(5, 12.5, "Bakery ref 1"), transaction(5, 3.0, "Coffee")];
+        let result = find_duplicate(&transaction(5, 12.5, "Bakery ref 2"), &earlier, 3).unwrap();
+        assert_eq!(result.kind, DuplicateKind::Exact);
+        assert_eq!(result.matched, earlier[0]);
+    }
+
+    #[test]
+    fn test_find_likely_duplicate_within_window() {
+        let earlier = vec![transaction(5, 12.5, "Card payment Bakery Berlin")];
+
+        let shifted = transaction(7, 12.5, "Bakery Berlin card");
+        assert_eq!(find_duplicate(&shifted, &earlier, 3).unwrap().kind, DuplicateKind::Likely);
+
+        // too far away, different amount or different description
+        assert!(find_duplicate(&transaction(12, 12.5, "Bakery Berlin card"), &earlier, 3).is_none());
+        assert!(find_duplicate(&transaction(6, 12.4, "Bakery Berlin card"), &earlier, 3).is_none());
+        assert!(find_duplicate(&transaction(6, 12.5, "Pharmacy"), &earlier, 3).is_none());
+    }
+
+    #[test]
+    fn test_find_notes_duplicate() {
+        let mut notes = transaction(1, 12.5, "Groceries");
+        notes.category = Some("Groceries".to_string());
+        let mut bank = transaction(17, 12.5, "Card payment Lidl");
+        bank.category = Some("Groceries".to_string());
+        assert!(is_from_notes(&notes) && !is_from_notes(&bank));
+
+        // either way around
+        assert_eq!(find_duplicate(&bank, &[notes.clone()], 3).unwrap().kind, DuplicateKind::SameMonth);
+        assert_eq!(find_duplicate(&notes, &[bank.clone()], 3).unwrap().kind, DuplicateKind::SameMonth);
+
+        // another category, amount or month
+        let mut other = bank.clone();
+        other.category = Some("Sweets".to_string());
+        assert!(find_duplicate(&other, &[notes.clone()], 3).is_none());
+        other = bank.clone();
+        other.amount = 12.0;
+        assert!(find_duplicate(&other, &[notes.clone()], 3).is_none());
+        other = bank.clone();
+        other.booking_date = NaiveDate::from_ymd_opt(2024, 2, 17).unwrap();
+        assert!(find_duplicate(&other, &[notes.clone()], 3).is_none());
+        // two bank transactions are never compared by month only
+        assert!(find_duplicate(&transaction(20, 12.5, "Bakery"), &[bank], 3).is_none());
+    }
+
+    #[test]
+    fn test_file_content_hash() {
+        let temp_dir = tempdir().unwrap();
+        let first = temp_dir.path().join("a.txt");
+        let second = temp_dir.path().join("b.txt");
+        fs::write(&first, "March 2024").unwrap();
+        fs::write(&second, "March 2024").unwrap();
+
+        let first_hash = file_content_hash(first.to_str().unwrap()).unwrap();
+        assert_eq!(first_hash.len(), 64);
+        assert_eq!(first_hash, file_content_hash(second.to_str().unwrap()).unwrap());
+
+        fs::write(&second, "April 2024").unwrap();
+        assert_ne!(first_hash, file_content_hash(second.to_str().unwrap()).unwrap());
+    }
+}
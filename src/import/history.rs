@@ -0,0 +1,120 @@
+// every categorised transaction that made it into the spreadsheet is kept here,
+// one json object per line, so later imports can learn from past choices
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::structs::Transaction;
+
+const TRANSACTION_HISTORY_FILE: &str = "transaction_history.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HistoryRecord {
+    #[serde(flatten)]
+    pub transaction: Transaction,
+    #[serde(default)]
+    pub source_hash: String, // content hash of the imported file, empty for old records
+}
+
+// appends transactions to the history file in the destination folder
+pub fn append_transaction_history(destination_path: &str, transactions: &[Transaction], source_hash: &str) -> io::Result<()> {
+    let history_path = Path::new(destination_path).join(TRANSACTION_HISTORY_FILE);
+    let mut file = OpenOptions::new().create(true).append(true).open(history_path)?;
+
+    for transaction in transactions {
+        let record = HistoryRecord {
+            transaction: transaction.clone(),
+            source_hash: source_hash.to_string(),
+        };
+        let line = serde_json::to_string(&record).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writeln!(file, "{}", line)?;
+    }
+    Ok(())
+}
+
+// reads the whole history, broken lines are skipped
+pub fn load_transaction_history(destination_path: &str) -> Vec<HistoryRecord> {
+    let history_path = Path::new(destination_path).join(TRANSACTION_HISTORY_FILE);
+
+    match fs::read_to_string(history_path) {
+        Ok(data) => data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    println!("Skipping broken history line: {}", e);
+                    None
+                }
+            })
+            .collect(),
+        Err(_) => Vec::new(), // nothing imported yet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::structs::CreditDebit;
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn transaction(description: &str) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(),
+            amount: 4.2,
+            credit_debit: CreditDebit::Debit,
+            description: description.to_string(),
+            counterparty: Some("Shop".to_string()),
+            category: Some("Groceries".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_history() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        assert!(load_transaction_history(destination).is_empty());
+
+        append_transaction_history(destination, &[transaction("first")], "aaa").unwrap();
+        append_transaction_history(destination, &[transaction("second"), transaction("third")], "bbb").unwrap();
+
+        let history = load_transaction_history(destination);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].transaction, transaction("first"));
+        assert_eq!(history[0].source_hash, "aaa");
+        assert_eq!(history[2].transaction.description, "third");
+        assert_eq!(history[2].source_hash, "bbb");
+    }
+
+    #[test]
+    fn test_records_without_hash_are_loaded() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        // written before source hashes were recorded
+        let line = serde_json::to_string(&transaction("old")).unwrap();
+        fs::write(temp_dir.path().join(TRANSACTION_HISTORY_FILE), format!("{}\n", line)).unwrap();
+
+        let history = load_transaction_history(destination);
+        assert_eq!(history[0].transaction, transaction("old"));
+        assert_eq!(history[0].source_hash, "");
+    }
+
+    #[test]
+    fn test_broken_lines_are_skipped() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        append_transaction_history(destination, &[transaction("first")], "aaa").unwrap();
+        let history_path = temp_dir.path().join(TRANSACTION_HISTORY_FILE);
+        let mut data = fs::read_to_string(&history_path).unwrap();
+        data.push_str("{ broken\n");
+        fs::write(&history_path, data).unwrap();
+
+        assert_eq!(load_transaction_history(destination).len(), 1);
+    }
+}
@@ -0,0 +1,184 @@
+// append-only log of every import written into the spreadsheet, one json object per line
+// in the destination folder, so it is possible to tell what was written, from which file and when
+
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::structs::{CellChange, MonthExpenses};
+
+const IMPORT_LOG_FILE: &str = "import_log.jsonl";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ImportLogEntry {
+    pub id: u64, // import number, counts from 1
+    pub timestamp: DateTime<Utc>,
+    pub source_file: String,
+    pub source_hash: String,
+    pub month_expenses: Vec<MonthExpenses>,
+    pub cells: Vec<CellChange>, // every cell written, with the value it had before
+    pub backup_file: Option<String>, // name of the backup holding the workbook as it was before the import
+    #[serde(default)]
+    pub undo_of: Option<u64>, // set for undo entries, cells are the ones that were reverted
+}
+
+impl ImportLogEntry {
+    // one line description for the GUI and CLI
+    //  #3 2024-06-02 18:40 statement.xml: May 2024, June 2024 (7 cells)
+    //  #4 2024-06-03 09:12 undo of #3 (7 cells)
+    pub fn summary(&self) -> String {
+        if let Some(undone_id) = self.undo_of {
+            return format!("#{} {} undo of #{} ({} cells)", self.id, self.timestamp.format("%Y-%m-%d %H:%M"), undone_id, self.cells.len());
+        }
+
+        let file_name = Path::new(&self.source_file)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| self.source_file.clone());
+        let mut months: Vec<String> = self.month_expenses.iter().map(|me| format!("{} {}", me.month, me.year)).collect();
+        if months.is_empty() {
+            months.push("nothing written".to_string()); // every transaction was left out
+        }
+
+        format!(
+            "#{} {} {}: {} ({} cells)",
+            self.id,
+            self.timestamp.format("%Y-%m-%d %H:%M"),
+            file_name,
+            months.join(", "),
+            self.cells.len()
+        )
+    }
+}
+
+// ids of imports that were undone
+pub fn undone_imports(log: &[ImportLogEntry]) -> HashSet<u64> {
+    log.iter().filter_map(|entry| entry.undo_of).collect()
+}
+
+// source hashes of undone imports, their transactions don't count as imported anymore
+pub fn undone_source_hashes(log: &[ImportLogEntry]) -> HashSet<String> {
+    let undone = undone_imports(log);
+    log.iter()
+        .filter(|entry| undone.contains(&entry.id) && !entry.source_hash.is_empty())
+        .map(|entry| entry.source_hash.clone())
+        .collect()
+}
+
+// appends the entry with the next free id, returns that id
+pub fn append_import_log(destination_path: &str, mut entry: ImportLogEntry) -> io::Result<u64> {
+    entry.id = load_import_log(destination_path).iter().map(|e| e.id).max().unwrap_or(0) + 1;
+
+    let log_path = Path::new(destination_path).join(IMPORT_LOG_FILE);
+    let line = serde_json::to_string(&entry).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(log_path)?;
+    writeln!(file, "{}", line)?;
+    Ok(entry.id)
+}
+
+// reads the whole log, oldest first, broken lines are skipped
+pub fn load_import_log(destination_path: &str) -> Vec<ImportLogEntry> {
+    let log_path = Path::new(destination_path).join(IMPORT_LOG_FILE);
+
+    match fs::read_to_string(log_path) {
+        Ok(data) => data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(entry) => Some(entry),
+                Err(e) => {
+                    println!("Skipping broken import log line: {}", e);
+                    None
+                }
+            })
+            .collect(),
+        Err(_) => Vec::new(), // nothing imported yet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    fn entry(source_file: &str) -> ImportLogEntry {
+        let mut expenses_data = HashMap::new();
+        expenses_data.insert("Groceries".to_string(), 150.0);
+
+        ImportLogEntry {
+            id: 0,
+            timestamp: Utc.with_ymd_and_hms(2024, 6, 2, 18, 40, 0).unwrap(),
+            source_file: source_file.to_string(),
+            source_hash: "abc".to_string(),
+            month_expenses: vec![MonthExpenses { year: 2024, month: "May".to_string(), expenses_data, income_data: HashMap::new() }],
+            cells: vec![CellChange {
+                row: 6,
+                col: 2,
+                year: 2024,
+                month: "May".to_string(),
+                category: "Groceries".to_string(),
+                previous: None,
+                value: 150.0,
+            }],
+            backup_file: Some("backup_20240602_184000.xlsx".to_string()),
+            undo_of: None,
+        }
+    }
+
+    #[test]
+    fn test_append_and_load_import_log() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        assert!(load_import_log(destination).is_empty());
+        assert_eq!(append_import_log(destination, entry("first.xml")).unwrap(), 1);
+        assert_eq!(append_import_log(destination, entry("second.xml")).unwrap(), 2);
+
+        let log = load_import_log(destination);
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0], ImportLogEntry { id: 1, ..entry("first.xml") });
+        assert_eq!(log[1].id, 2);
+        assert_eq!(log[1].source_file, "second.xml");
+    }
+
+    #[test]
+    fn test_broken_lines_are_skipped() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        append_import_log(destination, entry("first.xml")).unwrap();
+        let log_path = temp_dir.path().join(IMPORT_LOG_FILE);
+        let mut data = fs::read_to_string(&log_path).unwrap();
+        data.push_str("{ broken\n");
+        fs::write(&log_path, data).unwrap();
+
+        assert_eq!(load_import_log(destination).len(), 1);
+        // ids keep counting after the broken line
+        assert_eq!(append_import_log(destination, entry("second.xml")).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_summary() {
+        let import = ImportLogEntry { id: 3, ..entry("/home/me/statements/statement.xml") };
+        assert_eq!(import.summary(), "#3 2024-06-02 18:40 statement.xml: May 2024 (1 cells)");
+
+        let undo = ImportLogEntry { id: 4, undo_of: Some(3), ..entry("") };
+        assert_eq!(undo.summary(), "#4 2024-06-02 18:40 undo of #3 (1 cells)");
+    }
+
+    #[test]
+    fn test_undone_source_hashes() {
+        let log = vec![
+            ImportLogEntry { id: 1, source_hash: "aaa".to_string(), ..entry("a.xml") },
+            ImportLogEntry { id: 2, source_hash: "bbb".to_string(), ..entry("b.xml") },
+            ImportLogEntry { id: 3, source_hash: String::new(), undo_of: Some(2), ..entry("") },
+        ];
+        assert_eq!(undone_imports(&log), HashSet::from([2]));
+        assert_eq!(undone_source_hashes(&log), HashSet::from(["bbb".to_string()]));
+    }
+}
@@ -0,0 +1,1134 @@
+// import preview: file is read into transactions, categorised by rules and kept here
+// until every transaction has a category from the spreadsheet, only then it is written
+// likely duplicates of already imported transactions are flagged and left out by default.
+// a month an earlier import already wrote gets the included transactions added to its cells,
+// so a statement overlapping the last one doesn't replace the month with the remainder
+// months that go over the budgets from config are reported before writing, so are categories
+// that cost far more or less than usual in a month (typos like 1250 for 12.50)
+// transactions categorised as an income source of the year (salary, from notes or a bank
+// credit) are written into the income area instead of the expenses
+// recurring expenses from config are added to the imported months, one that the file already
+// has (same category and amount that month) is left out by default
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use chrono::{Datelike, Utc};
+
+use crate::file_handlers::camt_handlers::transform_camt_file_into_transactions;
+use crate::file_handlers::mt940_handlers::transform_mt940_file_into_transactions;
+use crate::file_handlers::txt_handlers::transform_file_into_transactions;
+use crate::file_handlers::xls_carry_over::xls_check_carry_over;
+use crate::file_handlers::xls_handlers::{
+    xls_categories_for_year, xls_cells_to_write, xls_current_workbook_path, xls_formula_cells_to_skip, xls_income_sources_for_year,
+    xls_months_with_data, xls_perform_logged_workbook_update, xls_read_sheet, RES_WORKBOOK_NAME,
+};
+use crate::import::classifier::{CategoryClassifier, Suggestion};
+use crate::import::duplicates::{file_content_hash, find_duplicate, DuplicateMatch};
+use crate::import::history::{append_transaction_history, load_transaction_history, HistoryRecord};
+use crate::import::import_log::{append_import_log, load_import_log, undone_imports, undone_source_hashes, ImportLogEntry};
+use crate::import::recurring::{recurring_transactions, RecurringEntry, RecurringExpense};
+use crate::import::rules::{CategoryRule, RuleEngine};
+use crate::stats::budgets::{month_variances, year_alert, Budget, BudgetAlert, BudgetVariance};
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::stats::summary::{month_outlier, Outlier};
+use crate::utils::config::AppConfig;
+use crate::utils::external_edits::check_before_write;
+use crate::utils::folder_file_utils::{pin_backup, BACKUP_FOLDER};
+use crate::utils::structs::{CreditDebit, MonthExpenses, Transaction};
+use crate::utils::workbook_lock::WorkbookLock;
+use crate::utils::transaction_utils::{month_name_from_number, month_number_from_name, split_income, transactions_into_me_structs};
+
+// what a written import tells the caller
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportOutcome {
+    pub months: usize, // months written
+    pub alerts: Vec<BudgetAlert>,
+}
+
+pub struct ImportPreview {
+    pub source_path: String,
+    pub source_hash: String, // content hash of the imported file
+    pub transactions: Vec<Transaction>,
+    categories: HashMap<i64, Vec<String>>, // header row of every year present in the import, income sources included
+    income_sources: HashMap<i64, Vec<String>>, // income area of every year present in the import
+    suggestions: HashMap<usize, Suggestion>, // learned from history, by transaction index
+    duplicates: HashMap<usize, DuplicateMatch>, // by transaction index
+    recurring: HashMap<usize, RecurringEntry>, // added from config, by transaction index
+    recurring_errors: Vec<String>, // broken definitions in config, left out
+    income_credits: HashSet<usize>, // credits that look like income in a year without income area
+    excluded: HashSet<usize>, // transactions that won't be written
+    overlapping_months: Vec<String>, // "March 2024" already imported from another file
+    months_imported_before: BTreeSet<YearMonth>, // same months, written on top of what they hold
+    budgets: Vec<Budget>,
+    workbook_series: ExpenseSeries, // spreadsheet before the import
+}
+
+// picks the importer by file extension: notes (.txt), camt.053 (.xml) or MT940 (.sta, .mt940, .940)
+pub fn load_transactions_from_file(filepath: &str) -> Option<Vec<Transaction>> {
+    let extension = Path::new(filepath).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "txt" => transform_file_into_transactions(filepath),
+        "xml" => transform_camt_file_into_transactions(filepath),
+        "sta" | "mt940" | "940" => transform_mt940_file_into_transactions(filepath),
+        _ => {
+            println!("Unsupported file type: {}", extension);
+            None
+        }
+    }
+}
+
+impl ImportPreview {
+    // reads the file, looks up categories in the current spreadsheet, applies rules from config
+    // and then suggestions learned from the transaction history
+    // files that were already imported (same content hash) are refused
+    pub fn load(filepath: &str, destination_path: &str, config: &AppConfig) -> Result<ImportPreview, String> {
+        let source_hash = file_content_hash(filepath).map_err(|e| format!("Cannot read the file: {}", e))?;
+        // transactions of undone imports are forgotten, so the file can be imported again
+        let log = load_import_log(destination_path);
+        let undone = undone_source_hashes(&log);
+        let mut history = load_transaction_history(destination_path);
+        history.retain(|record| !undone.contains(&record.source_hash));
+        // an import with every transaction left out is only in the log
+        let undone_ids = undone_imports(&log);
+        let logged = log
+            .iter()
+            .any(|entry| entry.source_hash == source_hash && entry.undo_of.is_none() && !undone_ids.contains(&entry.id));
+        if logged || history.iter().any(|record| record.source_hash == source_hash) {
+            return Err("This file was already imported, skipped".to_string());
+        }
+
+        let transactions = load_transactions_from_file(filepath).ok_or("Cannot read transactions from the file")?;
+        if transactions.is_empty() {
+            return Err("No transactions found in the file".to_string());
+        }
+
+        let workbook_path = xls_current_workbook_path(destination_path).ok_or("No spreadsheet found in destination folder")?;
+        let workbook_path_str = workbook_path.to_str().ok_or("Invalid spreadsheet path")?;
+        // known before the transactions are categorised, not only once they are written
+        xls_check_carry_over(workbook_path_str)?;
+
+        let mut categories: HashMap<i64, Vec<String>> = HashMap::new();
+        let mut income_sources: HashMap<i64, Vec<String>> = HashMap::new();
+        for transaction in &transactions {
+            let year = transaction.booking_date.year() as i64;
+            if let Entry::Vacant(entry) = categories.entry(year) {
+                let year_categories = xls_categories_for_year(year, workbook_path_str)
+                    .ok_or(format!("Year {} is not in the spreadsheet", year))?;
+                entry.insert(year_categories);
+                income_sources.insert(year, xls_income_sources_for_year(year, workbook_path_str).unwrap_or_default());
+            }
+        }
+
+        let mut preview = ImportPreview::from_transactions(filepath, transactions, categories);
+        preview.set_income_sources(income_sources);
+        preview.source_hash = source_hash;
+        preview.apply_rules(&config.rules)?;
+
+        let history_transactions: Vec<Transaction> = history.iter().map(|record| record.transaction.clone()).collect();
+        let classifier = CategoryClassifier::train(&history_transactions);
+        preview.apply_suggestions(&classifier, config.suggestion_auto_apply_confidence);
+        // after rules and suggestions, so the file's own rent is recognised by its category
+        preview.add_recurring(&config.recurring);
+        // categorised by now, notes entries are matched by category. recurring expenses
+        // written by an earlier import of the month are duplicates too
+        preview.flag_duplicates(&history, config.duplicate_date_window_days);
+        preview.flag_income_credits();
+
+        preview.budgets = config.budgets.clone();
+        preview.workbook_series = ExpenseSeries::from_workbook(workbook_path_str).unwrap_or_default();
+        Ok(preview)
+    }
+
+    pub fn from_transactions(source_path: &str, transactions: Vec<Transaction>, categories: HashMap<i64, Vec<String>>) -> ImportPreview {
+        ImportPreview {
+            source_path: source_path.to_string(),
+            source_hash: String::new(),
+            transactions,
+            categories,
+            income_sources: HashMap::new(),
+            suggestions: HashMap::new(),
+            duplicates: HashMap::new(),
+            recurring: HashMap::new(),
+            recurring_errors: Vec::new(),
+            income_credits: HashSet::new(),
+            excluded: HashSet::new(),
+            overlapping_months: Vec::new(),
+            months_imported_before: BTreeSet::new(),
+            budgets: Vec::new(),
+            workbook_series: ExpenseSeries::default(),
+        }
+    }
+
+    // compares transactions with already imported ones, duplicates are excluded until the user keeps them
+    // also notes months that were imported from another file (e.g. notes first, bank statement later)
+    pub fn flag_duplicates(&mut self, history: &[HistoryRecord], date_window_days: i64) {
+        let earlier: Vec<Transaction> = history.iter().map(|record| record.transaction.clone()).collect();
+
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            if let Some(duplicate) = find_duplicate(transaction, &earlier, date_window_days) {
+                self.duplicates.insert(index, duplicate);
+                self.excluded.insert(index);
+            }
+        }
+
+        let own_months: BTreeSet<(i32, u32)> = self.transactions.iter().map(|t| (t.booking_date.year(), t.booking_date.month())).collect();
+        let other_files_months: HashSet<(i32, u32)> = history
+            .iter()
+            .filter(|record| record.source_hash != self.source_hash)
+            .map(|record| (record.transaction.booking_date.year(), record.transaction.booking_date.month()))
+            .collect();
+
+        self.months_imported_before = own_months
+            .into_iter()
+            .filter(|month| other_files_months.contains(month))
+            .map(|(year, month)| YearMonth::new(year as i64, month))
+            .collect();
+        self.overlapping_months = self.months_imported_before.iter().map(YearMonth::label).collect();
+    }
+
+    pub fn duplicate_for(&self, index: usize) -> Option<&DuplicateMatch> {
+        self.duplicates.get(&index)
+    }
+
+    // indexes of flagged duplicates (kept or not)
+    pub fn duplicates(&self) -> Vec<usize> {
+        let mut indexes: Vec<usize> = self.duplicates.keys().copied().collect();
+        indexes.sort();
+        indexes
+    }
+
+    // a credit bigger than what its category spent that month can't be only a refund (salary,
+    // a transfer from savings). without an income area it would turn into a negative expense,
+    // so it is left out until the user keeps it. uncategorised credits are left out too
+    pub fn flag_income_credits(&mut self) {
+        let mut spent: HashMap<(i32, u32, &str), f64> = HashMap::new();
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            if let (CreditDebit::Debit, Some(category), false) = (transaction.credit_debit, &transaction.category, self.is_excluded(index)) {
+                let key = (transaction.booking_date.year(), transaction.booking_date.month(), category.as_str());
+                *spent.entry(key).or_insert(0.0) += transaction.amount;
+            }
+        }
+
+        let mut flagged = Vec::new();
+        for (index, transaction) in self.transactions.iter().enumerate() {
+            let year = transaction.booking_date.year() as i64;
+            if transaction.credit_debit != CreditDebit::Credit
+                || self.is_excluded(index)
+                || self.income_sources.get(&year).is_some_and(|sources| !sources.is_empty())
+            {
+                continue;
+            }
+            let spent_in_month = transaction
+                .category
+                .as_deref()
+                .and_then(|category| spent.get(&(transaction.booking_date.year(), transaction.booking_date.month(), category)))
+                .copied()
+                .unwrap_or(0.0);
+            if transaction.amount > spent_in_month + 0.005 {
+                flagged.push(index);
+            }
+        }
+        for index in flagged {
+            self.income_credits.insert(index);
+            self.excluded.insert(index);
+        }
+    }
+
+    // indexes of credits flagged as income (kept or not)
+    pub fn income_credits(&self) -> Vec<usize> {
+        let mut indexes: Vec<usize> = self.income_credits.iter().copied().collect();
+        indexes.sort();
+        indexes
+    }
+
+    // adds the recurring expenses due in the months of the file, returns how many were added
+    // broken definitions are left out, see recurring_errors
+    pub fn add_recurring(&mut self, definitions: &[RecurringExpense]) -> usize {
+        let months: BTreeSet<YearMonth> = self
+            .transactions
+            .iter()
+            .map(|t| YearMonth::new(t.booking_date.year() as i64, t.booking_date.month()))
+            .collect();
+        let months: Vec<YearMonth> = months.into_iter().collect();
+
+        let (recurring, errors) = recurring_transactions(definitions, &months);
+        self.recurring_errors = errors;
+        let added = recurring.len();
+        for (transaction, frequency) in recurring {
+            let in_file = self
+                .transactions
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| !self.recurring.contains_key(index))
+                .map(|(_, other)| other)
+                .find(|other| {
+                    other.category == transaction.category
+                        && other.credit_debit == CreditDebit::Debit
+                        && (other.amount - transaction.amount).abs() < 0.005
+                        && (other.booking_date.year(), other.booking_date.month())
+                            == (transaction.booking_date.year(), transaction.booking_date.month())
+                })
+                .cloned();
+
+            let index = self.transactions.len();
+            if in_file.is_some() {
+                self.excluded.insert(index);
+            }
+            self.transactions.push(transaction);
+            self.recurring.insert(index, RecurringEntry { frequency, in_file });
+        }
+        added
+    }
+
+    pub fn recurring_errors(&self) -> &[String] {
+        &self.recurring_errors
+    }
+
+    pub fn recurring_for(&self, index: usize) -> Option<&RecurringEntry> {
+        self.recurring.get(&index)
+    }
+
+    // indexes of added recurring expenses (kept or not)
+    pub fn recurring(&self) -> Vec<usize> {
+        let mut indexes: Vec<usize> = self.recurring.keys().copied().collect();
+        indexes.sort();
+        indexes
+    }
+
+    pub fn overlapping_months(&self) -> &[String] {
+        &self.overlapping_months
+    }
+
+    // true = transaction is left out of the import
+    pub fn set_excluded(&mut self, index: usize, excluded: bool) {
+        if excluded {
+            self.excluded.insert(index);
+        } else {
+            self.excluded.remove(&index);
+        }
+    }
+
+    pub fn is_excluded(&self, index: usize) -> bool {
+        self.excluded.contains(&index)
+    }
+
+    // transactions that will be written
+    pub fn included_transactions(&self) -> Vec<Transaction> {
+        (0..self.transactions.len())
+            .filter(|index| !self.is_excluded(*index))
+            .map(|index| self.transactions[index].clone())
+            .collect()
+    }
+
+    // categorises transactions which don't have a category yet
+    pub fn apply_rules(&mut self, rules: &[CategoryRule]) -> Result<(), String> {
+        let engine = RuleEngine::new(rules)?;
+        for transaction in self.transactions.iter_mut().filter(|t| t.category.is_none()) {
+            let year = transaction.booking_date.year() as i64;
+            if let Some(categories) = self.categories.get(&year) {
+                transaction.category = engine.categorise(transaction, categories);
+            }
+        }
+        Ok(())
+    }
+
+    // suggests categories for transactions no rule matched
+    // confident suggestions with enough history behind them are applied right away, returns how many were applied
+    pub fn apply_suggestions(&mut self, classifier: &CategoryClassifier, auto_apply_confidence: f64) -> usize {
+        let mut applied = 0;
+        for index in self.unresolved() {
+            let Some(suggestion) = classifier.suggest(&self.transactions[index], self.categories_for(index)) else {
+                continue;
+            };
+            if suggestion.can_auto_apply(auto_apply_confidence) {
+                self.transactions[index].category = Some(suggestion.category.clone());
+                applied += 1;
+            }
+            self.suggestions.insert(index, suggestion);
+        }
+        applied
+    }
+
+    pub fn suggestion_for(&self, index: usize) -> Option<&Suggestion> {
+        self.suggestions.get(&index)
+    }
+
+    // categories from the header row of the transaction's year
+    pub fn categories_for(&self, index: usize) -> &[String] {
+        self.transactions
+            .get(index)
+            .and_then(|t| self.categories.get(&(t.booking_date.year() as i64)))
+            .map(|categories| categories.as_slice())
+            .unwrap_or(&[])
+    }
+
+    // indexes of transactions without a category, or with one that isn't in the spreadsheet
+    // (typo in notes), these are shown as "Uncategorised"
+    pub fn unresolved(&self) -> Vec<usize> {
+        (0..self.transactions.len())
+            .filter(|&index| !self.is_excluded(index))
+            .filter(|&index| match &self.transactions[index].category {
+                Some(category) => !self.categories_for(index).contains(category),
+                None => true,
+            })
+            .collect()
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.unresolved().is_empty()
+    }
+
+    // manual choice from the GUI, optionally remembered as a new rule (caller saves the config)
+    pub fn assign_category(&mut self, index: usize, category: &str, remember_as_rule: bool, config: &mut AppConfig) -> Result<(), String> {
+        if !self.categories_for(index).iter().any(|c| c == category) {
+            return Err(format!("{} is not a category in the spreadsheet", category));
+        }
+
+        let transaction = &mut self.transactions[index];
+        transaction.category = Some(category.to_string());
+
+        if remember_as_rule {
+            config.rules.push(CategoryRule::from_transaction(transaction, category));
+        }
+        Ok(())
+    }
+
+    // income sources can be picked like categories, an expense category of the same name wins
+    pub fn set_income_sources(&mut self, income_sources: HashMap<i64, Vec<String>>) {
+        for (year, sources) in &income_sources {
+            let categories = self.categories.entry(*year).or_default();
+            for source in sources {
+                if !categories.contains(source) {
+                    categories.push(source.clone());
+                }
+            }
+        }
+        self.income_sources = income_sources;
+    }
+
+    fn is_income_source(&self, year: i64, category: &str) -> bool {
+        self.income_sources.get(&year).is_some_and(|sources| sources.iter().any(|source| source == category))
+    }
+
+    pub fn month_expenses(&self) -> Vec<MonthExpenses> {
+        let mut month_expenses = transactions_into_me_structs(&self.included_transactions());
+        for me in &mut month_expenses {
+            let sources: Vec<String> = me
+                .expenses_data
+                .keys()
+                .filter(|category| self.is_income_source(me.year, category))
+                .cloned()
+                .collect();
+            split_income(me, &sources);
+        }
+        month_expenses
+    }
+
+    // months of the import
+    fn imported_months(&self) -> Vec<YearMonth> {
+        self.month_expenses()
+            .iter()
+            .filter_map(|me| Some(YearMonth::new(me.year, month_number_from_name(&me.month)?)))
+            .collect()
+    }
+
+    // the categories as they are going to be once written: imported categories replace
+    // the cells of their month, everything else keeps what the spreadsheet has
+    fn series_after_import<'a>(&self, categories: impl IntoIterator<Item = &'a str>) -> ExpenseSeries {
+        let month_expenses = self.month_expenses();
+        let imported = |category: &str, month: YearMonth| {
+            let value = month_expenses
+                .iter()
+                .find(|me| me.year == month.year && month_number_from_name(&me.month) == Some(month.month))
+                .and_then(|me| me.expenses_data.get(category).copied())?;
+            match self.months_imported_before.contains(&month) {
+                true => Some(value + self.workbook_series.value(category, month).unwrap_or(0.0)),
+                false => Some(value),
+            }
+        };
+
+        let mut series = ExpenseSeries::default();
+        for category in categories {
+            let mut months: BTreeSet<YearMonth> = self.imported_months().into_iter().collect();
+            if let Some(values) = self.workbook_series.category(category) {
+                months.extend(values.keys());
+            }
+            for month in months {
+                if let Some(value) = imported(category, month).or(self.workbook_series.value(category, month)) {
+                    series.insert(category, month, value);
+                }
+            }
+        }
+        series
+    }
+
+    fn budgeted_series_after_import(&self) -> ExpenseSeries {
+        self.series_after_import(self.budgets.iter().map(|budget| budget.category.as_str()))
+    }
+
+    // cells of the import holding a formula of the user, they are not written
+    //  May 2024 Rent
+    pub fn formula_cells(&self, destination_path: &str) -> Vec<String> {
+        let Some(workbook_path) = xls_current_workbook_path(destination_path) else {
+            return Vec::new();
+        };
+        self.month_expenses()
+            .iter()
+            .flat_map(|me| xls_formula_cells_to_skip(me, workbook_path.to_str().unwrap_or_default()))
+            .map(|cell| format!("{} {} {}", cell.month, cell.year, cell.category))
+            .collect()
+    }
+
+    // imported categories whose month is unusual against their history in the spreadsheet
+    pub fn anomalies(&self) -> Vec<Outlier> {
+        let month_expenses = self.month_expenses();
+        let categories: BTreeSet<&str> = month_expenses.iter().flat_map(|me| me.expenses_data.keys().map(String::as_str)).collect();
+        let series = self.series_after_import(categories.iter().copied());
+        let mut anomalies = Vec::new();
+        for me in &month_expenses {
+            let Some(month) = month_number_from_name(&me.month).map(|month| YearMonth::new(me.year, month)) else {
+                continue;
+            };
+            let mut categories: Vec<&String> = me.expenses_data.keys().collect();
+            categories.sort();
+            for category in categories {
+                anomalies.extend(series.category(category).and_then(|values| month_outlier(category, values, month)));
+            }
+        }
+        anomalies
+    }
+
+    fn categories_of_year(&self, year: i64) -> &[String] {
+        self.categories.get(&year).map(|categories| categories.as_slice()).unwrap_or(&[])
+    }
+
+    // actual against budget of every budgeted category in the imported months
+    pub fn budget_variances(&self) -> Vec<BudgetVariance> {
+        let series = self.budgeted_series_after_import();
+        self.imported_months()
+            .into_iter()
+            .flat_map(|month| month_variances(&self.budgets, &series, month, self.categories_of_year(month.year)))
+            .collect()
+    }
+
+    pub fn overspent(&self) -> Vec<BudgetVariance> {
+        self.budget_variances().into_iter().filter(|variance| variance.is_over()).collect()
+    }
+
+    // months over budget, then years on track to go over, counted up to the last imported month
+    pub fn budget_alerts(&self) -> Vec<BudgetAlert> {
+        let mut alerts: Vec<BudgetAlert> = self.overspent().iter().map(BudgetAlert::from_variance).collect();
+
+        let series = self.budgeted_series_after_import();
+        let mut last_months: BTreeMap<i64, YearMonth> = BTreeMap::new();
+        for month in self.imported_months() {
+            let last = last_months.entry(month.year).or_insert(month);
+            *last = (*last).max(month);
+        }
+        for month in last_months.into_values() {
+            for budget in self.budgets.iter().filter(|budget| self.categories_of_year(month.year).contains(&budget.category)) {
+                alerts.extend(year_alert(budget, &series, month));
+            }
+        }
+        alerts
+    }
+
+    // writes every month into the spreadsheet, returns number of months written with the budget
+    // alerts of the written months. when a month fails the ones before it stay written and logged
+    // a spreadsheet edited outside the app is only written with force
+    pub fn commit(&self, destination_path: &str, force: bool) -> Result<ImportOutcome, String> {
+        let unresolved = self.unresolved().len();
+        if unresolved > 0 {
+            return Err(format!("{} transactions are still uncategorised", unresolved));
+        }
+        let mut month_expenses = self.month_expenses();
+        if month_expenses.is_empty() {
+            // every transaction was left out, the file is logged so the next import skips it
+            let entry = ImportLogEntry {
+                id: 0, // assigned when appended
+                timestamp: Utc::now(),
+                source_file: self.source_path.clone(),
+                source_hash: self.source_hash.clone(),
+                month_expenses: Vec::new(),
+                cells: Vec::new(),
+                backup_file: None,
+                undo_of: None,
+            };
+            append_import_log(destination_path, entry).map_err(|e| format!("Nothing to write, but the import log was not saved: {}", e))?;
+            return Ok(ImportOutcome { months: 0, alerts: Vec::new() });
+        }
+        // held until the import log and history are written too
+        let _lock = WorkbookLock::acquire(destination_path)?;
+        // not even with force, writing would lose what the check lists
+        if let Some(workbook_path) = xls_current_workbook_path(destination_path) {
+            xls_check_carry_over(workbook_path.to_str().unwrap_or_default())?;
+        }
+        check_before_write(destination_path, RES_WORKBOOK_NAME, force)?;
+
+        if let Some(workbook_path) = xls_current_workbook_path(destination_path) {
+            for me in month_expenses.iter_mut() {
+                let month = month_number_from_name(&me.month).map(|month| YearMonth::new(me.year, month));
+                if month.is_some_and(|month| self.months_imported_before.contains(&month)) {
+                    add_current_values(me, workbook_path.to_str().unwrap_or_default());
+                }
+            }
+        }
+        let alerts = self.budget_alerts(); // against the spreadsheet as it was read for the preview
+        let starts_new_year = starts_new_year(&month_expenses, destination_path);
+        let mut cells = Vec::new();
+        let mut backup_file = None;
+        let mut failed = None;
+        let mut written = 0;
+        for me in &month_expenses {
+            let Some(update) = xls_perform_logged_workbook_update(me.clone(), destination_path) else {
+                failed = Some(format!("Failed to write {} {} into the spreadsheet", me.month, me.year));
+                break;
+            };
+            written += 1;
+            cells.extend(update.cells);
+            // every month makes a backup, the first one is the state before the import
+            if backup_file.is_none() {
+                if starts_new_year {
+                    if let Err(e) = pin_backup(&Path::new(destination_path).join(BACKUP_FOLDER), &update.backup_name) {
+                        println!("Error pinning backup: {}", e);
+                    }
+                }
+                backup_file = Some(update.backup_name);
+            }
+        }
+        // months written before a failure are logged all the same, so they can be undone
+        if written == 0 {
+            return Err(failed.unwrap_or_default());
+        }
+        let written_months: HashSet<(i64, String)> =
+            month_expenses[..written].iter().map(|me| (me.year, me.month.clone())).collect();
+        let written_transactions: Vec<Transaction> = self
+            .included_transactions()
+            .into_iter()
+            .filter(|t| {
+                let month = month_name_from_number(t.booking_date.month()).unwrap_or_default().to_string();
+                written_months.contains(&(t.booking_date.year() as i64, month))
+            })
+            .collect();
+        month_expenses.truncate(written);
+
+        let entry = ImportLogEntry {
+            id: 0, // assigned when appended
+            timestamp: Utc::now(),
+            source_file: self.source_path.clone(),
+            source_hash: self.source_hash.clone(),
+            month_expenses,
+            cells,
+            backup_file,
+            undo_of: None,
+        };
+        if let Err(e) = append_import_log(destination_path, entry) {
+            println!("Error saving import log: {}", e);
+        }
+
+        // remember the choices, next imports learn from them
+        if let Err(e) = append_transaction_history(destination_path, &written_transactions, &self.source_hash) {
+            println!("Error saving transaction history: {}", e);
+        }
+        if let Some(e) = failed {
+            return Err(format!("{}, {} months before it were written and can be undone from the history", e, written));
+        }
+        Ok(ImportOutcome { months: written, alerts })
+    }
+}
+
+// adds what the month's cells hold now, the import is written on top of an earlier one
+fn add_current_values(me: &mut MonthExpenses, path_to_workbook: &str) {
+    for cell in xls_cells_to_write(me, path_to_workbook).unwrap_or_default() {
+        let Some(previous) = cell.previous else {
+            continue;
+        };
+        // an expense category of the same name as an income source wins, as in the preview
+        if let Some(value) = me.expenses_data.get_mut(&cell.category) {
+            *value += previous;
+        } else if let Some(value) = me.income_data.get_mut(&cell.category) {
+            *value += previous;
+        }
+    }
+}
+
+// true if the import writes the first numbers into some year of the spreadsheet,
+// the backup taken before such an import is kept forever
+fn starts_new_year(month_expenses: &[MonthExpenses], destination_path: &str) -> bool {
+    let Some(cells) = xls_current_workbook_path(destination_path).and_then(|path| xls_read_sheet(path.to_str()?)) else {
+        return false;
+    };
+    let months_with_data = xls_months_with_data(&cells);
+    month_expenses
+        .iter()
+        .any(|me| months_with_data.get(&me.year).is_none_or(|months| months.is_empty()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::{write_test_sheet, write_test_workbook};
+    use crate::file_handlers::xls_handlers::xls_read_cell_values;
+    use crate::import::duplicates::DuplicateKind;
+    use crate::import::recurring::Frequency;
+    use crate::import::rules::RuleCondition;
+    use crate::stats::budgets::AlertKind;
+    use crate::utils::external_edits::WORKBOOK_HASH_FILE;
+    use crate::utils::structs::CreditDebit;
+    use chrono::NaiveDate;
+
+    fn transaction(description: &str, category: Option<&str>) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 5, 10).unwrap(),
+            amount: 10.0,
+            credit_debit: CreditDebit::Debit,
+            description: description.to_string(),
+            counterparty: None,
+            category: category.map(|c| c.to_string()),
+        }
+    }
+
+    fn preview(transactions: Vec<Transaction>) -> ImportPreview {
+        let mut categories = HashMap::new();
+        categories.insert(2024, vec!["Groceries".to_string(), "Sweets".to_string()]);
+        ImportPreview::from_transactions("test.xml", transactions, categories)
+    }
+
+    #[test]
+    fn test_unresolved_transactions() {
+        let preview = preview(vec![
+            transaction("a", Some("Groceries")),
+            transaction("b", None),
+            transaction("c", Some("Grocreies")), // typo in notes
+        ]);
+        assert_eq!(preview.unresolved(), vec![1, 2]);
+        assert!(!preview.is_resolved());
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let mut preview = preview(vec![transaction("Candy shop", None), transaction("Rent", None)]);
+        let rules = vec![CategoryRule {
+            condition: RuleCondition::DescriptionContains { pattern: "candy".to_string() },
+            category: "Sweets".to_string(),
+        }];
+
+        preview.apply_rules(&rules).unwrap();
+        assert_eq!(preview.transactions[0].category.as_deref(), Some("Sweets"));
+        assert_eq!(preview.unresolved(), vec![1]);
+    }
+
+    #[test]
+    fn test_assign_category_and_remember_rule() {
+        let mut preview = preview(vec![transaction("Candy shop", None)]);
+        let mut config = AppConfig::default();
+
+        assert!(preview.assign_category(0, "Rent", true, &mut config).is_err());
+        assert!(config.rules.is_empty());
+
+        preview.assign_category(0, "Sweets", true, &mut config).unwrap();
+        assert!(preview.is_resolved());
+        assert_eq!(config.rules.len(), 1);
+        assert_eq!(config.rules[0].category, "Sweets");
+
+        let me = preview.month_expenses();
+        assert_eq!(me.len(), 1);
+        assert_eq!(me[0].month, "May");
+        assert_eq!(me[0].expenses_data.get("Sweets"), Some(&10.0));
+    }
+
+    #[test]
+    fn test_apply_suggestions() {
+        let mut preview = preview(vec![
+            transaction("Candy shop Berlin", None),
+            transaction("Unknown", None),
+            transaction("Something shop", None),
+        ]);
+        let mut history = Vec::new();
+        for _ in 0..5 {
+            history.push(transaction("Candy shop Berlin", Some("Sweets")));
+            history.push(transaction("Grocery shop", Some("Groceries")));
+        }
+        let classifier = CategoryClassifier::train(&history);
+
+        let applied = preview.apply_suggestions(&classifier, 0.9);
+        assert_eq!(applied, 1);
+        assert_eq!(preview.transactions[0].category.as_deref(), Some("Sweets"));
+
+        // low confidence suggestion is kept for the GUI, but not applied
+        assert!(preview.suggestion_for(1).is_none());
+        assert!(preview.suggestion_for(2).unwrap().confidence < 0.9);
+        assert_eq!(preview.unresolved(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_flag_duplicates() {
+        let mut preview = preview(vec![transaction("Candy shop", None), transaction("Rent", None)]);
+        preview.source_hash = "new".to_string();
+
+        let history = vec![HistoryRecord {
+            transaction: transaction("Candy shop", Some("Sweets")),
+            source_hash: "old".to_string(),
+        }];
+        preview.flag_duplicates(&history, 3);
+
+        assert_eq!(preview.duplicates(), vec![0]);
+        assert!(preview.is_excluded(0));
+        assert_eq!(preview.overlapping_months(), &["May 2024".to_string()]);
+
+        // excluded duplicate doesn't need a category and isn't written
+        assert_eq!(preview.unresolved(), vec![1]);
+        assert_eq!(preview.included_transactions().len(), 1);
+
+        // user decides to keep it anyway
+        preview.set_excluded(0, false);
+        assert_eq!(preview.unresolved(), vec![0, 1]);
+        assert_eq!(preview.included_transactions().len(), 2);
+    }
+
+    #[test]
+    fn test_overlapping_statements() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        let config = AppConfig {
+            rules: vec![CategoryRule {
+                condition: RuleCondition::DescriptionContains { pattern: "bakery".to_string() },
+                category: "Sweets".to_string(),
+            }],
+            ..AppConfig::default()
+        };
+        let import = |name: &str, statement: &str| {
+            let path = temp_dir.path().join(name);
+            std::fs::write(&path, statement).unwrap();
+            let preview = ImportPreview::load(path.to_str().unwrap(), destination, &config).unwrap();
+            preview.commit(destination, false).unwrap();
+            preview
+        };
+        let sweets = || {
+            let workbook_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+            xls_read_cell_values(&[(2, 3)], workbook_path.to_str().unwrap()).unwrap()[0]
+        };
+
+        // January 1 - 20, then January 15 - 31 with the 15th in both
+        import("first.sta", ":61:240105D10,00NMSC\n:86:Bakery one\n:61:240115D5,00NMSC\n:86:Bakery two\n");
+        assert_eq!(sweets(), Some(15.0));
+        let second = import("second.sta", ":61:240115D5,00NMSC\n:86:Bakery two\n:61:240125D7,00NMSC\n:86:Bakery three\n");
+        assert_eq!(second.duplicates(), vec![0]);
+        assert_eq!(sweets(), Some(22.0));
+    }
+
+    #[test]
+    fn test_notes_duplicate_of_bank_statement() {
+        let mut preview = preview(vec![transaction("Card payment Lidl", Some("Groceries"))]);
+        preview.source_hash = "bank".to_string();
+        // the same 10.00 written into the notes of May before
+        let mut notes = transaction("Groceries", Some("Groceries"));
+        notes.booking_date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+        preview.flag_duplicates(&[HistoryRecord { transaction: notes, source_hash: "notes".to_string() }], 3);
+        assert_eq!(preview.duplicate_for(0).unwrap().kind, DuplicateKind::SameMonth);
+    }
+
+    #[test]
+    fn test_already_imported_file_is_skipped() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let file_path = temp_dir.path().join("notes.txt");
+        std::fs::write(&file_path, "May 2024\nSweets\n10\n").unwrap();
+        let file_path_str = file_path.to_str().unwrap();
+
+        let hash = file_content_hash(file_path_str).unwrap();
+        append_transaction_history(destination, &[transaction("Sweets", Some("Sweets"))], &hash).unwrap();
+
+        let result = ImportPreview::load(file_path_str, destination, &AppConfig::default());
+        assert_eq!(result.err().as_deref(), Some("This file was already imported, skipped"));
+    }
+
+    #[test]
+    fn test_income_credits_without_income_area() {
+        let mut refund = transaction("Shoes returned", Some("Clothes"));
+        refund.credit_debit = CreditDebit::Credit;
+        refund.amount = 30.0;
+        let mut salary = transaction("Salary", Some("Groceries"));
+        salary.credit_debit = CreditDebit::Credit;
+        salary.amount = 2500.0; // more than groceries cost in May
+        let mut unknown = transaction("Transfer", None);
+        unknown.credit_debit = CreditDebit::Credit;
+        let mut shoes = transaction("Shoes", Some("Clothes"));
+        shoes.amount = 80.0;
+        let mut categories = HashMap::new();
+        categories.insert(2024, vec!["Groceries".to_string(), "Clothes".to_string()]);
+        let transactions = vec![transaction("Shop", Some("Groceries")), shoes, refund, salary, unknown];
+        let mut preview = ImportPreview::from_transactions("test.xml", transactions, categories);
+
+        preview.flag_income_credits();
+        assert_eq!(preview.income_credits(), vec![3, 4]);
+        assert!(preview.is_resolved()); // the uncategorised credit is left out
+        let month_expenses = preview.month_expenses();
+        assert_eq!(month_expenses[0].expenses_data.get("Groceries"), Some(&10.0));
+        assert_eq!(month_expenses[0].expenses_data.get("Clothes"), Some(&50.0));
+
+        // with an income area the salary goes there once it is categorised
+        let mut income_sources = HashMap::new();
+        income_sources.insert(2024, vec!["Salary".to_string()]);
+        let mut preview = ImportPreview::from_transactions("test.xml", preview.transactions.clone(), preview.categories.clone());
+        preview.set_income_sources(income_sources);
+        preview.flag_income_credits();
+        assert!(preview.income_credits().is_empty());
+    }
+
+    #[test]
+    fn test_commit_with_everything_excluded() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        let file_path = temp_dir.path().join("notes.txt");
+        std::fs::write(&file_path, "May 2024\nSweets\n10\n").unwrap();
+        let file_path_str = file_path.to_str().unwrap();
+
+        let mut preview = ImportPreview::load(file_path_str, destination, &AppConfig::default()).unwrap();
+        preview.set_excluded(0, true);
+        assert_eq!(preview.commit(destination, false).map(|outcome| outcome.months), Ok(0));
+        let log = load_import_log(destination);
+        assert!(log[0].summary().ends_with("notes.txt: nothing written (0 cells)"), "{}", log[0].summary());
+        let result = ImportPreview::load(file_path_str, destination, &AppConfig::default());
+        assert_eq!(result.err().as_deref(), Some("This file was already imported, skipped"));
+
+        // undoing it lets the file be imported again
+        let undone = crate::import::undo::undo_import(destination, log[0].id, false).unwrap();
+        assert!(matches!(undone, crate::import::undo::UndoOutcome::Undone { cells: 0, .. }));
+        assert!(ImportPreview::load(file_path_str, destination, &AppConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_workbook_with_lost_content_is_refused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let workbook_path = temp_dir.path().join(RES_WORKBOOK_NAME);
+        write_test_workbook(&workbook_path);
+        let file_path = temp_dir.path().join("notes.txt");
+        std::fs::write(&file_path, "May 2024\nSweets\n10\n").unwrap();
+        let file_path_str = file_path.to_str().unwrap();
+        let preview = ImportPreview::load(file_path_str, destination, &AppConfig::default()).unwrap();
+
+        // column widths set in the meantime, even force doesn't write over them
+        let workbook = xlsxwriter::Workbook::new(workbook_path.to_str().unwrap()).unwrap();
+        let mut sheet = workbook.add_worksheet(Some("Sheet1")).unwrap();
+        write_test_sheet(&mut sheet, &[], &[]);
+        sheet.set_column(1, 1, 20.0, None).unwrap();
+        workbook.close().unwrap();
+        let before = std::fs::read(&workbook_path).unwrap();
+
+        let error = preview.commit(destination, true).unwrap_err();
+        assert!(error.contains("Sheet1: column widths"), "{}", error);
+        assert_eq!(std::fs::read(&workbook_path).unwrap(), before);
+        let error = ImportPreview::load(file_path_str, destination, &AppConfig::default()).err().unwrap();
+        assert!(error.contains("Sheet1: column widths"), "{}", error);
+    }
+
+    #[test]
+    fn test_commit_refuses_unresolved() {
+        let preview = preview(vec![transaction("a", None)]);
+        assert!(preview.commit("does/not/matter", false).is_err());
+    }
+
+    #[test]
+    fn test_commit_asks_about_external_edits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        // hash of some other workbook, as if edited by hand after the last import
+        std::fs::write(temp_dir.path().join(WORKBOOK_HASH_FILE), "0000").unwrap();
+
+        let preview = preview(vec![transaction("Grocery shop", Some("Groceries"))]);
+        assert!(preview.commit(destination, false).is_err());
+        assert_eq!(preview.commit(destination, true).map(|outcome| outcome.months), Ok(1));
+        // the app's own write is not an external edit
+        assert_eq!(preview.commit(destination, false).map(|outcome| outcome.months), Ok(1));
+    }
+
+    #[test]
+    fn test_commit_failing_month_is_logged() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        // 2025 is a category list here, but the spreadsheet has no 2025 block to write into
+        let mut next_year = transaction("Candy shop", Some("Sweets"));
+        next_year.booking_date = NaiveDate::from_ymd_opt(2025, 5, 10).unwrap();
+        let mut categories = HashMap::new();
+        categories.insert(2024, vec!["Groceries".to_string(), "Sweets".to_string()]);
+        categories.insert(2025, vec!["Sweets".to_string()]);
+        let preview = ImportPreview::from_transactions("test.xml", vec![transaction("Bakery", Some("Sweets")), next_year], categories);
+
+        let error = preview.commit(destination, false).unwrap_err();
+        assert!(error.starts_with("Failed to write May 2025"), "{}", error);
+        let log = load_import_log(destination);
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].month_expenses.len(), 1);
+        assert_eq!(log[0].cells[0].month, "May");
+        assert_eq!(load_transaction_history(destination).len(), 1);
+    }
+
+    #[test]
+    fn test_budget_variances() {
+        let mut preview = preview(vec![transaction("Candy shop", Some("Sweets")), transaction("Bakery", Some("Sweets"))]);
+        preview.budgets = vec![
+            Budget { category: "Sweets".to_string(), monthly: 15.0, overrides: Default::default() },
+            Budget { category: "Groceries".to_string(), monthly: 100.0, overrides: Default::default() },
+        ];
+        // groceries of May are already in the spreadsheet and not part of the import
+        preview.workbook_series.insert("Groceries", YearMonth::new(2024, 5), 120.0);
+
+        let variances = preview.budget_variances();
+        assert_eq!(variances.len(), 2);
+        assert_eq!((variances[0].actual, variances[1].actual), (20.0, 120.0));
+        assert_eq!(preview.overspent().len(), 2);
+
+        // without the second sweets transaction May stays within the Sweets budget
+        preview.set_excluded(1, true);
+        assert_eq!(preview.overspent().len(), 1);
+        assert_eq!(preview.overspent()[0].category, "Groceries");
+    }
+
+    #[test]
+    fn test_budget_alerts() {
+        let mut preview = preview(vec![transaction("Candy shop", Some("Sweets"))]);
+        preview.budgets = vec![Budget { category: "Sweets".to_string(), monthly: 12.0, overrides: Default::default() }];
+        // 10 in May is within the budget, but January - April were 20 each
+        for month in 1..=4 {
+            preview.workbook_series.insert("Sweets", YearMonth::new(2024, month), 20.0);
+        }
+        // the import replaces what May had
+        preview.workbook_series.insert("Sweets", YearMonth::new(2024, 5), 50.0);
+
+        let alerts = preview.budget_alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, AlertKind::YearOnTrackToOverspend);
+        assert_eq!((alerts[0].category.as_str(), alerts[0].budget, alerts[0].actual), ("Sweets", 144.0, 90.0));
+        assert_eq!(alerts[0].month, YearMonth::new(2024, 5));
+
+        preview.transactions[0].amount = 13.0;
+        let alerts = preview.budget_alerts();
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(alerts[0].kind, AlertKind::MonthOverBudget);
+    }
+
+    #[test]
+    fn test_anomalies() {
+        let mut preview = preview(vec![transaction("Candy shop", Some("Sweets")), transaction("Market", Some("Groceries"))]);
+        for month in 1..=4 {
+            preview.workbook_series.insert("Sweets", YearMonth::new(2024, month), 10.0 + month as f64);
+            preview.workbook_series.insert("Groceries", YearMonth::new(2024, month), 100.0);
+        }
+        // 10 of sweets is usual, groceries usually cost 100
+        let anomalies = preview.anomalies();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].category, "Groceries");
+        assert_eq!(anomalies[0].month, YearMonth::new(2024, 5));
+        assert!(anomalies[0].score < 0.0);
+
+        // 1250 instead of 12.50
+        preview.transactions[0].amount = 1250.0;
+        preview.transactions[1].amount = 95.0;
+        let anomalies = preview.anomalies();
+        assert_eq!(anomalies.len(), 1);
+        assert_eq!(anomalies[0].describe(), "Sweets 100.0x typical month: 1250.00 in May 2024, usually 12.50");
+    }
+
+    #[test]
+    fn test_income_sources() {
+        let mut salary = transaction("employer", Some("Salary"));
+        salary.amount = 3000.0;
+        salary.credit_debit = CreditDebit::Credit;
+        let mut preview = preview(vec![transaction("a", Some("Groceries")), salary]);
+        assert_eq!(preview.unresolved(), vec![1]);
+
+        let mut income_sources = HashMap::new();
+        income_sources.insert(2024, vec!["Salary".to_string()]);
+        preview.set_income_sources(income_sources);
+        assert!(preview.is_resolved());
+
+        let month_expenses = preview.month_expenses();
+        assert_eq!(month_expenses[0].expenses_data.get("Groceries"), Some(&10.0));
+        assert_eq!(month_expenses[0].expenses_data.get("Salary"), None);
+        assert_eq!(month_expenses[0].income_data.get("Salary"), Some(&3000.0));
+    }
+
+    #[test]
+    fn test_add_recurring() {
+        let mut preview = preview(vec![transaction("a", Some("Groceries"))]);
+        let definition = |category: &str, amount: f64| RecurringExpense {
+            category: category.to_string(),
+            amount,
+            frequency: Frequency::Monthly,
+            start: "2024-01".to_string(),
+            end: None,
+            day: 15,
+            description: None,
+            skip: Vec::new(),
+        };
+        let mut not_yet = definition("Sweets", 1.0);
+        not_yet.start = "2024-06".to_string();
+        // groceries for 10.00 are in the file already
+        let added = preview.add_recurring(&[definition("Sweets", 5.0), definition("Groceries", 10.0), not_yet]);
+        assert_eq!(added, 2);
+        assert_eq!(preview.recurring(), vec![1, 2]);
+        assert_eq!(preview.transactions[1].booking_date, NaiveDate::from_ymd_opt(2024, 5, 15).unwrap());
+        assert_eq!(preview.recurring_for(1).unwrap().in_file, None);
+        assert_eq!(preview.recurring_for(2).unwrap().in_file.as_ref().unwrap().description, "a");
+        assert!(!preview.is_excluded(1) && preview.is_excluded(2));
+
+        let month_expenses = preview.month_expenses();
+        assert_eq!(month_expenses[0].expenses_data.get("Sweets"), Some(&5.0));
+        assert_eq!(month_expenses[0].expenses_data.get("Groceries"), Some(&10.0));
+
+        // skipped for this month
+        preview.set_excluded(1, true);
+        assert_eq!(preview.month_expenses()[0].expenses_data.get("Sweets"), None);
+
+        // a broken definition is left out, the good ones are still added
+        let mut preview = ImportPreview::from_transactions("test.xml", vec![transaction("a", Some("Groceries"))], preview.categories);
+        let broken = RecurringExpense { start: "soon".to_string(), ..definition("Sweets", 5.0) };
+        assert_eq!(preview.add_recurring(&[broken, definition("Sweets", 5.0)]), 1);
+        assert_eq!(preview.recurring_errors().len(), 1);
+    }
+
+    #[test]
+    fn test_recurring_already_suggested_in_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        // candy shop learned from April, no rule for it
+        let mut april = transaction("Candy shop", Some("Sweets"));
+        april.booking_date = NaiveDate::from_ymd_opt(2024, 4, 3).unwrap();
+        append_transaction_history(destination, &[april.clone(), april.clone(), april], "april").unwrap();
+
+        let file_path = temp_dir.path().join("may.sta");
+        std::fs::write(&file_path, ":61:240503D5,00NMSC\n:86:Candy shop\n").unwrap();
+        let config = AppConfig {
+            recurring: vec![serde_json::from_str(r#"{ "category": "Sweets", "amount": 5.0, "start": "2024-01" }"#).unwrap()],
+            ..AppConfig::default()
+        };
+        let preview = ImportPreview::load(file_path.to_str().unwrap(), destination, &config).unwrap();
+        assert_eq!(preview.transactions[0].category.as_deref(), Some("Sweets"));
+        assert!(preview.recurring_for(1).unwrap().in_file.is_some());
+        assert_eq!(preview.month_expenses()[0].expenses_data.get("Sweets"), Some(&5.0));
+    }
+
+    #[test]
+    fn test_starts_new_year() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let me = |year| MonthExpenses { year, month: "May".to_string(), expenses_data: HashMap::new(), income_data: HashMap::new() };
+
+        // no spreadsheet, nothing to protect
+        assert!(!starts_new_year(&[me(2024)], destination));
+
+        // test workbook has January 2024 filled in
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        assert!(!starts_new_year(&[me(2024)], destination));
+        assert!(starts_new_year(&[me(2024), me(2025)], destination));
+    }
+}
@@ -0,0 +1,173 @@
+// recurring expenses kept in config.json, added to every imported month they are due in:
+//  { "category": "Rent", "amount": 700.0, "frequency": "monthly", "start": "2024-01", "end": "2024-12",
+//    "day": 1, "description": "Flat rent", "skip": ["2024-08"] }
+// frequency counts from the start month (quarterly from 2024-02 is February, May, ...), end is
+// optional and included, day past the end of a month is its last day. skipped months are left
+// out for good, one import at a time they are left out in the preview
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use crate::stats::series::YearMonth;
+use crate::utils::structs::{CreditDebit, Transaction};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    #[default]
+    Monthly,
+    Quarterly,
+    Yearly,
+}
+
+impl Frequency {
+    pub fn months(&self) -> i64 {
+        match self {
+            Frequency::Monthly => 1,
+            Frequency::Quarterly => 3,
+            Frequency::Yearly => 12,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Frequency::Monthly => "monthly",
+            Frequency::Quarterly => "quarterly",
+            Frequency::Yearly => "yearly",
+        }
+    }
+}
+
+fn first_day() -> u32 {
+    1
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecurringExpense {
+    pub category: String,
+    pub amount: f64,
+    #[serde(default)]
+    pub frequency: Frequency,
+    pub start: String, // "2024-01"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+    #[serde(default = "first_day")]
+    pub day: u32, // of the month, 1 - 31
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>, // category if not set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip: Vec<String>, // "2024-08"
+}
+
+impl RecurringExpense {
+    fn month(&self, text: &str) -> Result<YearMonth, String> {
+        YearMonth::parse(text).ok_or(format!("Recurring {}: invalid month \"{}\", expected 2024-05", self.category, text))
+    }
+
+    pub fn is_due(&self, month: YearMonth) -> Result<bool, String> {
+        if !(1..=31).contains(&self.day) {
+            return Err(format!("Recurring {}: invalid day {}", self.category, self.day));
+        }
+        let start = self.month(&self.start)?;
+        if let Some(end) = &self.end {
+            if month > self.month(end)? {
+                return Ok(false);
+            }
+        }
+        for skipped in &self.skip {
+            if self.month(skipped)? == month {
+                return Ok(false);
+            }
+        }
+        let since_start = (month.year - start.year) * 12 + month.month as i64 - start.month as i64;
+        Ok(since_start >= 0 && since_start % self.frequency.months() == 0)
+    }
+
+    pub fn transaction(&self, month: YearMonth) -> Transaction {
+        Transaction {
+            booking_date: day_of_month(month, self.day),
+            amount: self.amount,
+            credit_debit: CreditDebit::Debit,
+            description: self.description.clone().unwrap_or_else(|| self.category.clone()),
+            counterparty: None,
+            category: Some(self.category.clone()),
+        }
+    }
+}
+
+// the day, or the last day of a shorter month
+fn day_of_month(month: YearMonth, day: u32) -> NaiveDate {
+    (1..=day)
+        .rev()
+        .find_map(|day| NaiveDate::from_ymd_opt(month.year as i32, month.month, day))
+        .unwrap_or_default()
+}
+
+// recurring expense added to an import, with what the import already had
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecurringEntry {
+    pub frequency: Frequency,
+    pub in_file: Option<Transaction>, // same category and amount booked in the file that month
+}
+
+// transactions of every definition due in one of the months, in the order of the definitions.
+// a broken definition is skipped, its error comes back with the others
+pub fn recurring_transactions(definitions: &[RecurringExpense], months: &[YearMonth]) -> (Vec<(Transaction, Frequency)>, Vec<String>) {
+    let mut transactions = Vec::new();
+    let mut errors = Vec::new();
+    for definition in definitions {
+        let due: Result<Vec<YearMonth>, String> = months
+            .iter()
+            .filter_map(|month| definition.is_due(*month).map(|due| due.then_some(*month)).transpose())
+            .collect();
+        match due {
+            Ok(due) => transactions.extend(due.into_iter().map(|month| (definition.transaction(month), definition.frequency))),
+            Err(e) => errors.push(format!("{}, skipped", e)),
+        }
+    }
+    (transactions, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rent() -> RecurringExpense {
+        serde_json::from_str(r#"{ "category": "Rent", "amount": 700.0, "start": "2024-01", "day": 31 }"#).unwrap()
+    }
+
+    #[test]
+    fn test_is_due() {
+        let mut rent = rent();
+        assert_eq!(rent.frequency, Frequency::Monthly);
+        assert!(!rent.is_due(YearMonth::new(2023, 12)).unwrap());
+        assert!(rent.is_due(YearMonth::new(2024, 1)).unwrap());
+        assert!(rent.is_due(YearMonth::new(2030, 7)).unwrap());
+
+        rent.frequency = Frequency::Quarterly;
+        rent.start = "2024-02".to_string();
+        rent.end = Some("2024-11".to_string());
+        rent.skip = vec!["2024-08".to_string()];
+        let due: Vec<u32> = (1..=12).filter(|month| rent.is_due(YearMonth::new(2024, *month)).unwrap()).collect();
+        assert_eq!(due, vec![2, 5, 11]);
+
+        rent.skip = vec!["August".to_string()];
+        assert!(rent.is_due(YearMonth::new(2024, 2)).unwrap_err().contains("invalid month"));
+    }
+
+    #[test]
+    fn test_recurring_transactions() {
+        let months = [YearMonth::new(2024, 2), YearMonth::new(2024, 3)];
+        let mut broken = rent();
+        broken.day = 0;
+        let (transactions, errors) = recurring_transactions(&[broken, rent()], &months);
+        assert_eq!(errors, vec!["Recurring Rent: invalid day 0, skipped".to_string()]);
+        assert_eq!(transactions.len(), 2);
+        let (february, frequency) = &transactions[0];
+        assert_eq!(*frequency, Frequency::Monthly);
+        assert_eq!(february.booking_date, NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()); // last day
+        assert_eq!((february.amount, february.description.as_str()), (700.0, "Rent"));
+        assert_eq!(february.category.as_deref(), Some("Rent"));
+        assert_eq!(transactions[1].0.booking_date, NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+    }
+}
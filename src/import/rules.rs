@@ -0,0 +1,220 @@
+// rule based categorisation of imported transactions
+// rules live in config.json and are checked in order, the first match wins:
+//  "rules": [
+//      { "condition": { "type": "counterparty_contains", "pattern": "Lidl" }, "category": "Groceries" },
+//      { "condition": { "type": "description_regex", "pattern": "(?i)netflix|spotify" }, "category": "Subscriptions" },
+//      { "condition": { "type": "amount_range", "min": 500.0, "max": null }, "category": "Rent" }
+//  ]
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::utils::structs::Transaction;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleCondition {
+    DescriptionContains { pattern: String }, // case insensitive
+    DescriptionRegex { pattern: String },
+    CounterpartyContains { pattern: String }, // case insensitive
+    CounterpartyRegex { pattern: String },
+    AmountRange { min: Option<f64>, max: Option<f64> }, // inclusive, open ended if None
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CategoryRule {
+    pub condition: RuleCondition,
+    pub category: String, // has to be one of the categories in the year header row
+}
+
+impl CategoryRule {
+    // turns a manual choice into a rule, counterparty is more stable than the description
+    pub fn from_transaction(transaction: &Transaction, category: &str) -> CategoryRule {
+        let condition = match &transaction.counterparty {
+            Some(counterparty) if !counterparty.trim().is_empty() => RuleCondition::CounterpartyContains {
+                pattern: counterparty.trim().to_string(),
+            },
+            _ => RuleCondition::DescriptionContains {
+                pattern: transaction.description.trim().to_string(),
+            },
+        };
+        CategoryRule { condition, category: category.to_string() }
+    }
+}
+
+// rule with its regex compiled once
+enum CompiledCondition {
+    DescriptionContains(String),
+    DescriptionRegex(Regex),
+    CounterpartyContains(String),
+    CounterpartyRegex(Regex),
+    AmountRange(Option<f64>, Option<f64>),
+}
+
+pub struct RuleEngine {
+    rules: Vec<(CompiledCondition, String)>,
+}
+
+impl RuleEngine {
+    // fails on the first invalid regex, so a typo in config doesn't silently disable a rule
+    pub fn new(rules: &[CategoryRule]) -> Result<RuleEngine, String> {
+        let mut compiled = Vec::new();
+
+        for rule in rules {
+            let condition = match &rule.condition {
+                RuleCondition::DescriptionContains { pattern } => CompiledCondition::DescriptionContains(pattern.to_lowercase()),
+                RuleCondition::DescriptionRegex { pattern } => CompiledCondition::DescriptionRegex(compile_regex(pattern)?),
+                RuleCondition::CounterpartyContains { pattern } => CompiledCondition::CounterpartyContains(pattern.to_lowercase()),
+                RuleCondition::CounterpartyRegex { pattern } => CompiledCondition::CounterpartyRegex(compile_regex(pattern)?),
+                RuleCondition::AmountRange { min, max } => CompiledCondition::AmountRange(*min, *max),
+            };
+            compiled.push((condition, rule.category.clone()));
+        }
+
+        Ok(RuleEngine { rules: compiled })
+    }
+
+    // returns category of the first matching rule which exists in the header row
+    pub fn categorise(&self, transaction: &Transaction, categories: &[String]) -> Option<String> {
+        for (condition, category) in &self.rules {
+            if !condition_matches(condition, transaction) {
+                continue;
+            }
+            if categories.contains(category) {
+                return Some(category.clone());
+            }
+            println!("Rule category {} is not in the spreadsheet, rule skipped", category);
+        }
+        None
+    }
+
+    // categorises transactions which don't have a category yet, returns how many were matched
+    pub fn categorise_transactions(&self, transactions: &mut [Transaction], categories: &[String]) -> usize {
+        let mut matched = 0;
+        for transaction in transactions.iter_mut().filter(|t| t.category.is_none()) {
+            if let Some(category) = self.categorise(transaction, categories) {
+                transaction.category = Some(category);
+                matched += 1;
+            }
+        }
+        matched
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex, String> {
+    Regex::new(pattern).map_err(|e| format!("Invalid rule regex {}: {}", pattern, e))
+}
+
+fn condition_matches(condition: &CompiledCondition, transaction: &Transaction) -> bool {
+    let counterparty = transaction.counterparty.as_deref().unwrap_or_default();
+
+    match condition {
+        CompiledCondition::DescriptionContains(pattern) => transaction.description.to_lowercase().contains(pattern.as_str()),
+        CompiledCondition::DescriptionRegex(regex) => regex.is_match(&transaction.description),
+        CompiledCondition::CounterpartyContains(pattern) => !counterparty.is_empty() && counterparty.to_lowercase().contains(pattern.as_str()),
+        CompiledCondition::CounterpartyRegex(regex) => !counterparty.is_empty() && regex.is_match(counterparty),
+        CompiledCondition::AmountRange(min, max) => {
+            min.is_none_or(|min| transaction.amount >= min) && max.is_none_or(|max| transaction.amount <= max)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::structs::CreditDebit;
+    use chrono::NaiveDate;
+
+    fn transaction(description: &str, counterparty: Option<&str>, amount: f64) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            amount,
+            credit_debit: CreditDebit::Debit,
+            description: description.to_string(),
+            counterparty: counterparty.map(|c| c.to_string()),
+            category: None,
+        }
+    }
+
+    fn rule(condition: RuleCondition, category: &str) -> CategoryRule {
+        CategoryRule { condition, category: category.to_string() }
+    }
+
+    fn categories() -> Vec<String> {
+        vec!["Groceries".to_string(), "Sweets".to_string(), "Rent".to_string()]
+    }
+
+    #[test]
+    fn test_rules_are_checked_in_order() {
+        let engine = RuleEngine::new(&[
+            rule(RuleCondition::DescriptionContains { pattern: "BAKERY".to_string() }, "Sweets"),
+            rule(RuleCondition::DescriptionContains { pattern: "bakery".to_string() }, "Groceries"),
+        ]).unwrap();
+
+        let result = engine.categorise(&transaction("Card payment Bakery", None, 3.0), &categories());
+        assert_eq!(result.as_deref(), Some("Sweets"));
+    }
+
+    #[test]
+    fn test_rule_conditions() {
+        let engine = RuleEngine::new(&[
+            rule(RuleCondition::CounterpartyContains { pattern: "lidl".to_string() }, "Groceries"),
+            rule(RuleCondition::DescriptionRegex { pattern: r"^Candy \d+$".to_string() }, "Sweets"),
+            rule(RuleCondition::AmountRange { min: Some(500.0), max: None }, "Rent"),
+        ]).unwrap();
+
+        let categories = categories();
+        assert_eq!(engine.categorise(&transaction("x", Some("LIDL Berlin"), 10.0), &categories).as_deref(), Some("Groceries"));
+        assert_eq!(engine.categorise(&transaction("Candy 42", None, 2.0), &categories).as_deref(), Some("Sweets"));
+        assert_eq!(engine.categorise(&transaction("Transfer", None, 800.0), &categories).as_deref(), Some("Rent"));
+        assert_eq!(engine.categorise(&transaction("Transfer", None, 80.0), &categories), None);
+    }
+
+    #[test]
+    fn test_rule_with_unknown_category_is_skipped() {
+        let engine = RuleEngine::new(&[
+            rule(RuleCondition::DescriptionContains { pattern: "cinema".to_string() }, "Fun"),
+            rule(RuleCondition::AmountRange { min: None, max: Some(20.0) }, "Sweets"),
+        ]).unwrap();
+
+        let result = engine.categorise(&transaction("Cinema", None, 12.0), &categories());
+        assert_eq!(result.as_deref(), Some("Sweets"));
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        let result = RuleEngine::new(&[rule(RuleCondition::DescriptionRegex { pattern: "(".to_string() }, "Sweets")]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_categorise_transactions_keeps_existing_categories() {
+        let engine = RuleEngine::new(&[
+            rule(RuleCondition::AmountRange { min: None, max: None }, "Groceries"),
+        ]).unwrap();
+
+        let mut transactions = vec![transaction("a", None, 1.0), transaction("b", None, 2.0)];
+        transactions[0].category = Some("Sweets".to_string());
+
+        assert_eq!(engine.categorise_transactions(&mut transactions, &categories()), 1);
+        assert_eq!(transactions[0].category.as_deref(), Some("Sweets"));
+        assert_eq!(transactions[1].category.as_deref(), Some("Groceries"));
+    }
+
+    #[test]
+    fn test_rule_from_transaction() {
+        let with_counterparty = CategoryRule::from_transaction(&transaction("Card 1234", Some(" Lidl "), 1.0), "Groceries");
+        assert_eq!(with_counterparty.condition, RuleCondition::CounterpartyContains { pattern: "Lidl".to_string() });
+
+        let without_counterparty = CategoryRule::from_transaction(&transaction("Netflix", None, 1.0), "Fun");
+        assert_eq!(without_counterparty.condition, RuleCondition::DescriptionContains { pattern: "Netflix".to_string() });
+        assert_eq!(without_counterparty.category, "Fun");
+    }
+
+    #[test]
+    fn test_rules_json_format() {
+        let json = r#"[{ "condition": { "type": "amount_range", "min": 1.5, "max": null }, "category": "Rent" }]"#;
+        let rules: Vec<CategoryRule> = serde_json::from_str(json).unwrap();
+        assert_eq!(rules[0].condition, RuleCondition::AmountRange { min: Some(1.5), max: None });
+    }
+}
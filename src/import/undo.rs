@@ -0,0 +1,209 @@
+// undo of a single import from the import log
+// cells written by the import get the values they had before it, the rest of the spreadsheet
+// stays as it is (unlike restoring a backup). cells changed after the import (by hand or
+// by a later import) are reported first and only reverted when forced
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use chrono::Utc;
+
+use crate::file_handlers::xls_carry_over::xls_check_carry_over;
+use crate::file_handlers::xls_handlers::{xls_current_workbook_path, xls_perform_cells_update, xls_read_cell_values, RES_WORKBOOK_NAME};
+use crate::import::import_log::{append_import_log, load_import_log, undone_imports, ImportLogEntry};
+use crate::utils::external_edits::{detect_external_edit, ExternalEdit};
+use crate::utils::structs::CellChange;
+use crate::utils::workbook_lock::WorkbookLock;
+
+// cell that doesn't hold the value the import wrote anymore
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangedCell {
+    pub cell: CellChange,
+    pub current: Option<f64>,
+}
+
+impl ChangedCell {
+    //  May 2024 Groceries: 150.00 was written, now 160.00
+    pub fn describe(&self) -> String {
+        let current = match self.current {
+            Some(value) => format!("{:.2}", value),
+            None => "empty".to_string(),
+        };
+        format!("{} {} {}: {:.2} was written, now {}", self.cell.month, self.cell.year, self.cell.category, self.cell.value, current)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UndoOutcome {
+    Undone { cells: usize, log_id: u64 }, // log_id of the undo entry
+    ChangedSinceImport(Vec<ChangedCell>), // nothing was written
+    EditedOutsideApp(String), // nothing was written, the reason
+}
+
+// import that can still be undone
+fn find_import(log: &[ImportLogEntry], import_id: u64) -> Result<&ImportLogEntry, String> {
+    let entry = log
+        .iter()
+        .find(|entry| entry.id == import_id)
+        .ok_or(format!("Import #{} not found", import_id))?;
+    if entry.undo_of.is_some() {
+        return Err(format!("#{} is an undo, it can't be undone", import_id));
+    }
+    if undone_imports(log).contains(&import_id) {
+        return Err(format!("Import #{} was already undone", import_id));
+    }
+    Ok(entry)
+}
+
+// cells of the import that were changed since, compared in cents
+pub fn changed_since_import(destination_path: &str, entry: &ImportLogEntry) -> Result<Vec<ChangedCell>, String> {
+    let workbook_path = xls_current_workbook_path(destination_path).ok_or("No spreadsheet found in destination folder")?;
+    let workbook_path_str = workbook_path.to_str().ok_or("Invalid spreadsheet path")?;
+
+    let positions: Vec<(u32, u32)> = entry.cells.iter().map(|cell| (cell.row, cell.col)).collect();
+    let current_values = xls_read_cell_values(&positions, workbook_path_str).ok_or("Cannot read the spreadsheet")?;
+
+    Ok(entry
+        .cells
+        .iter()
+        .zip(current_values)
+        .filter(|(cell, current)| current.is_none_or(|value| (value * 100.0).round() != (cell.value * 100.0).round()))
+        .map(|(cell, current)| ChangedCell { cell: cell.clone(), current })
+        .collect())
+}
+
+// writes the previous values back, changed cells are only overwritten with force
+pub fn undo_import(destination_path: &str, import_id: u64, force: bool) -> Result<UndoOutcome, String> {
+    let _lock = WorkbookLock::acquire(destination_path)?;
+    let log = load_import_log(destination_path);
+    let entry = find_import(&log, import_id)?;
+    if entry.cells.is_empty() {
+        // nothing was written, undoing only lets the file be imported again
+        return log_undo(destination_path, entry, None).map(|log_id| UndoOutcome::Undone { cells: 0, log_id });
+    }
+
+    let changed = changed_since_import(destination_path, entry)?;
+    if !changed.is_empty() && !force {
+        return Ok(UndoOutcome::ChangedSinceImport(changed));
+    }
+    // not even with force, writing would lose what the check lists
+    if let Some(workbook_path) = xls_current_workbook_path(destination_path) {
+        xls_check_carry_over(workbook_path.to_str().unwrap_or_default())?;
+    }
+    match detect_external_edit(destination_path, RES_WORKBOOK_NAME) {
+        Some(edit) if edit.can_force() && !force => {
+            return Ok(UndoOutcome::EditedOutsideApp(edit.describe()));
+        }
+        Some(edit @ ExternalEdit::OpenElsewhere(_)) => return Err(edit.describe()),
+        _ => {}
+    }
+
+    // the first recorded previous value of a cell is the one from before the import
+    let mut cells: HashMap<(u32, u32), Option<f64>> = HashMap::new();
+    for cell in &entry.cells {
+        if let Entry::Vacant(vacant) = cells.entry((cell.row, cell.col)) {
+            vacant.insert(cell.previous);
+        }
+    }
+
+    let backup_name = xls_perform_cells_update(&cells, destination_path).ok_or(format!("Failed to undo import #{}", import_id))?;
+
+    let log_id = log_undo(destination_path, entry, Some(backup_name))?;
+
+    Ok(UndoOutcome::Undone { cells: entry.cells.len(), log_id })
+}
+
+fn log_undo(destination_path: &str, entry: &ImportLogEntry, backup_file: Option<String>) -> Result<u64, String> {
+    let undo_entry = ImportLogEntry {
+        id: 0, // assigned when appended
+        timestamp: Utc::now(),
+        source_file: String::new(),
+        source_hash: String::new(),
+        month_expenses: Vec::new(),
+        cells: entry.cells.clone(),
+        backup_file,
+        undo_of: Some(entry.id),
+    };
+    append_import_log(destination_path, undo_entry).map_err(|e| format!("Undone, but the import log was not saved: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::import::preview::ImportPreview;
+    use crate::utils::structs::{CreditDebit, Transaction};
+    use chrono::NaiveDate;
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    fn transaction(amount: f64, category: &str) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(2024, 1, 10).unwrap(),
+            amount,
+            credit_debit: CreditDebit::Debit,
+            description: "Card payment".to_string(),
+            counterparty: None,
+            category: Some(category.to_string()),
+        }
+    }
+
+    // imports January groceries 40 + sweets 5 into the test workbook, returns the import id
+    fn import(destination: &str) -> u64 {
+        let mut categories = HashMap::new();
+        categories.insert(2024, vec!["Groceries".to_string(), "Sweets".to_string()]);
+        let preview = ImportPreview::from_transactions(
+            "statement.xml",
+            vec![transaction(40.0, "Groceries"), transaction(5.0, "Sweets")],
+            categories,
+        );
+        preview.commit(destination, false).unwrap();
+        load_import_log(destination).last().unwrap().id
+    }
+
+    fn january_values(destination: &str) -> Vec<Option<f64>> {
+        let workbook_path = Path::new(destination).join(RES_WORKBOOK_NAME);
+        xls_read_cell_values(&[(2, 2), (2, 3)], workbook_path.to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_undo_import() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let import_id = import(destination);
+        assert_eq!(january_values(destination), vec![Some(40.0), Some(5.0)]);
+
+        let outcome = undo_import(destination, import_id, false).unwrap();
+        assert_eq!(outcome, UndoOutcome::Undone { cells: 2, log_id: import_id + 1 });
+        assert_eq!(january_values(destination), vec![Some(100.0), None]);
+
+        // only once
+        assert!(undo_import(destination, import_id, false).is_err());
+        assert!(undo_import(destination, import_id + 1, false).is_err());
+    }
+
+    #[test]
+    fn test_undo_warns_about_changed_cells() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let import_id = import(destination);
+
+        // sweets edited by hand after the import
+        let mut edit = HashMap::new();
+        edit.insert((2, 3), Some(9.0));
+        xls_perform_cells_update(&edit, destination).unwrap();
+
+        let UndoOutcome::ChangedSinceImport(changed) = undo_import(destination, import_id, false).unwrap() else {
+            panic!("changed cells were not reported");
+        };
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].describe(), "January 2024 Sweets: 5.00 was written, now 9.00");
+        assert_eq!(january_values(destination), vec![Some(40.0), Some(9.0)]);
+
+        assert!(matches!(undo_import(destination, import_id, true).unwrap(), UndoOutcome::Undone { .. }));
+        assert_eq!(january_values(destination), vec![Some(100.0), None]);
+    }
+}
@@ -1,10 +1,45 @@
 pub mod file_handlers {
     pub mod txt_handlers;
     pub mod xls_handlers;
-    
+    pub mod xls_summary;
+    pub mod xls_budget;
+    pub mod xls_carry_over;
+    pub mod camt_handlers;
+    pub mod mt940_handlers;
 }
 
-mod utils {
+pub mod import {
+    pub mod rules;
+    pub mod preview;
+    pub mod history;
+    pub mod classifier;
+    pub mod duplicates;
+    pub mod import_log;
+    pub mod undo;
+    pub mod recurring;
+}
+
+pub mod stats {
+    pub mod series;
+    pub mod summary;
+    pub mod dashboard;
+    pub mod charts;
+    pub mod chart_export;
+    pub mod budgets;
+    pub mod forecast;
+    pub mod report;
+    pub mod cpi;
+    pub mod cashflow;
+}
+
+pub mod utils {
     pub mod structs;
     pub mod folder_file_utils;
-}
\ No newline at end of file
+    pub mod transaction_utils;
+    pub mod config;
+    pub mod backup_utils;
+    pub mod retention;
+    pub mod backup_store;
+    pub mod workbook_lock;
+    pub mod external_edits;
+}
@@ -1,17 +1,60 @@
+mod cli;
+
 use acc_app::file_handlers::txt_handlers::show_debug_data_from_file;
-use acc_app::file_handlers::txt_handlers::transform_file_into_me_struct;
-use acc_app::file_handlers::xls_handlers::xls_perform_workbook_update;
+use acc_app::file_handlers::xls_handlers::RES_WORKBOOK_NAME;
+use acc_app::import::duplicates::DuplicateKind;
+use acc_app::import::import_log::{load_import_log, undone_imports};
+use acc_app::import::undo::{undo_import, UndoOutcome};
+use acc_app::import::preview::{ImportOutcome, ImportPreview};
+use acc_app::stats::chart_export::{chart_title, default_chart_path, write_chart};
+use acc_app::stats::charts::{self, Anchor, ChartKind};
+use acc_app::stats::cpi::RealTerms;
+use acc_app::stats::dashboard::{load_dashboard, Dashboard};
+use acc_app::stats::report::export_report;
+use acc_app::stats::summary::Delta;
+use acc_app::utils::backup_utils::{diff_backup, list_backups, open_backup_read_only, restore_backup};
+use acc_app::utils::config::{read_config, save_config};
+use acc_app::utils::external_edits::detect_external_edit;
+use acc_app::utils::folder_file_utils::{migrate_to_single_workbook, open_with_default_app};
+use acc_app::utils::structs::{CreditDebit, Transaction};
 use rfd::FileDialog;
+use slint::{Model, ModelRc, SharedString, VecModel};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
 
 slint::include_modules!();
 
 // hardcoded and exposed, yep
 const DESTINATION_FOR_SAVED_SPREADSHEET: &str = "/Users/yaroslav.k0/Documents/Acc/expenses"; // filepath where tmp files are going to be stored
 
+// statistics of the last refresh, kept for hovering and switching between charts
+struct ShownStats {
+    charts: Vec<charts::Chart>,
+    real_terms: Option<RealTerms>, // prices the statistics are in, None when as booked
+    generation: u64, // counts refreshes, results of older ones are dropped
+    results: Sender<(u64, Result<Dashboard, String>)>, // from the worker threads
+}
+
 fn main() -> Result<(), slint::PlatformError> {
+    // destination folders of older versions have no single workbook yet
+    if let Err(e) = migrate_to_single_workbook(DESTINATION_FOR_SAVED_SPREADSHEET, RES_WORKBOOK_NAME) {
+        println!("Error migrating destination folder: {}", e);
+    }
 
+    // with arguments the app works as a command line tool
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args, DESTINATION_FOR_SAVED_SPREADSHEET));
+    }
 
     let ui = AppWindow::new()?;
+
+    // import waiting for the user to pick categories or decide about duplicates
+    let pending_preview: Rc<RefCell<Option<ImportPreview>>> = Rc::new(RefCell::new(None));
+    // statistics come back from worker threads through the channel, see refresh_stats
+    let (results, stats_results) = mpsc::channel();
+    let shown_stats = Rc::new(RefCell::new(ShownStats { charts: Vec::new(), real_terms: None, generation: 0, results }));
     // ui.on_request_increase_value({
     //     let ui_handle = ui.as_weak();
     //     move || {
@@ -37,17 +80,354 @@ fn main() -> Result<(), slint::PlatformError> {
     // put monthly expenses into excel hook
     ui.on_put_me_into_excel({
         let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        let pending_preview = pending_preview.clone();
         move || {
             let ui = ui_handle.unwrap();
 
             let tmp = ui.get_filepath();
             let filepath = tmp.as_str();
 
-            if let Some(me) = transform_file_into_me_struct(filepath){
-                let _success = xls_perform_workbook_update(me, DESTINATION_FOR_SAVED_SPREADSHEET);
+            ui.set_budget_alerts(ModelRc::default()); // of the previous import
+            // without the rules from config everything would have to be categorised by hand
+            let config = match read_config(DESTINATION_FOR_SAVED_SPREADSHEET) {
+                Ok(config) => config,
+                Err(e) => {
+                    ui.set_state(format!("{}, fix it and import again", e).into());
+                    return;
+                }
+            };
+            match ImportPreview::load(filepath, DESTINATION_FOR_SAVED_SPREADSHEET, &config) {
+                Ok(preview) => {
+                    let nothing_to_ask = preview.is_resolved()
+                        && preview.duplicates().is_empty()
+                        && preview.income_credits().is_empty()
+                        && preview.recurring().is_empty()
+                        && preview.recurring_errors().is_empty()
+                        && preview.overlapping_months().is_empty()
+                        && preview.overspent().is_empty()
+                        && preview.anomalies().is_empty()
+                        && preview.formula_cells(DESTINATION_FOR_SAVED_SPREADSHEET).is_empty()
+                        && detect_external_edit(DESTINATION_FOR_SAVED_SPREADSHEET, RES_WORKBOOK_NAME).is_none();
+                    if nothing_to_ask {
+                        // everything is categorised, nothing to ask
+                        show_commit(&ui, preview.commit(DESTINATION_FOR_SAVED_SPREADSHEET, false));
+                        clear_preview(&ui);
+                        *pending_preview.borrow_mut() = None;
+                        refresh_stats(&ui, &shown_stats);
+                    } else {
+                        show_preview(&ui, &preview);
+                        *pending_preview.borrow_mut() = Some(preview);
+                    }
+                }
+                Err(e) => {
+                    ui.set_state(e.into());
+                    clear_preview(&ui);
+                    *pending_preview.borrow_mut() = None;
+                }
+            }
+        }
+    });
+
+    // set once the user was told the spreadsheet was edited outside the app, "Write" again writes anyway
+    let write_warned: Rc<Cell<bool>> = Rc::new(Cell::new(false));
+
+    // decisions from the "Possible duplicates", "Recurring" and "Uncategorised" lists are applied and the import is written
+    ui.on_write_preview({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        let pending_preview = pending_preview.clone();
+        let write_warned = write_warned.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let mut pending = pending_preview.borrow_mut();
+            let Some(preview) = pending.as_mut() else {
+                return;
+            };
+
+            for row in ui.get_duplicates().iter() {
+                preview.set_excluded(row.index as usize, !row.keep);
+            }
+            for row in ui.get_recurring().iter() {
+                preview.set_excluded(row.index as usize, !row.keep);
+            }
+            for row in ui.get_income_credits().iter() {
+                preview.set_excluded(row.index as usize, !row.keep);
+            }
+
+            // config broken since the preview was loaded: choices are applied, only no rules are saved
+            let mut config = read_config(DESTINATION_FOR_SAVED_SPREADSHEET).unwrap_or_default();
+            let mut new_rules = false;
+            for row in ui.get_uncategorised().iter() {
+                if row.category.is_empty() {
+                    continue;
+                }
+                match preview.assign_category(row.index as usize, row.category.as_str(), row.make_rule, &mut config) {
+                    Ok(()) => new_rules |= row.make_rule,
+                    Err(e) => println!("{}", e),
+                }
+            }
+            if new_rules {
+                if let Err(e) = save_config(DESTINATION_FOR_SAVED_SPREADSHEET, &config) {
+                    println!("Error saving config: {}", e);
+                }
+            }
+
+            // kept duplicates and credits can still be uncategorised
+            if !preview.is_resolved() {
+                show_preview(&ui, preview);
+                return;
+            }
+
+            let force = write_warned.replace(false);
+            let result = preview.commit(DESTINATION_FOR_SAVED_SPREADSHEET, force);
+            // edited or open elsewhere: the preview stays, so it can be written once that is sorted out
+            if let (Err(e), Some(edit)) = (&result, detect_external_edit(DESTINATION_FOR_SAVED_SPREADSHEET, RES_WORKBOOK_NAME)) {
+                // content the write can't keep is refused even then
+                if edit.can_force() && *e == edit.describe() {
+                    write_warned.set(true);
+                    ui.set_state(format!("{}. Click Write again to write anyway", e).into());
+                } else {
+                    ui.set_state(e.clone().into());
+                }
+                return;
+            }
+
+            let written = result.is_ok();
+            show_commit(&ui, result);
+            clear_preview(&ui);
+            *pending = None;
+            if written {
+                refresh_stats(&ui, &shown_stats);
             }
-            // not finished
-            // add response on the frontend
+        }
+    });
+
+    ui.on_cancel_preview({
+        let ui_handle = ui.as_weak();
+        let pending_preview = pending_preview.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            write_warned.set(false);
+            clear_preview(&ui);
+            *pending_preview.borrow_mut() = None;
+            ui.set_state("Import cancelled".into());
+        }
+    });
+
+    ui.on_show_history({
+        let ui_handle = ui.as_weak();
+        move || {
+            let ui = ui_handle.unwrap();
+            let rows = import_history_rows();
+            if rows.row_count() == 0 {
+                ui.set_state("Nothing imported yet".into());
+            }
+            ui.set_import_history(rows);
+        }
+    });
+
+    // import the user was warned about, clicking "Undo" again on it overwrites the changed cells
+    let undo_warned: Rc<Cell<Option<u64>>> = Rc::new(Cell::new(None));
+    ui.on_undo_import({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move |id| {
+            let ui = ui_handle.unwrap();
+            let id = id as u64;
+            let force = undo_warned.get() == Some(id);
+            undo_warned.set(None);
+
+            match undo_import(DESTINATION_FOR_SAVED_SPREADSHEET, id, force) {
+                Ok(UndoOutcome::Undone { cells, .. }) => {
+                    ui.set_state(format!("Import #{} undone ({} cells)", id, cells).into());
+                    refresh_stats(&ui, &shown_stats);
+                }
+                Ok(UndoOutcome::ChangedSinceImport(changed)) => {
+                    undo_warned.set(Some(id));
+                    let cells: Vec<String> = changed.iter().map(|cell| cell.describe()).collect();
+                    ui.set_state(format!("Changed since import: {}. Click Undo again to overwrite", cells.join("; ")).into());
+                }
+                Ok(UndoOutcome::EditedOutsideApp(reason)) => {
+                    undo_warned.set(Some(id));
+                    ui.set_state(format!("{}. Click Undo again to write anyway", reason).into());
+                }
+                Err(e) => ui.set_state(e.into()),
+            }
+            ui.set_import_history(import_history_rows());
+        }
+    });
+
+    ui.on_show_backups({
+        let ui_handle = ui.as_weak();
+        move || {
+            let ui = ui_handle.unwrap();
+            let rows = backup_rows();
+            if rows.row_count() == 0 {
+                ui.set_state("No backups yet".into());
+            }
+            ui.set_backups(rows);
+            ui.set_backup_diff(ModelRc::default());
+        }
+    });
+
+    ui.on_open_backup({
+        let ui_handle = ui.as_weak();
+        move |name| {
+            let ui = ui_handle.unwrap();
+            let state = match open_backup_read_only(DESTINATION_FOR_SAVED_SPREADSHEET, name.as_str()) {
+                Ok(path) => match open_with_default_app(&path) {
+                    Ok(()) => format!("Opened a read only copy of {}", name),
+                    Err(e) => format!("Cannot open {}: {}", path.display(), e),
+                },
+                Err(e) => e,
+            };
+            ui.set_state(state.into());
+        }
+    });
+
+    ui.on_diff_backup({
+        let ui_handle = ui.as_weak();
+        move |name| {
+            let ui = ui_handle.unwrap();
+            match diff_backup(DESTINATION_FOR_SAVED_SPREADSHEET, name.as_str()) {
+                Ok(diff) => {
+                    ui.set_state(format!("{} cells differ between {} and the current workbook", diff.len(), name).into());
+                    let lines: Vec<SharedString> = diff.iter().map(|cell| cell.describe().into()).collect();
+                    ui.set_backup_diff(ModelRc::new(VecModel::from(lines)));
+                }
+                Err(e) => ui.set_state(e.into()),
+            }
+        }
+    });
+
+    // backup the user was asked about, restore happens on the second click
+    let restore_asked: Rc<RefCell<Option<SharedString>>> = Rc::new(RefCell::new(None));
+    ui.on_restore_backup({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move |name| {
+            let ui = ui_handle.unwrap();
+            if restore_asked.borrow().as_ref() != Some(&name) {
+                ui.set_state(format!("Click Restore again to replace the current workbook with {}", name).into());
+                *restore_asked.borrow_mut() = Some(name);
+                return;
+            }
+            *restore_asked.borrow_mut() = None;
+
+            let state = match restore_backup(DESTINATION_FOR_SAVED_SPREADSHEET, name.as_str()) {
+                Ok(()) => {
+                    refresh_stats(&ui, &shown_stats);
+                    format!("Restored {}", name)
+                }
+                Err(e) => e,
+            };
+            ui.set_state(state.into());
+            ui.set_backups(backup_rows());
+            ui.set_backup_diff(ModelRc::default());
+        }
+    });
+
+    ui.on_refresh_stats({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move || refresh_stats(&ui_handle.unwrap(), &shown_stats)
+    });
+
+    ui.on_stats_loaded({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move || show_stats_results(&ui_handle.unwrap(), &shown_stats, &stats_results)
+    });
+
+    ui.on_select_stats_year({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move |year| {
+            let ui = ui_handle.unwrap();
+            ui.set_stats_year(year);
+            refresh_stats(&ui, &shown_stats);
+        }
+    });
+
+    let kinds: Vec<SharedString> = ChartKind::ALL.iter().map(|kind| kind.title().into()).collect();
+    ui.set_chart_kinds(ModelRc::new(VecModel::from(kinds)));
+
+    ui.on_select_chart({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move |index| {
+            let ui = ui_handle.unwrap();
+            ui.set_chart_kind(index);
+            ui.set_chart_note(SharedString::default());
+            show_chart(&ui, &shown_stats.borrow().charts);
+        }
+    });
+
+    // shown chart is saved as .svg and .png into the charts folder, on a worker thread like statistics
+    ui.on_export_chart({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let kind = ChartKind::ALL[ui.get_chart_kind() as usize];
+            let shown = shown_stats.borrow();
+            let Some(chart) = shown.charts.get(ui.get_chart_kind() as usize).cloned() else {
+                return;
+            };
+            let real_terms = shown.real_terms.clone();
+            let Ok(year) = ui.get_stats_year().parse::<i64>() else {
+                return;
+            };
+            ui.set_chart_note("Exporting chart...".into());
+
+            let ui_handle = ui.as_weak();
+            std::thread::spawn(move || {
+                let title = chart_title(kind, year, real_terms.as_ref());
+                let svg = default_chart_path(DESTINATION_FOR_SAVED_SPREADSHEET, kind, year, "svg");
+                let png = svg.with_extension("png");
+                let note = match write_chart(&chart, &title, &svg).and_then(|()| write_chart(&chart, &title, &png)) {
+                    Ok(()) => format!("Saved {} and .png", svg.display()),
+                    Err(e) => e,
+                };
+                let _ = ui_handle.upgrade_in_event_loop(move |ui| ui.set_chart_note(note.into()));
+            });
+        }
+    });
+
+    ui.on_export_report({
+        let ui_handle = ui.as_weak();
+        move || {
+            let ui = ui_handle.unwrap();
+            let Ok(year) = ui.get_stats_year().parse::<i64>() else {
+                return;
+            };
+            let real_terms = ui.get_stats_real_terms();
+            ui.set_report_note("Exporting report...".into());
+
+            let ui_handle = ui.as_weak();
+            std::thread::spawn(move || {
+                let note = match export_report(DESTINATION_FOR_SAVED_SPREADSHEET, Some(year), None, real_terms) {
+                    Ok(path) => format!("Saved {}", path.display()),
+                    Err(e) => e,
+                };
+                let _ = ui_handle.upgrade_in_event_loop(move |ui| ui.set_report_note(note.into()));
+            });
+        }
+    });
+
+    ui.on_chart_hover_at({
+        let ui_handle = ui.as_weak();
+        let shown_stats = shown_stats.clone();
+        move |x, y| {
+            let ui = ui_handle.unwrap();
+            let shown = shown_stats.borrow();
+            let label = shown
+                .charts
+                .get(ui.get_chart_kind() as usize)
+                .and_then(|chart| chart.hover_label(x as f64, y as f64))
+                .unwrap_or_default();
+            ui.set_chart_hover(label.into());
         }
     });
 
@@ -77,5 +457,406 @@ fn main() -> Result<(), slint::PlatformError> {
         }
     });
 
+    refresh_stats(&ui, &shown_stats);
     ui.run()
 }
+
+// statistics are read on a worker thread, reading a big workbook mustn't freeze the window
+fn refresh_stats(ui: &AppWindow, shown_stats: &Rc<RefCell<ShownStats>>) {
+    let year = ui.get_stats_year().parse::<i64>().ok();
+    let real_terms = ui.get_stats_real_terms();
+    ui.set_stats_loading(true);
+
+    let mut shown = shown_stats.borrow_mut();
+    shown.generation += 1;
+    let generation = shown.generation;
+    let results = shown.results.clone();
+    let ui_handle = ui.as_weak();
+    std::thread::spawn(move || {
+        let dashboard = load_dashboard(DESTINATION_FOR_SAVED_SPREADSHEET, year, real_terms);
+        // the window is gone when nobody receives
+        if results.send((generation, dashboard)).is_ok() {
+            let _ = ui_handle.upgrade_in_event_loop(|ui| ui.invoke_stats_loaded());
+        }
+    });
+}
+
+// shows what the latest refresh read, a slow earlier one finishing last is dropped
+fn show_stats_results(ui: &AppWindow, shown_stats: &Rc<RefCell<ShownStats>>, results: &Receiver<(u64, Result<Dashboard, String>)>) {
+    while let Ok((generation, dashboard)) = results.try_recv() {
+        let mut shown = shown_stats.borrow_mut();
+        if generation == shown.generation {
+            show_dashboard(ui, &mut shown, dashboard);
+        }
+    }
+}
+
+// fills the "Statistics" tab
+fn show_dashboard(ui: &AppWindow, shown: &mut ShownStats, dashboard: Result<Dashboard, String>) {
+    ui.set_stats_loading(false);
+    let dashboard = match dashboard {
+        Ok(dashboard) => dashboard,
+        Err(e) => {
+            ui.set_stats_summary(e.into());
+            ui.set_stats_top(ModelRc::default());
+            ui.set_stats_rows(ModelRc::default());
+            ui.set_stats_outliers(ModelRc::default());
+            ui.set_stats_forecast(ModelRc::default());
+            shown.charts.clear();
+            shown.real_terms = None;
+            show_chart(ui, &shown.charts);
+            return;
+        }
+    };
+
+    let years: Vec<SharedString> = dashboard.years.iter().rev().map(|year| year.to_string().into()).collect();
+    ui.set_stats_years(ModelRc::new(VecModel::from(years)));
+    ui.set_stats_year(dashboard.year.to_string().into());
+
+    let mut summary = format!("Spent {:.2} in {}", dashboard.total, dashboard.year);
+    if let Some(delta) = dashboard.total_change {
+        summary.push_str(&format!(", {} vs the same months of {}", change_label(Some(delta)), dashboard.year - 1));
+    }
+    if let Some(cash_flow) = dashboard.cash_flow {
+        summary.push_str(&format!("; earned {:.2}, saved {:.2}", cash_flow.income, cash_flow.net()));
+        if let Some(rate) = cash_flow.savings_rate() {
+            summary.push_str(&format!(" ({:.0}%)", rate * 100.0));
+        }
+    }
+    if let Some(real_terms) = &dashboard.real_terms {
+        summary.push_str(&format!(", {}", real_terms.label()));
+    }
+    ui.set_stats_summary(summary.into());
+
+    let top: Vec<SharedString> = dashboard
+        .top_categories(5)
+        .iter()
+        .enumerate()
+        .map(|(place, row)| format!("{}. {} {:.2} ({:.0}%)", place + 1, row.stats.category, row.stats.total, row.stats.share * 100.0).into())
+        .collect();
+    ui.set_stats_top(ModelRc::new(VecModel::from(top)));
+
+    let rows: Vec<StatsRow> = dashboard
+        .rows
+        .iter()
+        .map(|row| StatsRow {
+            category: row.stats.category.clone().into(),
+            total: format!("{:.2}", row.stats.total).into(),
+            mean: format!("{:.2}", row.stats.mean).into(),
+            median: format!("{:.2}", row.stats.median).into(),
+            std_dev: format!("{:.2}", row.stats.std_dev).into(),
+            range: format!("{:.2} - {:.2}", row.stats.min, row.stats.max).into(),
+            share: format!("{:.0}%", row.stats.share * 100.0).into(),
+            change: change_label(row.year_change).into(),
+            outlier: row.outlier,
+        })
+        .collect();
+    ui.set_stats_rows(ModelRc::new(VecModel::from(rows)));
+
+    let outliers: Vec<SharedString> = dashboard.outliers.iter().map(|outlier| outlier.describe().into()).collect();
+    ui.set_stats_outliers(ModelRc::new(VecModel::from(outliers)));
+
+    let forecast: Vec<SharedString> = dashboard
+        .forecast
+        .iter()
+        .flat_map(|forecast| std::iter::once(&forecast.total).chain(&forecast.categories))
+        .map(|series| series.describe().into())
+        .collect();
+    ui.set_stats_forecast(ModelRc::new(VecModel::from(forecast)));
+
+    shown.charts = dashboard.charts;
+    shown.real_terms = dashboard.real_terms;
+    show_chart(ui, &shown.charts);
+}
+
+// draws the chart picked in the "Charts" tab
+fn show_chart(ui: &AppWindow, charts: &[charts::Chart]) {
+    let Some(chart) = charts.get(ui.get_chart_kind() as usize) else {
+        ui.set_chart_paths(ModelRc::default());
+        ui.set_chart_rects(ModelRc::default());
+        ui.set_chart_points(ModelRc::default());
+        ui.set_chart_labels(ModelRc::default());
+        return;
+    };
+
+    let color = |rgb: charts::Rgb| slint::Color::from_rgb_u8(rgb[0], rgb[1], rgb[2]);
+    let paths: Vec<ChartPath> = chart
+        .paths
+        .iter()
+        .map(|path| ChartPath { commands: path.commands.clone().into(), color: color(path.color), filled: path.filled })
+        .collect();
+    let rects: Vec<ChartRect> = chart
+        .rects
+        .iter()
+        .map(|rect| ChartRect { x: rect.x as f32, y: rect.y as f32, width: rect.width as f32, height: rect.height as f32, color: color(rect.color) })
+        .collect();
+    let points: Vec<ChartPoint> = chart
+        .points
+        .iter()
+        .map(|point| ChartPoint { x: point.x as f32, y: point.y as f32, color: color(point.color) })
+        .collect();
+    let labels: Vec<ChartLabel> = chart
+        .labels
+        .iter()
+        .map(|label| ChartLabel {
+            x: label.x as f32,
+            y: label.y as f32,
+            text: label.text.clone().into(),
+            anchor: match label.anchor {
+                Anchor::Start => LabelAnchor::Start,
+                Anchor::Middle => LabelAnchor::Middle,
+                Anchor::End => LabelAnchor::End,
+            },
+        })
+        .collect();
+
+    ui.set_chart_paths(ModelRc::new(VecModel::from(paths)));
+    ui.set_chart_rects(ModelRc::new(VecModel::from(rects)));
+    ui.set_chart_points(ModelRc::new(VecModel::from(points)));
+    ui.set_chart_labels(ModelRc::new(VecModel::from(labels)));
+    ui.set_chart_hover(SharedString::default());
+}
+
+//  +12.5%, "new" when nothing was spent the year before, empty without a year to compare with
+fn change_label(delta: Option<Delta>) -> String {
+    match delta.map(|delta| delta.percent()) {
+        Some(Some(percent)) => format!("{:+.1}%", percent),
+        Some(None) => "new".to_string(),
+        None => String::new(),
+    }
+}
+
+// import log for the "History" list, newest first
+fn import_history_rows() -> ModelRc<ImportHistoryRow> {
+    let log = load_import_log(DESTINATION_FOR_SAVED_SPREADSHEET);
+    let undone = undone_imports(&log);
+
+    let rows: Vec<ImportHistoryRow> = log
+        .iter()
+        .rev()
+        .map(|entry| ImportHistoryRow {
+            id: entry.id as i32,
+            summary: entry.summary().into(),
+            can_undo: entry.undo_of.is_none() && !undone.contains(&entry.id),
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
+
+// backups for the "Backups" list, newest first
+fn backup_rows() -> ModelRc<BackupRow> {
+    let rows: Vec<BackupRow> = list_backups(DESTINATION_FOR_SAVED_SPREADSHEET)
+        .iter()
+        .map(|backup| BackupRow {
+            name: backup.file_name.clone().into(),
+            summary: backup.summary().into(),
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
+
+// state area after an import was written, budget alerts stay listed under it until the next import
+fn show_commit(ui: &AppWindow, result: Result<ImportOutcome, String>) {
+    match result {
+        Ok(outcome) if outcome.months == 0 => ui.set_state("Nothing to write, every transaction was left out".into()),
+        Ok(outcome) => {
+            let mut state = format!("Data is in spreadsheet now ({} months)", outcome.months);
+            if !outcome.alerts.is_empty() {
+                state.push_str(&format!(", {} budget alerts", outcome.alerts.len()));
+            }
+            let alerts: Vec<SharedString> = outcome.alerts.iter().map(|alert| alert.describe().into()).collect();
+            ui.set_state(state.into());
+            ui.set_budget_alerts(ModelRc::new(VecModel::from(alerts)));
+        }
+        Err(e) => ui.set_state(e.into()),
+    }
+}
+
+// fills the preview lists and tells what is left to decide
+fn show_preview(ui: &AppWindow, preview: &ImportPreview) {
+    let mut notes: Vec<String> = Vec::new();
+
+    let unresolved = preview.unresolved().len();
+    if unresolved > 0 {
+        notes.push(format!("{} transactions need a category", unresolved));
+    }
+    let duplicates = preview.duplicates().len();
+    if duplicates > 0 {
+        notes.push(format!("{} possible duplicates", duplicates));
+    }
+    let income_credits = preview.income_credits().len();
+    if income_credits > 0 {
+        notes.push(format!("{} credits look like income, the year has no Income area", income_credits));
+    }
+    let recurring = recurring_rows(preview);
+    if recurring.row_count() > 0 {
+        notes.push(format!("{} recurring expenses added", recurring.row_count()));
+    }
+    notes.extend(preview.recurring_errors().iter().cloned());
+    if !preview.overlapping_months().is_empty() {
+        notes.push(format!("already imported from another file: {}", preview.overlapping_months().join(", ")));
+    }
+    let overspent: Vec<String> = preview.overspent().iter().map(|variance| variance.describe()).collect();
+    if !overspent.is_empty() {
+        notes.push(format!("over budget: {}", overspent.join(", ")));
+    }
+    let anomalies: Vec<String> = preview.anomalies().iter().map(|anomaly| anomaly.describe()).collect();
+    if !anomalies.is_empty() {
+        notes.push(format!("unusual, check for typos: {}", anomalies.join(", ")));
+    }
+    let formula_cells = preview.formula_cells(DESTINATION_FOR_SAVED_SPREADSHEET);
+    if !formula_cells.is_empty() {
+        notes.push(format!("formulas left as they are: {}", formula_cells.join(", ")));
+    }
+    if let Some(edit) = detect_external_edit(DESTINATION_FOR_SAVED_SPREADSHEET, RES_WORKBOOK_NAME) {
+        notes.push(edit.describe());
+    }
+    if notes.is_empty() {
+        notes.push("Ready to write".to_string());
+    }
+
+    ui.set_state(notes.join("; ").into());
+    ui.set_uncategorised(uncategorised_rows(preview));
+    ui.set_duplicates(duplicate_rows(preview));
+    ui.set_income_credits(income_credit_rows(preview));
+    ui.set_recurring(recurring);
+    ui.set_preview_pending(true);
+}
+
+fn clear_preview(ui: &AppWindow) {
+    ui.set_uncategorised(ModelRc::default());
+    ui.set_duplicates(ModelRc::default());
+    ui.set_income_credits(ModelRc::default());
+    ui.set_recurring(ModelRc::default());
+    ui.set_preview_pending(false);
+}
+
+// amount as shown in the GUI, refunds are negative
+fn signed_amount(transaction: &Transaction) -> f64 {
+    match transaction.credit_debit {
+        CreditDebit::Debit => transaction.amount,
+        CreditDebit::Credit => -transaction.amount,
+    }
+}
+
+fn transaction_label(transaction: &Transaction) -> String {
+    match &transaction.counterparty {
+        Some(counterparty) => format!("{} - {}", counterparty, transaction.description),
+        None => transaction.description.clone(),
+    }
+}
+
+// rows for the "Possible duplicates" list, duplicates are skipped unless "Keep" is checked
+fn duplicate_rows(preview: &ImportPreview) -> ModelRc<DuplicateRow> {
+    let rows: Vec<DuplicateRow> = preview
+        .duplicates()
+        .into_iter()
+        .filter_map(|index| {
+            let transaction = &preview.transactions[index];
+            let duplicate = preview.duplicate_for(index)?;
+            let kind = match duplicate.kind {
+                DuplicateKind::Exact => "same as",
+                DuplicateKind::Likely => "similar to",
+                DuplicateKind::SameMonth => "same category and amount this month as",
+            };
+
+            Some(DuplicateRow {
+                index: index as i32,
+                date: transaction.booking_date.format("%d.%m.%Y").to_string().into(),
+                description: transaction_label(transaction).into(),
+                amount: format!("{:.2}", signed_amount(transaction)).into(),
+                reason: format!("{} {} {}", kind, duplicate.matched.booking_date.format("%d.%m.%Y"), transaction_label(&duplicate.matched)).into(),
+                keep: !preview.is_excluded(index),
+            })
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
+
+// rows for the "Income" list, credits bigger than their category's spending are skipped unless "Keep" is checked
+fn income_credit_rows(preview: &ImportPreview) -> ModelRc<DuplicateRow> {
+    let rows: Vec<DuplicateRow> = preview
+        .income_credits()
+        .into_iter()
+        .map(|index| {
+            let transaction = &preview.transactions[index];
+            let reason = match &transaction.category {
+                Some(category) => format!("more than {} spent this month, kept it is written as a refund", category),
+                None => "no category, kept it needs one".to_string(),
+            };
+            DuplicateRow {
+                index: index as i32,
+                date: transaction.booking_date.format("%d.%m.%Y").to_string().into(),
+                description: transaction_label(transaction).into(),
+                amount: format!("{:.2}", signed_amount(transaction)).into(),
+                reason: reason.into(),
+                keep: !preview.is_excluded(index),
+            }
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
+
+// rows for the "Recurring" list, recurring expenses the file already has are skipped unless "Keep"
+// is checked. ones that were imported before are in the duplicates list instead
+fn recurring_rows(preview: &ImportPreview) -> ModelRc<RecurringRow> {
+    let rows: Vec<RecurringRow> = preview
+        .recurring()
+        .into_iter()
+        .filter(|index| preview.duplicate_for(*index).is_none())
+        .filter_map(|index| {
+            let transaction = &preview.transactions[index];
+            let entry = preview.recurring_for(index)?;
+            let reason = match &entry.in_file {
+                Some(other) => format!("in the file already: {} {}", other.booking_date.format("%d.%m.%Y"), transaction_label(other)),
+                None => format!("recurring, {}", entry.frequency.name()),
+            };
+
+            Some(RecurringRow {
+                index: index as i32,
+                date: transaction.booking_date.format("%d.%m.%Y").to_string().into(),
+                description: transaction_label(transaction).into(),
+                amount: format!("{:.2}", signed_amount(transaction)).into(),
+                reason: reason.into(),
+                keep: !preview.is_excluded(index),
+            })
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
+
+// rows for the "Uncategorised" list in the GUI
+fn uncategorised_rows(preview: &ImportPreview) -> ModelRc<UncategorisedRow> {
+    let rows: Vec<UncategorisedRow> = preview
+        .unresolved()
+        .into_iter()
+        .map(|index| {
+            let transaction = &preview.transactions[index];
+            let categories: Vec<SharedString> = preview.categories_for(index).iter().map(|c| SharedString::from(c.as_str())).collect();
+
+            // low confidence suggestion is only shown next to the list, nothing is picked until the user does
+            let suggestion = preview
+                .suggestion_for(index)
+                .map(|suggestion| format!("{}? {:.0}%", suggestion.category, suggestion.confidence * 100.0))
+                .unwrap_or_default();
+
+            UncategorisedRow {
+                index: index as i32,
+                date: transaction.booking_date.format("%d.%m.%Y").to_string().into(),
+                description: transaction_label(transaction).into(),
+                amount: format!("{:.2}", signed_amount(transaction)).into(),
+                categories: ModelRc::new(VecModel::from(categories)),
+                category: SharedString::new(),
+                suggestion: suggestion.into(),
+                make_rule: false,
+            }
+        })
+        .collect();
+
+    ModelRc::new(VecModel::from(rows))
+}
@@ -0,0 +1,232 @@
+// monthly budget per category, kept in config.json:
+//  { "category": "Groceries", "monthly": 400.0, "overrides": { "December": 600.0, "2024-07": 0.0 } }
+// an override by month name holds for that month of every year, "2024-07" only for July 2024
+// and wins over the month name
+
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::utils::transaction_utils::month_name_from_number;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Budget {
+    pub category: String,
+    pub monthly: f64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub overrides: BTreeMap<String, f64>,
+}
+
+impl Budget {
+    pub fn amount_for(&self, month: YearMonth) -> f64 {
+        let year_month = format!("{}-{:02}", month.year, month.month);
+        let month_name = month_name_from_number(month.month).unwrap_or_default();
+        self.overrides
+            .get(&year_month)
+            .or_else(|| self.overrides.get(month_name))
+            .copied()
+            .unwrap_or(self.monthly)
+    }
+
+    // sum of the months of a year
+    pub fn amount_for_year(&self, year: i64) -> f64 {
+        (1..=12).fold(0.0, |total, month| total + self.amount_for(YearMonth::new(year, month)))
+    }
+}
+
+pub fn budget_for<'a>(budgets: &'a [Budget], category: &str) -> Option<&'a Budget> {
+    budgets.iter().find(|budget| budget.category == category)
+}
+
+// actual spend of a category against its budget, for one month
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetVariance {
+    pub category: String,
+    pub month: YearMonth,
+    pub budget: f64,
+    pub actual: f64,
+}
+
+impl BudgetVariance {
+    // positive = overspent
+    pub fn variance(&self) -> f64 {
+        self.actual - self.budget
+    }
+
+    // actual as percent of the budget, None for a 0 budget
+    pub fn percent(&self) -> Option<f64> {
+        (self.budget != 0.0).then(|| self.actual / self.budget * 100.0)
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.actual > self.budget
+    }
+
+    //  Groceries May 2024: 450.00 of 400.00 (+50.00, 112%)
+    pub fn describe(&self) -> String {
+        let percent = match self.percent() {
+            Some(percent) => format!(", {:.0}%", percent),
+            None => String::new(),
+        };
+        format!(
+            "{} {}: {:.2} of {:.2} ({:+.2}{})",
+            self.category,
+            self.month.label(),
+            self.actual,
+            self.budget,
+            self.variance(),
+            percent
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AlertKind {
+    MonthOverBudget,
+    YearOnTrackToOverspend, // spend so far, kept up for the rest of the year, goes over the year's budget
+}
+
+// something the import should tell about loudly
+#[derive(Clone, Debug, PartialEq)]
+pub struct BudgetAlert {
+    pub kind: AlertKind,
+    pub category: String,
+    pub month: YearMonth, // month that went over, or the last month counted for the year
+    pub budget: f64, // of the month, or of the whole year
+    pub actual: f64, // spent in the month, or in the year so far
+    pub percent: f64, // of the budget: actual for a month, projected spend for a year
+}
+
+impl BudgetAlert {
+    pub fn from_variance(variance: &BudgetVariance) -> BudgetAlert {
+        BudgetAlert {
+            kind: AlertKind::MonthOverBudget,
+            category: variance.category.clone(),
+            month: variance.month,
+            budget: variance.budget,
+            actual: variance.actual,
+            percent: variance.percent().unwrap_or(f64::INFINITY),
+        }
+    }
+
+    //  Groceries May 2024 over budget: 450.00 of 400.00 (112%)
+    //  Groceries 2024 on track to overspend: 2400.00 spent by May, 120% of 4800.00 expected
+    pub fn describe(&self) -> String {
+        match self.kind {
+            AlertKind::MonthOverBudget => format!(
+                "{} {} over budget: {:.2} of {:.2} ({:.0}%)",
+                self.category,
+                self.month.label(),
+                self.actual,
+                self.budget,
+                self.percent
+            ),
+            AlertKind::YearOnTrackToOverspend => format!(
+                "{} {} on track to overspend: {:.2} spent by {}, {:.0}% of {:.2} expected",
+                self.category,
+                self.month.year,
+                self.actual,
+                month_name_from_number(self.month.month).unwrap_or_default(),
+                self.percent,
+                self.budget
+            ),
+        }
+    }
+}
+
+// spend of the year up to the given month, carried on at the same pace for the rest of the year
+// alert when that goes over the budget of the whole year
+pub fn year_alert(budget: &Budget, series: &ExpenseSeries, through: YearMonth) -> Option<BudgetAlert> {
+    let year_budget = budget.amount_for_year(through.year);
+    let spent = (1..=through.month)
+        .filter_map(|month| series.value(&budget.category, YearMonth::new(through.year, month)))
+        .fold(0.0, |total, value| total + value);
+    let projected = spent / through.month as f64 * 12.0;
+    if year_budget <= 0.0 || projected <= year_budget {
+        return None;
+    }
+    Some(BudgetAlert {
+        kind: AlertKind::YearOnTrackToOverspend,
+        category: budget.category.clone(),
+        month: through,
+        budget: year_budget,
+        actual: spent,
+        percent: projected / year_budget * 100.0,
+    })
+}
+
+// every budgeted category in the month, nothing spent counts as 0
+// categories not in the given list (not in that year's header) are left out
+pub fn month_variances(budgets: &[Budget], series: &ExpenseSeries, month: YearMonth, categories: &[String]) -> Vec<BudgetVariance> {
+    budgets
+        .iter()
+        .filter(|budget| categories.contains(&budget.category))
+        .map(|budget| BudgetVariance {
+            category: budget.category.clone(),
+            month,
+            budget: budget.amount_for(month),
+            actual: series.value(&budget.category, month).unwrap_or(0.0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groceries() -> Budget {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("December".to_string(), 600.0);
+        overrides.insert("2024-12".to_string(), 700.0);
+        Budget { category: "Groceries".to_string(), monthly: 400.0, overrides }
+    }
+
+    #[test]
+    fn test_amount_for() {
+        let budget = groceries();
+        assert_eq!(budget.amount_for(YearMonth::new(2024, 5)), 400.0);
+        assert_eq!(budget.amount_for(YearMonth::new(2023, 12)), 600.0);
+        assert_eq!(budget.amount_for(YearMonth::new(2024, 12)), 700.0); // year-month wins
+        assert_eq!(budget.amount_for_year(2023), 11.0 * 400.0 + 600.0);
+    }
+
+    #[test]
+    fn test_month_variances() {
+        let mut series = ExpenseSeries::default();
+        series.insert("Groceries", YearMonth::new(2024, 5), 450.0);
+        let budgets = vec![groceries(), Budget { category: "Sweets".to_string(), monthly: 20.0, overrides: BTreeMap::new() }];
+        let categories = vec!["Groceries".to_string(), "Sweets".to_string()];
+
+        let variances = month_variances(&budgets, &series, YearMonth::new(2024, 5), &categories);
+        assert_eq!(variances.len(), 2);
+        assert!(variances[0].is_over());
+        assert_eq!(variances[0].variance(), 50.0);
+        assert_eq!(variances[0].describe(), "Groceries May 2024: 450.00 of 400.00 (+50.00, 112%)");
+        assert!(!variances[1].is_over());
+        assert_eq!(variances[1].actual, 0.0);
+
+        let alert = BudgetAlert::from_variance(&variances[0]);
+        assert_eq!(alert.describe(), "Groceries May 2024 over budget: 450.00 of 400.00 (112%)");
+
+        // Sweets is not a category of that year
+        assert_eq!(month_variances(&budgets, &series, YearMonth::new(2024, 5), &categories[..1]).len(), 1);
+    }
+
+    #[test]
+    fn test_year_alert() {
+        let budget = Budget { category: "Groceries".to_string(), monthly: 400.0, overrides: BTreeMap::new() };
+        let mut series = ExpenseSeries::default();
+        for month in 1..=4 {
+            series.insert("Groceries", YearMonth::new(2024, month), 390.0);
+        }
+        // within budget every month and for the year
+        assert_eq!(year_alert(&budget, &series, YearMonth::new(2024, 4)), None);
+
+        series.insert("Groceries", YearMonth::new(2024, 5), 600.0);
+        let alert = year_alert(&budget, &series, YearMonth::new(2024, 5)).unwrap();
+        assert_eq!(alert.kind, AlertKind::YearOnTrackToOverspend);
+        assert_eq!((alert.budget, alert.actual), (4800.0, 2160.0));
+        assert!((alert.percent - 108.0).abs() < 1e-9);
+        assert_eq!(alert.describe(), "Groceries 2024 on track to overspend: 2160.00 spent by May, 108% of 4800.00 expected");
+    }
+}
@@ -0,0 +1,77 @@
+// net cash flow (income minus expenses) and savings rate (net / income) per month and year.
+// a month counts once it has expenses or income, the missing side is 0. without any
+// income the savings rate is not known rather than minus infinity
+
+use std::collections::BTreeMap;
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CashFlow {
+    pub income: f64,
+    pub expenses: f64,
+}
+
+impl CashFlow {
+    pub fn net(&self) -> f64 {
+        self.income - self.expenses
+    }
+
+    // share of the income that was not spent, 0.25 for 25 %
+    pub fn savings_rate(&self) -> Option<f64> {
+        (self.income > 0.0).then(|| self.net() / self.income)
+    }
+
+    pub fn has_income(&self) -> bool {
+        self.income != 0.0
+    }
+}
+
+pub fn monthly_cash_flow(series: &ExpenseSeries) -> BTreeMap<YearMonth, CashFlow> {
+    let mut flows: BTreeMap<YearMonth, CashFlow> = BTreeMap::new();
+    for (month, expenses) in series.monthly_totals() {
+        flows.entry(month).or_default().expenses = expenses;
+    }
+    for (month, income) in series.income() {
+        flows.entry(*month).or_default().income = *income;
+    }
+    flows
+}
+
+pub fn yearly_cash_flow(series: &ExpenseSeries) -> BTreeMap<i64, CashFlow> {
+    let mut flows: BTreeMap<i64, CashFlow> = BTreeMap::new();
+    for (month, flow) in monthly_cash_flow(series) {
+        let year = flows.entry(month.year).or_default();
+        year.income += flow.income;
+        year.expenses += flow.expenses;
+    }
+    flows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cash_flow() {
+        let mut series = ExpenseSeries::default();
+        series.insert("Groceries", YearMonth::new(2024, 1), 300.0);
+        series.insert("Rent", YearMonth::new(2024, 1), 700.0);
+        series.insert("Groceries", YearMonth::new(2024, 2), 400.0);
+        series.insert_income(YearMonth::new(2024, 1), 2000.0);
+        series.insert_income(YearMonth::new(2024, 3), 500.0); // income without expenses
+
+        let months = monthly_cash_flow(&series);
+        assert_eq!(months.len(), 3);
+        let january = months[&YearMonth::new(2024, 1)];
+        assert_eq!(january.net(), 1000.0);
+        assert_eq!(january.savings_rate(), Some(0.5));
+        let february = months[&YearMonth::new(2024, 2)];
+        assert_eq!(february.net(), -400.0);
+        assert_eq!(february.savings_rate(), None);
+
+        let year = yearly_cash_flow(&series)[&2024];
+        assert_eq!(year, CashFlow { income: 2500.0, expenses: 1400.0 });
+        assert_eq!(year.savings_rate(), Some(0.44));
+    }
+}
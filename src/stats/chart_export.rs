@@ -0,0 +1,222 @@
+// charts written to files for pasting into reports, without the GUI:
+//  .svg  the shapes of stats::charts as they are, hover labels become <title> tooltips
+//  .png  the svg rasterised at twice its size
+// same data always gives the same svg (fixed canvas, fixed number formatting), the png only
+// depends on it and the fonts installed
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::stats::charts::{build_chart, Anchor, Chart, ChartKind, Rgb, CHART_HEIGHT, CHART_WIDTH};
+use crate::stats::cpi::RealTerms;
+use crate::stats::dashboard::load_series;
+
+pub const CHARTS_FOLDER: &str = "charts"; // in the destination folder
+const PNG_SCALE: f32 = 2.0;
+
+pub fn chart_svg(chart: &Chart, title: &str) -> String {
+    let mut svg = vec![
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="Helvetica, Arial, DejaVu Sans, sans-serif" font-size="10">"#,
+            w = CHART_WIDTH,
+            h = CHART_HEIGHT
+        ),
+        format!("<title>{}</title>", escape(title)),
+        format!(r#"<rect width="{}" height="{}" fill="white"/>"#, CHART_WIDTH, CHART_HEIGHT),
+    ];
+
+    for path in &chart.paths {
+        let paint = if path.filled {
+            format!(r#"fill="{}""#, hex(path.color))
+        } else {
+            format!(r#"fill="none" stroke="{}" stroke-width="2""#, hex(path.color))
+        };
+        svg.push(format!(r#"<path d="{}" {}/>"#, path.commands, paint));
+    }
+    for rect in &chart.rects {
+        svg.push(format!(
+            r#"<rect x="{:.2}" y="{:.2}" width="{:.2}" height="{:.2}" fill="{}">{}</rect>"#,
+            rect.x,
+            rect.y,
+            rect.width,
+            rect.height,
+            hex(rect.color),
+            tooltip(&rect.label)
+        ));
+    }
+    for point in &chart.points {
+        svg.push(format!(r#"<circle cx="{:.2}" cy="{:.2}" r="3" fill="{}">{}</circle>"#, point.x, point.y, hex(point.color), tooltip(&point.label)));
+    }
+    for label in &chart.labels {
+        let anchor = match label.anchor {
+            Anchor::Start => "start",
+            Anchor::Middle => "middle",
+            Anchor::End => "end",
+        };
+        svg.push(format!(
+            r##"<text x="{:.2}" y="{:.2}" text-anchor="{}" dominant-baseline="middle" fill="#6c757d">{}</text>"##,
+            label.x,
+            label.y,
+            anchor,
+            escape(&label.text)
+        ));
+    }
+
+    svg.push("</svg>".to_string());
+    svg.join("\n") + "\n"
+}
+
+pub fn chart_png(chart: &Chart, title: &str) -> Result<Vec<u8>, String> {
+    let mut options = resvg::usvg::Options::default();
+    options.fontdb_mut().load_system_fonts();
+    let tree = resvg::usvg::Tree::from_str(&chart_svg(chart, title), &options).map_err(|e| format!("Cannot draw chart: {}", e))?;
+
+    let (width, height) = ((CHART_WIDTH as f32 * PNG_SCALE) as u32, (CHART_HEIGHT as f32 * PNG_SCALE) as u32);
+    let mut pixmap = resvg::tiny_skia::Pixmap::new(width, height).ok_or("Cannot draw chart")?;
+    resvg::render(&tree, resvg::tiny_skia::Transform::from_scale(PNG_SCALE, PNG_SCALE), &mut pixmap.as_mut());
+    pixmap.encode_png().map_err(|e| format!("Cannot encode chart: {}", e))
+}
+
+// .png or .svg, depending on the file name
+pub fn write_chart(chart: &Chart, title: &str, output: &Path) -> Result<(), String> {
+    let content = match output.extension().and_then(|extension| extension.to_str()) {
+        Some("svg") => chart_svg(chart, title).into_bytes(),
+        Some("png") => chart_png(chart, title)?,
+        _ => return Err(format!("Charts are saved as .svg or .png, not {}", output.display())),
+    };
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    fs::write(output, content).map_err(|e| format!("Cannot write {}: {}", output.display(), e))
+}
+
+//  Monthly totals 2024
+//  Monthly totals 2024, in prices of May 2024
+pub fn chart_title(kind: ChartKind, year: i64, real_terms: Option<&RealTerms>) -> String {
+    let title = match kind {
+        ChartKind::Trend => kind.title().to_string(),
+        ChartKind::YearComparison => format!("{} {} / {}", kind.title(), year - 1, year),
+        _ => format!("{} {}", kind.title(), year),
+    };
+    match real_terms {
+        Some(real_terms) => format!("{}, {}", title, real_terms.label()),
+        None => title,
+    }
+}
+
+//  <destination>/charts/shares_2024.svg
+pub fn default_chart_path(destination_path: &str, kind: ChartKind, year: i64, extension: &str) -> PathBuf {
+    Path::new(destination_path).join(CHARTS_FOLDER).join(format!("{}_{}.{}", kind.name(), year, extension))
+}
+
+// chart of the workbook in the destination folder, year defaults to the latest one.
+// output defaults to the charts folder, returns where the chart was written
+pub fn export_chart(
+    destination_path: &str,
+    kind: ChartKind,
+    year: Option<i64>,
+    output: Option<&Path>,
+    real_terms: bool,
+) -> Result<PathBuf, String> {
+    let (series, real_terms) = load_series(destination_path, real_terms)?;
+    let years = series.years();
+    let year = match year {
+        Some(year) if years.contains(&year) => year,
+        Some(year) => return Err(format!("No expenses in {}", year)),
+        None => *years.last().ok_or("No expenses in the spreadsheet yet")?,
+    };
+
+    let output = output.map(Path::to_path_buf).unwrap_or_else(|| default_chart_path(destination_path, kind, year, "svg"));
+    write_chart(&build_chart(&series, kind, year), &chart_title(kind, year, real_terms.as_ref()), &output)?;
+    Ok(output)
+}
+
+fn hex(color: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+fn tooltip(label: &str) -> String {
+    if label.is_empty() {
+        return String::new();
+    }
+    format!("<title>{}</title>", escape(label))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::file_handlers::xls_handlers::RES_WORKBOOK_NAME;
+    use crate::stats::series::{ExpenseSeries, YearMonth};
+    use crate::utils::config::{save_config, AppConfig};
+
+    fn series() -> ExpenseSeries {
+        let mut series = ExpenseSeries::default();
+        for (month, groceries, rent) in [(1, 100.0, 500.0), (2, 300.0, 500.0), (3, 200.0, 0.0)] {
+            series.insert("Groceries & more", YearMonth::new(2024, month), groceries);
+            series.insert("Rent", YearMonth::new(2024, month), rent);
+        }
+        series.insert("Groceries & more", YearMonth::new(2023, 1), 80.0);
+        series
+    }
+
+    // UPDATE_SNAPSHOTS=1 cargo test rewrites the snapshots after a deliberate change
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = Path::new(file!()).with_file_name("snapshots").join(name);
+        if std::env::var("UPDATE_SNAPSHOTS").is_ok() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, actual).unwrap();
+        }
+        let expected = fs::read_to_string(&path).unwrap_or_default();
+        assert!(expected == actual, "{} differs from the snapshot, rerun with UPDATE_SNAPSHOTS=1 if that is intended\n{}", name, actual);
+    }
+
+    #[test]
+    fn test_chart_svg_snapshots() {
+        for kind in [ChartKind::Trend, ChartKind::Shares, ChartKind::YearComparison] {
+            let chart = build_chart(&series(), kind, 2024);
+            let svg = chart_svg(&chart, &chart_title(kind, 2024, None));
+            assert_eq!(svg, chart_svg(&build_chart(&series(), kind, 2024), &chart_title(kind, 2024, None)));
+            assert_snapshot(&format!("{}.svg", kind.name()), &svg);
+        }
+    }
+
+    #[test]
+    fn test_chart_png() {
+        let chart = build_chart(&series(), ChartKind::Shares, 2024);
+        let png = chart_png(&chart, "Category shares 2024").unwrap();
+        let pixmap = resvg::tiny_skia::Pixmap::decode_png(&png).unwrap();
+        assert_eq!((pixmap.width(), pixmap.height()), (1120, 520));
+        assert_eq!(png, chart_png(&chart, "Category shares 2024").unwrap());
+    }
+
+    #[test]
+    fn test_export_chart() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let path = export_chart(destination, ChartKind::MonthlyBars, None, None, false).unwrap();
+        assert_eq!(path, temp_dir.path().join(CHARTS_FOLDER).join("months_2024.svg"));
+        assert!(fs::read_to_string(&path).unwrap().contains("<title>Groceries, January 2024: 100.00</title>"));
+
+        let png = temp_dir.path().join("report").join("trend.png");
+        assert_eq!(export_chart(destination, ChartKind::Trend, Some(2024), Some(&png), false).unwrap(), png);
+        assert!(png.exists());
+
+        assert!(export_chart(destination, ChartKind::Trend, Some(1999), None, false).is_err());
+        assert!(export_chart(destination, ChartKind::Trend, None, Some(&temp_dir.path().join("chart.pdf")), false).is_err());
+
+        // real terms need a CPI table
+        assert!(export_chart(destination, ChartKind::MonthlyBars, None, None, true).is_err());
+        fs::write(temp_dir.path().join("cpi.csv"), "2024-01,100\n2024-06,110\n").unwrap();
+        save_config(destination, &AppConfig { cpi_table: Some("cpi.csv".to_string()), ..AppConfig::default() }).unwrap();
+        let svg = fs::read_to_string(export_chart(destination, ChartKind::MonthlyBars, None, None, true).unwrap()).unwrap();
+        assert!(svg.contains("<title>Months by category 2024, in prices of June 2024</title>"));
+        assert!(svg.contains("<title>Groceries, January 2024: 110.00</title>"));
+    }
+}
@@ -0,0 +1,576 @@
+// charts of the workbook history laid out on a fixed canvas, so the GUI only has to draw
+// paths, rectangles, points and labels. coordinates are pixels from the top left corner
+//  Trend        monthly totals, one line per year
+//  MonthlyBars  every month of a year stacked by category
+//  Shares       donut of the categories of a year
+//  Heatmap      month by category, each category against its own most expensive month
+//  YearComparison  every category of a year next to the same months of the year before
+// biggest categories get a color each, the rest is drawn together as "Other"
+
+use std::f64::consts::PI;
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::stats::summary::category_stats;
+use crate::utils::transaction_utils::month_name_from_number;
+
+pub const CHART_WIDTH: f64 = 560.0;
+pub const CHART_HEIGHT: f64 = 260.0;
+
+const PLOT_LEFT: f64 = 48.0; // value axis on the left
+const PLOT_RIGHT: f64 = 430.0; // legend on the right
+const PLOT_TOP: f64 = 8.0;
+const PLOT_BOTTOM: f64 = 236.0; // month names below
+const LEGEND_LEFT: f64 = 444.0;
+const LEGEND_ROW: f64 = 18.0;
+const DONUT_CENTER: (f64, f64) = (130.0, 130.0);
+const DONUT_RADII: (f64, f64) = (62.0, 112.0); // inner, outer
+const HEATMAP_LEFT: f64 = 112.0; // category names on the left
+const HEATMAP_ROW: f64 = 24.0; // at most, shrinks with many categories
+
+const TREND_YEARS: usize = 5; // older years only clutter the lines
+const CHART_CATEGORIES: usize = 7;
+const HOVER_DISTANCE: f64 = 6.0; // around a point of a line
+
+pub type Rgb = [u8; 3];
+
+const PALETTE: [Rgb; CHART_CATEGORIES] = [
+    [0x42, 0x63, 0xeb],
+    [0xf7, 0x67, 0x07],
+    [0x2f, 0x9e, 0x44],
+    [0xe0, 0x31, 0x31],
+    [0x70, 0x48, 0xe8],
+    [0x10, 0x98, 0xad],
+    [0xf5, 0x9f, 0x00],
+];
+const OTHER_COLOR: Rgb = [0x86, 0x8e, 0x96];
+const GRID_COLOR: Rgb = [0xe9, 0xec, 0xef];
+const HEAT_COLD: Rgb = [0xf1, 0xf3, 0xf5];
+const HEAT_HOT: Rgb = [0xe0, 0x31, 0x31];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartKind {
+    Trend,
+    MonthlyBars,
+    Shares,
+    Heatmap,
+    YearComparison,
+}
+
+impl ChartKind {
+    pub const ALL: [ChartKind; 5] =
+        [ChartKind::Trend, ChartKind::MonthlyBars, ChartKind::Shares, ChartKind::Heatmap, ChartKind::YearComparison];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ChartKind::Trend => "Monthly totals",
+            ChartKind::MonthlyBars => "Months by category",
+            ChartKind::Shares => "Category shares",
+            ChartKind::Heatmap => "Heatmap",
+            ChartKind::YearComparison => "Year comparison",
+        }
+    }
+
+    // for the command line and file names
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChartKind::Trend => "trend",
+            ChartKind::MonthlyBars => "months",
+            ChartKind::Shares => "shares",
+            ChartKind::Heatmap => "heatmap",
+            ChartKind::YearComparison => "years",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ChartKind> {
+        ChartKind::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+// which side of the text its x is on
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+// svg path commands, lines are stroked, donut slices filled
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartPath {
+    pub commands: String,
+    pub color: Rgb,
+    pub filled: bool,
+}
+
+// bars, heatmap cells, legend swatches and grid lines (no label, nothing to hover)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartRect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub color: Rgb,
+    pub label: String,
+}
+
+impl ChartRect {
+    fn contains(&self, x: f64, y: f64) -> bool {
+        x >= self.x && x <= self.x + self.width && y >= self.y && y <= self.y + self.height
+    }
+}
+
+// month on a line
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartPoint {
+    pub x: f64,
+    pub y: f64,
+    pub color: Rgb,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChartLabel {
+    pub x: f64,
+    pub y: f64, // middle of the text
+    pub text: String,
+    pub anchor: Anchor,
+}
+
+// area of a donut slice, angles in radians clockwise from 12 o'clock
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sector {
+    pub start: f64,
+    pub end: f64,
+    pub label: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chart {
+    pub kind: ChartKind,
+    pub paths: Vec<ChartPath>,
+    pub rects: Vec<ChartRect>,
+    pub points: Vec<ChartPoint>,
+    pub sectors: Vec<Sector>,
+    pub labels: Vec<ChartLabel>,
+}
+
+impl Chart {
+    fn new(kind: ChartKind) -> Chart {
+        Chart { kind, paths: Vec::new(), rects: Vec::new(), points: Vec::new(), sectors: Vec::new(), labels: Vec::new() }
+    }
+
+    // exact value under the pointer, points sit on top of everything else
+    pub fn hover_label(&self, x: f64, y: f64) -> Option<&str> {
+        let point = self.points.iter().rev().find(|point| (point.x - x).hypot(point.y - y) <= HOVER_DISTANCE);
+        if let Some(point) = point {
+            return Some(&point.label);
+        }
+        let rect = self.rects.iter().rev().find(|rect| !rect.label.is_empty() && rect.contains(x, y));
+        if let Some(rect) = rect {
+            return Some(&rect.label);
+        }
+
+        let (dx, dy) = (x - DONUT_CENTER.0, y - DONUT_CENTER.1);
+        let distance = dx.hypot(dy);
+        if distance < DONUT_RADII.0 || distance > DONUT_RADII.1 {
+            return None;
+        }
+        let angle = dx.atan2(-dy).rem_euclid(2.0 * PI);
+        self.sectors.iter().find(|sector| angle >= sector.start && angle < sector.end).map(|sector| sector.label.as_str())
+    }
+}
+
+// every kind of chart, in ChartKind::ALL order. the year is the one of bars, shares and heatmap
+pub fn build_charts(series: &ExpenseSeries, year: i64) -> Vec<Chart> {
+    ChartKind::ALL.iter().map(|kind| build_chart(series, *kind, year)).collect()
+}
+
+pub fn build_chart(series: &ExpenseSeries, kind: ChartKind, year: i64) -> Chart {
+    match kind {
+        ChartKind::Trend => trend_chart(series),
+        ChartKind::MonthlyBars => monthly_bars_chart(series, year),
+        ChartKind::Shares => shares_chart(series, year),
+        ChartKind::Heatmap => heatmap_chart(series, year),
+        ChartKind::YearComparison => year_comparison_chart(series, year),
+    }
+}
+
+pub fn trend_chart(series: &ExpenseSeries) -> Chart {
+    let mut chart = Chart::new(ChartKind::Trend);
+    let totals = series.monthly_totals();
+    let years = series.years();
+    let years = &years[years.len().saturating_sub(TREND_YEARS)..];
+
+    let max = nice_max(totals.iter().filter(|(month, _)| years.contains(&month.year)).map(|(_, total)| *total).fold(0.0, f64::max));
+    value_axis(&mut chart, max);
+    month_axis(&mut chart, line_x, PLOT_BOTTOM + 12.0);
+
+    for (index, year) in years.iter().enumerate() {
+        let color = PALETTE[index % PALETTE.len()];
+        let mut commands: Vec<String> = Vec::new();
+        let mut previous_month = None;
+        let mut year_total = 0.0;
+
+        for month in 1..=12 {
+            let month = YearMonth::new(*year, month);
+            let Some(total) = totals.get(&month) else {
+                continue;
+            };
+            let (x, y) = (line_x(month.month), value_y(*total, max));
+            // gap without data starts a new line
+            let command = if previous_month == Some(month.month - 1) { "L" } else { "M" };
+            commands.push(format!("{} {:.1} {:.1}", command, x, y));
+            chart.points.push(ChartPoint { x, y, color, label: format!("{}: {:.2}", month.label(), total) });
+            previous_month = Some(month.month);
+            year_total += total;
+        }
+
+        chart.paths.push(ChartPath { commands: commands.join(" "), color, filled: false });
+        legend_entry(&mut chart, LEGEND_LEFT, index, color, year.to_string(), format!("{}: {:.2} in total", year, year_total));
+    }
+    chart
+}
+
+pub fn monthly_bars_chart(series: &ExpenseSeries, year: i64) -> Chart {
+    let mut chart = Chart::new(ChartKind::MonthlyBars);
+    let groups = category_groups(series, year);
+    let band = (PLOT_RIGHT - PLOT_LEFT) / 12.0;
+
+    // refunds bigger than the spend of a month are left out, bars only go up
+    let month_total = |month: YearMonth| groups.iter().map(|group| group.value(series, month).max(0.0)).sum::<f64>();
+    let max = nice_max((1..=12).map(|month| month_total(YearMonth::new(year, month))).fold(0.0, f64::max));
+    value_axis(&mut chart, max);
+    month_axis(&mut chart, |month| PLOT_LEFT + band * (month as f64 - 0.5), PLOT_BOTTOM + 12.0);
+
+    for month in 1..=12 {
+        let month = YearMonth::new(year, month);
+        let mut top = PLOT_BOTTOM;
+        for group in &groups {
+            let value = group.value(series, month);
+            if value <= 0.0 {
+                continue;
+            }
+            let height = value / max * (PLOT_BOTTOM - PLOT_TOP);
+            top -= height;
+            chart.rects.push(ChartRect {
+                x: PLOT_LEFT + band * (month.month as f64 - 1.0) + band * 0.15,
+                y: top,
+                width: band * 0.7,
+                height,
+                color: group.color,
+                label: format!("{}, {}: {:.2}", group.name, month.label(), value),
+            });
+        }
+    }
+
+    for (index, group) in groups.iter().enumerate() {
+        let total: f64 = (1..=12).map(|month| group.value(series, YearMonth::new(year, month))).sum();
+        legend_entry(&mut chart, LEGEND_LEFT, index, group.color, group.name.clone(), format!("{} in {}: {:.2}", group.name, year, total));
+    }
+    chart
+}
+
+pub fn shares_chart(series: &ExpenseSeries, year: i64) -> Chart {
+    let mut chart = Chart::new(ChartKind::Shares);
+    let totals: Vec<(CategoryGroup, f64)> = category_groups(series, year)
+        .into_iter()
+        .map(|group| {
+            let total = (1..=12).map(|month| group.value(series, YearMonth::new(year, month))).sum();
+            (group, total)
+        })
+        .filter(|(_, total)| *total > 0.0)
+        .collect();
+    let grand_total: f64 = totals.iter().map(|(_, total)| total).sum();
+
+    let mut start = 0.0;
+    for (index, (group, total)) in totals.iter().enumerate() {
+        let share = total / grand_total;
+        let end = start + share * 2.0 * PI;
+        let label = format!("{} in {}: {:.2} ({:.1}%)", group.name, year, total, share * 100.0);
+
+        chart.paths.push(ChartPath { commands: slice_commands(start, end), color: group.color, filled: true });
+        chart.sectors.push(Sector { start, end, label: label.clone() });
+        legend_entry(&mut chart, DONUT_CENTER.0 + DONUT_RADII.1 + 40.0, index, group.color, format!("{} {:.0}%", group.name, share * 100.0), label);
+        start = end;
+    }
+    chart
+}
+
+pub fn heatmap_chart(series: &ExpenseSeries, year: i64) -> Chart {
+    let mut chart = Chart::new(ChartKind::Heatmap);
+    let categories: Vec<String> = category_stats(series, Some(year)).into_iter().map(|stats| stats.category).collect();
+    let row = HEATMAP_ROW.min((PLOT_BOTTOM - PLOT_TOP) / categories.len().max(1) as f64);
+    let column = (CHART_WIDTH - 8.0 - HEATMAP_LEFT) / 12.0;
+
+    for (index, category) in categories.iter().enumerate() {
+        let y = PLOT_TOP + row * index as f64;
+        chart.labels.push(ChartLabel { x: HEATMAP_LEFT - 6.0, y: y + row / 2.0, text: category.clone(), anchor: Anchor::End });
+
+        let values: Vec<(YearMonth, f64)> = (1..=12)
+            .filter_map(|month| {
+                let month = YearMonth::new(year, month);
+                Some((month, series.value(category, month)?))
+            })
+            .collect();
+        let max = values.iter().map(|(_, value)| *value).fold(0.0, f64::max);
+
+        for (month, value) in values {
+            let intensity = if max > 0.0 { (value / max).clamp(0.0, 1.0) } else { 0.0 };
+            chart.rects.push(ChartRect {
+                x: HEATMAP_LEFT + column * (month.month as f64 - 1.0),
+                y,
+                width: column - 1.0,
+                height: row - 1.0,
+                color: mix(HEAT_COLD, HEAT_HOT, intensity),
+                label: format!("{}, {}: {:.2}", category, month.label(), value),
+            });
+        }
+    }
+
+    month_axis(&mut chart, |month| HEATMAP_LEFT + column * (month as f64 - 0.5), PLOT_TOP + row * categories.len() as f64 + 10.0);
+    chart
+}
+
+// only months both years have data for, like summary::year_over_year
+pub fn year_comparison_chart(series: &ExpenseSeries, year: i64) -> Chart {
+    let mut chart = Chart::new(ChartKind::YearComparison);
+    let groups = category_groups(series, year);
+    let totals = series.monthly_totals();
+    let months: Vec<u32> = (1..=12)
+        .filter(|month| totals.contains_key(&YearMonth::new(year, *month)) && totals.contains_key(&YearMonth::new(year - 1, *month)))
+        .collect();
+    let sum = |group: &CategoryGroup, year: i64| {
+        months.iter().map(|month| group.value(series, YearMonth::new(year, *month))).fold(0.0, |total, value| total + value)
+    };
+
+    let values: Vec<(f64, f64)> = groups.iter().map(|group| (sum(group, year - 1).max(0.0), sum(group, year).max(0.0))).collect();
+    let max = nice_max(values.iter().map(|(previous, current)| previous.max(*current)).fold(0.0, f64::max));
+    value_axis(&mut chart, max);
+
+    let band = (PLOT_RIGHT - PLOT_LEFT) / groups.len().max(1) as f64;
+    let bar = band * 0.35;
+    for (index, (group, (previous, current))) in groups.iter().zip(values).enumerate() {
+        let left = PLOT_LEFT + band * index as f64 + band * 0.15;
+        for (offset, year, value, color) in [(0.0, year - 1, previous, OTHER_COLOR), (bar, year, current, PALETTE[0])] {
+            let height = value / max * (PLOT_BOTTOM - PLOT_TOP);
+            chart.rects.push(ChartRect { x: left + offset, y: PLOT_BOTTOM - height, width: bar, height, color, label: format!("{} in {}: {:.2}", group.name, year, value) });
+        }
+        // names longer than the band are cut
+        let fits = (band / 6.0) as usize;
+        let name: String = if group.name.chars().count() > fits { group.name.chars().take(fits.saturating_sub(1)).chain(['…']).collect() } else { group.name.clone() };
+        chart.labels.push(ChartLabel { x: PLOT_LEFT + band * (index as f64 + 0.5), y: PLOT_BOTTOM + 12.0, text: name, anchor: Anchor::Middle });
+    }
+
+    let compared = match (months.first(), months.last()) {
+        (Some(first), Some(last)) => format!("{} - {}", &month_name_from_number(*first).unwrap_or("?")[..3], &month_name_from_number(*last).unwrap_or("?")[..3]),
+        _ => "nothing to compare".to_string(),
+    };
+    legend_entry(&mut chart, LEGEND_LEFT, 0, OTHER_COLOR, (year - 1).to_string(), format!("{}, {}", year - 1, compared));
+    legend_entry(&mut chart, LEGEND_LEFT, 1, PALETTE[0], year.to_string(), format!("{}, {}", year, compared));
+    chart
+}
+
+// categories of a year sharing a color
+struct CategoryGroup {
+    name: String,
+    categories: Vec<String>,
+    color: Rgb,
+}
+
+impl CategoryGroup {
+    fn value(&self, series: &ExpenseSeries, month: YearMonth) -> f64 {
+        // fold from 0, an empty float sum is -0
+        self.categories.iter().filter_map(|category| series.value(category, month)).fold(0.0, |total, value| total + value)
+    }
+}
+
+// biggest categories of the year first, "Other" last
+fn category_groups(series: &ExpenseSeries, year: i64) -> Vec<CategoryGroup> {
+    let categories: Vec<String> = category_stats(series, Some(year)).into_iter().map(|stats| stats.category).collect();
+    let mut groups: Vec<CategoryGroup> = categories
+        .iter()
+        .take(CHART_CATEGORIES)
+        .zip(PALETTE)
+        .map(|(category, color)| CategoryGroup { name: category.clone(), categories: vec![category.clone()], color })
+        .collect();
+    if categories.len() > CHART_CATEGORIES {
+        groups.push(CategoryGroup { name: "Other".to_string(), categories: categories[CHART_CATEGORIES..].to_vec(), color: OTHER_COLOR });
+    }
+    groups
+}
+
+// ring between the donut radii, drawn as two arcs so no arc is ever more than half a circle
+fn slice_commands(start: f64, end: f64) -> String {
+    let (inner, outer) = DONUT_RADII;
+    let middle = (start + end) / 2.0;
+    let at = |radius: f64, angle: f64| {
+        format!("{:.2} {:.2}", DONUT_CENTER.0 + radius * angle.sin(), DONUT_CENTER.1 - radius * angle.cos())
+    };
+    format!(
+        "M {} A {r} {r} 0 0 1 {} A {r} {r} 0 0 1 {} L {} A {ir} {ir} 0 0 0 {} A {ir} {ir} 0 0 0 {} Z",
+        at(outer, start),
+        at(outer, middle),
+        at(outer, end),
+        at(inner, end),
+        at(inner, middle),
+        at(inner, start),
+        r = outer,
+        ir = inner,
+    )
+}
+
+fn value_axis(chart: &mut Chart, max: f64) {
+    for step in 0..=4 {
+        let value = max * step as f64 / 4.0;
+        let y = value_y(value, max);
+        chart.rects.push(ChartRect { x: PLOT_LEFT, y, width: PLOT_RIGHT - PLOT_LEFT, height: 1.0, color: GRID_COLOR, label: String::new() });
+        chart.labels.push(ChartLabel { x: PLOT_LEFT - 6.0, y, text: format!("{:.0}", value), anchor: Anchor::End });
+    }
+}
+
+fn month_axis(chart: &mut Chart, x_of: impl Fn(u32) -> f64, y: f64) {
+    for month in 1..=12 {
+        let name = month_name_from_number(month).unwrap_or("?");
+        chart.labels.push(ChartLabel { x: x_of(month), y, text: name[..3].to_string(), anchor: Anchor::Middle });
+    }
+}
+
+// colored square with the name next to it, the square shows the label on hover
+fn legend_entry(chart: &mut Chart, left: f64, row: usize, color: Rgb, text: String, label: String) {
+    let y = PLOT_TOP + LEGEND_ROW * row as f64;
+    chart.rects.push(ChartRect { x: left, y: y + 3.0, width: 10.0, height: 10.0, color, label });
+    chart.labels.push(ChartLabel { x: left + 16.0, y: y + 8.0, text, anchor: Anchor::Start });
+}
+
+fn line_x(month: u32) -> f64 {
+    PLOT_LEFT + (PLOT_RIGHT - PLOT_LEFT) * (month as f64 - 1.0) / 11.0
+}
+
+fn value_y(value: f64, max: f64) -> f64 {
+    PLOT_BOTTOM - value / max * (PLOT_BOTTOM - PLOT_TOP)
+}
+
+// top of the value axis, 1, 2, 2.5 or 5 times a power of ten
+fn nice_max(value: f64) -> f64 {
+    if value <= 0.0 {
+        return 1.0;
+    }
+    let magnitude = 10f64.powf(value.log10().floor());
+    [1.0, 2.0, 2.5, 5.0, 10.0].iter().map(|step| step * magnitude).find(|max| *max >= value).unwrap_or(10.0 * magnitude)
+}
+
+fn mix(from: Rgb, to: Rgb, amount: f64) -> Rgb {
+    let channel = |index: usize| (from[index] as f64 + (to[index] as f64 - from[index] as f64) * amount).round() as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Groceries and Rent January - March 2024, March without Rent, Groceries in May 2023
+    fn series() -> ExpenseSeries {
+        let mut series = ExpenseSeries::default();
+        series.insert("Groceries", YearMonth::new(2023, 5), 80.0);
+        for (month, groceries) in [(1, 100.0), (2, 300.0), (3, 200.0)] {
+            series.insert("Groceries", YearMonth::new(2024, month), groceries);
+        }
+        series.insert("Rent", YearMonth::new(2024, 1), 500.0);
+        series.insert("Rent", YearMonth::new(2024, 2), 500.0);
+        series
+    }
+
+    #[test]
+    fn test_trend_chart() {
+        let chart = trend_chart(&series());
+        assert_eq!(chart.paths.len(), 2); // 2023 and 2024
+        assert_eq!(chart.points.len(), 4);
+
+        // January 2024 has 600 of an axis up to 1000
+        let january = &chart.points[1];
+        assert_eq!(january.label, "January 2024: 600.00");
+        assert_eq!((january.x, january.y), (PLOT_LEFT, PLOT_BOTTOM - 0.6 * (PLOT_BOTTOM - PLOT_TOP)));
+        assert_eq!(chart.paths[1].commands.matches('L').count(), 2);
+        assert_eq!(chart.hover_label(january.x + 2.0, january.y - 2.0), Some("January 2024: 600.00"));
+        assert_eq!(chart.hover_label(january.x + 20.0, january.y - 20.0), None);
+    }
+
+    #[test]
+    fn test_monthly_bars_chart() {
+        let chart = monthly_bars_chart(&series(), 2024);
+        let bars: Vec<&ChartRect> = chart.rects.iter().filter(|rect| rect.label.contains(", ")).collect();
+        assert_eq!(bars.len(), 5);
+
+        // Rent is the bigger category, at the bottom of January
+        assert_eq!(bars[0].label, "Rent, January 2024: 500.00");
+        assert!((bars[0].y + bars[0].height - PLOT_BOTTOM).abs() < 1e-9);
+        assert!((bars[1].y + bars[1].height - bars[0].y).abs() < 1e-9);
+        let middle = (bars[1].x + bars[1].width / 2.0, bars[1].y + bars[1].height / 2.0);
+        assert_eq!(chart.hover_label(middle.0, middle.1), Some("Groceries, January 2024: 100.00"));
+    }
+
+    #[test]
+    fn test_shares_chart() {
+        let chart = shares_chart(&series(), 2024);
+        assert_eq!(chart.sectors.len(), 2);
+        assert!(chart.paths.iter().all(|path| path.filled));
+
+        // Rent is 1000 of 1600, starting at 12 o'clock
+        assert_eq!(chart.sectors[0].end, 1000.0 / 1600.0 * 2.0 * PI);
+        let (x, y) = (DONUT_CENTER.0 + 5.0, DONUT_CENTER.1 - 80.0);
+        assert_eq!(chart.hover_label(x, y), Some("Rent in 2024: 1000.00 (62.5%)"));
+        let (x, y) = (DONUT_CENTER.0 - 5.0, DONUT_CENTER.1 - 80.0);
+        assert_eq!(chart.hover_label(x, y), Some("Groceries in 2024: 600.00 (37.5%)"));
+        // hole in the middle
+        assert_eq!(chart.hover_label(DONUT_CENTER.0, DONUT_CENTER.1), None);
+    }
+
+    #[test]
+    fn test_heatmap_chart() {
+        let chart = heatmap_chart(&series(), 2024);
+        assert_eq!(chart.rects.len(), 5);
+
+        // most expensive month of a category is the hottest
+        let february = chart.rects.iter().find(|rect| rect.label == "Groceries, February 2024: 300.00").unwrap();
+        assert_eq!(february.color, HEAT_HOT);
+        let january = chart.rects.iter().find(|rect| rect.label == "Groceries, January 2024: 100.00").unwrap();
+        assert_eq!(january.color, mix(HEAT_COLD, HEAT_HOT, 1.0 / 3.0));
+        assert!(chart.labels.iter().any(|label| label.text == "Rent" && label.anchor == Anchor::End));
+    }
+
+    #[test]
+    fn test_year_comparison_chart() {
+        let chart = year_comparison_chart(&series(), 2024);
+        // only May has data in 2023, nothing in May 2024
+        assert!(chart.rects.iter().any(|rect| rect.label == "2023, nothing to compare"));
+
+        let mut series = series();
+        series.insert("Groceries", YearMonth::new(2023, 1), 50.0);
+        let chart = year_comparison_chart(&series, 2024);
+        assert!(chart.rects.iter().any(|rect| rect.label == "Groceries in 2023: 50.00"));
+        assert!(chart.rects.iter().any(|rect| rect.label == "Groceries in 2024: 100.00")); // January only
+        assert!(chart.rects.iter().any(|rect| rect.label == "Rent in 2024: 500.00"));
+        assert!(chart.rects.iter().any(|rect| rect.label == "2024, Jan - Jan"));
+        assert_eq!(ChartKind::from_name("years"), Some(ChartKind::YearComparison));
+    }
+
+    #[test]
+    fn test_category_groups() {
+        let mut series = ExpenseSeries::default();
+        for index in 0..9 {
+            series.insert(&format!("Category {}", index), YearMonth::new(2024, 1), 100.0 - index as f64);
+        }
+        let groups = category_groups(&series, 2024);
+        assert_eq!(groups.len(), CHART_CATEGORIES + 1);
+        assert_eq!(groups[CHART_CATEGORIES].name, "Other");
+        assert_eq!(groups[CHART_CATEGORIES].value(&series, YearMonth::new(2024, 1)), 93.0 + 92.0);
+    }
+
+    #[test]
+    fn test_nice_max() {
+        assert_eq!(nice_max(600.0), 1000.0);
+        assert_eq!(nice_max(1800.0), 2000.0);
+        assert_eq!(nice_max(2100.0), 2500.0);
+        assert_eq!(nice_max(0.0), 1.0);
+    }
+}
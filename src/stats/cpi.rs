@@ -0,0 +1,199 @@
+// consumer price index table for amounts in real terms, a CSV kept next to the spreadsheet:
+//  month,index
+//  2024-01,118.3
+//  2024-02,118.9
+// comma, semicolon or tab separated, a header line is skipped. every amount is multiplied by
+// index of the base month / index of its own month, so all years are in the same prices.
+// months after the last index use the last one (the index comes out late), months before
+// the first one are left as booked
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::utils::config::AppConfig;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CpiTable {
+    index: BTreeMap<YearMonth, f64>,
+}
+
+impl CpiTable {
+    pub fn parse(text: &str) -> Result<CpiTable, String> {
+        let mut table = CpiTable::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let Some((month, index)) = line.split_once([',', ';', '\t']) else {
+                return Err(format!("Line {}: expected month and index, got \"{}\"", number + 1, line));
+            };
+            let (month, index) = (YearMonth::parse(month), index.trim().parse::<f64>());
+            match (month, index) {
+                (Some(month), Ok(index)) if index > 0.0 => {
+                    table.index.insert(month, index);
+                }
+                _ if number == 0 => continue, // header
+                _ => return Err(format!("Line {}: invalid month or index \"{}\"", number + 1, line)),
+            }
+        }
+        if table.index.is_empty() {
+            return Err("No index values in the CPI table".to_string());
+        }
+        Ok(table)
+    }
+
+    pub fn latest(&self) -> Option<YearMonth> {
+        self.index.keys().next_back().copied()
+    }
+
+    // index of the month, or of the latest month before it, None before the table starts
+    pub fn index_for(&self, month: YearMonth) -> Option<f64> {
+        self.index.range(..=month).next_back().map(|(_, index)| *index)
+    }
+}
+
+pub fn load_cpi_table(path: &Path) -> Result<CpiTable, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Cannot read CPI table {}: {}", path.display(), e))?;
+    CpiTable::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+// how a series was restated, for labels and reports
+#[derive(Clone, Debug, PartialEq)]
+pub struct RealTerms {
+    pub base: YearMonth,
+    pub base_index: f64,
+    pub source: String, // file the index came from
+    pub unadjusted: Vec<YearMonth>, // months before the table, left as booked
+}
+
+impl RealTerms {
+    //  in prices of May 2024
+    pub fn label(&self) -> String {
+        format!("in prices of {}", self.base.label())
+    }
+
+    // lines explaining the adjustment
+    pub fn explanation(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "Amounts are {}: every month is multiplied by {:.2} (consumer price index of {}) divided by the index of its own month, from {}.",
+                self.label(),
+                self.base_index,
+                self.base.label(),
+                self.source
+            ),
+            "Months after the last index use the last one.".to_string(),
+        ];
+        if !self.unadjusted.is_empty() {
+            let months: Vec<String> = self.unadjusted.iter().map(YearMonth::label).collect();
+            lines.push(format!("Before the table starts, left as booked: {}.", months.join(", ")));
+        }
+        lines
+    }
+}
+
+// the series in prices of the base month
+pub fn restate(series: &ExpenseSeries, table: &CpiTable, base: YearMonth, source: &str) -> Result<(ExpenseSeries, RealTerms), String> {
+    let base_index = table.index_for(base).ok_or(format!("No consumer price index for {}", base.label()))?;
+    let mut restated = ExpenseSeries::default();
+    let mut unadjusted = Vec::new();
+    for category in series.categories() {
+        for (month, value) in series.category(category).into_iter().flatten() {
+            match table.index_for(*month) {
+                Some(index) => restated.insert(category, *month, value * base_index / index),
+                None => {
+                    restated.insert(category, *month, *value);
+                    unadjusted.push(*month);
+                }
+            }
+        }
+    }
+    for (month, value) in series.income() {
+        match table.index_for(*month) {
+            Some(index) => restated.insert_income(*month, value * base_index / index),
+            None => {
+                restated.insert_income(*month, *value);
+                unadjusted.push(*month);
+            }
+        }
+    }
+    unadjusted.sort();
+    unadjusted.dedup();
+    Ok((restated, RealTerms { base, base_index, source: source.to_string(), unadjusted }))
+}
+
+// restates with the table and base month from config, the table path is relative to the destination folder
+pub fn restate_configured(series: &ExpenseSeries, destination_path: &str, config: &AppConfig) -> Result<(ExpenseSeries, RealTerms), String> {
+    let table_path = config.cpi_table.as_ref().ok_or("No CPI table set in config (cpi_table)")?;
+    let table = load_cpi_table(&Path::new(destination_path).join(table_path))?;
+    let base = match &config.cpi_base_month {
+        Some(base) => YearMonth::parse(base).ok_or(format!("Invalid CPI base month \"{}\", expected 2024-05", base))?,
+        None => table.latest().ok_or("No index values in the CPI table")?,
+    };
+    restate(series, &table, base, table_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::config::{save_config, load_config};
+
+    const TABLE: &str = "month;index\n2023-01;100\n2023-06;110\n\n2024-01;125\n";
+
+    #[test]
+    fn test_parse_cpi_table() {
+        let table = CpiTable::parse(TABLE).unwrap();
+        assert_eq!(table.latest(), Some(YearMonth::new(2024, 1)));
+        assert_eq!(table.index_for(YearMonth::new(2023, 1)), Some(100.0));
+        assert_eq!(table.index_for(YearMonth::new(2023, 9)), Some(110.0)); // latest before
+        assert_eq!(table.index_for(YearMonth::new(2022, 12)), None);
+
+        assert!(CpiTable::parse("2024-01,100\n2024-02,abc").unwrap_err().starts_with("Line 2"));
+        assert!(CpiTable::parse("month,index\n").is_err());
+    }
+
+    #[test]
+    fn test_restate() {
+        let table = CpiTable::parse(TABLE).unwrap();
+        let mut series = ExpenseSeries::default();
+        series.insert("Groceries", YearMonth::new(2022, 12), 90.0);
+        series.insert("Groceries", YearMonth::new(2023, 1), 100.0);
+        series.insert("Groceries", YearMonth::new(2024, 2), 130.0);
+        series.insert_income(YearMonth::new(2023, 1), 1000.0);
+
+        let (restated, real_terms) = restate(&series, &table, YearMonth::new(2024, 1), "cpi.csv").unwrap();
+        assert_eq!(restated.income().get(&YearMonth::new(2023, 1)), Some(&1250.0));
+        assert_eq!(restated.value("Groceries", YearMonth::new(2023, 1)), Some(125.0));
+        assert_eq!(restated.value("Groceries", YearMonth::new(2024, 2)), Some(130.0));
+        assert_eq!(restated.value("Groceries", YearMonth::new(2022, 12)), Some(90.0));
+        assert_eq!(real_terms.unadjusted, vec![YearMonth::new(2022, 12)]);
+        assert_eq!(real_terms.label(), "in prices of January 2024");
+        assert_eq!(real_terms.explanation().len(), 3);
+
+        assert!(restate(&series, &table, YearMonth::new(2020, 1), "cpi.csv").is_err());
+    }
+
+    #[test]
+    fn test_restate_configured() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let mut series = ExpenseSeries::default();
+        series.insert("Groceries", YearMonth::new(2023, 1), 100.0);
+
+        assert!(restate_configured(&series, destination, &load_config(destination)).is_err());
+
+        fs::write(temp_dir.path().join("cpi.csv"), TABLE).unwrap();
+        let config = AppConfig { cpi_table: Some("cpi.csv".to_string()), ..AppConfig::default() };
+        save_config(destination, &config).unwrap();
+        let (restated, real_terms) = restate_configured(&series, destination, &load_config(destination)).unwrap();
+        assert_eq!(real_terms.base, YearMonth::new(2024, 1)); // latest of the table
+        assert_eq!(restated.value("Groceries", YearMonth::new(2023, 1)), Some(125.0));
+
+        let config = AppConfig { cpi_base_month: Some("2023-06".to_string()), ..config };
+        let (restated, _) = restate_configured(&series, destination, &config).unwrap();
+        assert_eq!(restated.value("Groceries", YearMonth::new(2023, 1)), Some(110.0));
+    }
+}
@@ -0,0 +1,176 @@
+// everything the statistics view shows for one year, put together away from the UI thread
+// (it only holds plain data, so it can be sent back from a worker thread)
+
+use crate::file_handlers::xls_handlers::xls_current_workbook_path;
+use crate::stats::cashflow::{monthly_cash_flow, CashFlow};
+use crate::stats::charts::{build_charts, Chart};
+use crate::stats::cpi::{restate_configured, RealTerms};
+use crate::stats::forecast::{forecast, Forecast};
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::stats::summary::{category_stats, outliers, year_over_year, CategoryStats, Delta, Outlier};
+use crate::utils::config::load_config;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DashboardRow {
+    pub stats: CategoryStats,
+    pub year_change: Option<Delta>, // same months of the previous year
+    pub outlier: bool, // at least one unusual month in the year
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dashboard {
+    pub years: Vec<i64>, // every year with data, oldest first
+    pub year: i64,
+    pub rows: Vec<DashboardRow>, // biggest spend first
+    pub total: f64,
+    pub total_change: Option<Delta>,
+    pub outliers: Vec<Outlier>,
+    pub charts: Vec<Chart>, // in ChartKind::ALL order
+    pub forecast: Option<Forecast>, // rest of the year, only for the latest year
+    pub real_terms: Option<RealTerms>, // set when every amount is restated to prices of one month
+    pub cash_flow: Option<CashFlow>, // None when the year has no income
+    pub monthly_cash_flow: Vec<(YearMonth, CashFlow)>, // months of the year, empty without income
+}
+
+impl Dashboard {
+    pub fn top_categories(&self, count: usize) -> &[DashboardRow] {
+        &self.rows[..count.min(self.rows.len())]
+    }
+}
+
+// year defaults to the latest one, None when the workbook has no data at all
+pub fn build_dashboard(series: &ExpenseSeries, year: Option<i64>) -> Option<Dashboard> {
+    let years = series.years();
+    let year = year.filter(|year| years.contains(year)).or(years.last().copied())?;
+
+    let outliers = outliers(series, Some(year));
+    let year_change = |category: Option<&str>| {
+        year_over_year(series, category)
+            .into_iter()
+            .find(|(delta_year, _)| *delta_year == year)
+            .map(|(_, delta)| delta)
+    };
+
+    let rows: Vec<DashboardRow> = category_stats(series, Some(year))
+        .into_iter()
+        .map(|stats| DashboardRow {
+            year_change: year_change(Some(&stats.category)),
+            outlier: outliers.iter().any(|outlier| outlier.category == stats.category),
+            stats,
+        })
+        .collect();
+
+    let monthly_cash_flow: Vec<(YearMonth, CashFlow)> = monthly_cash_flow(series)
+        .into_iter()
+        .filter(|(month, _)| month.year == year)
+        .collect();
+    let cash_flow = monthly_cash_flow
+        .iter()
+        .any(|(_, flow)| flow.has_income())
+        .then(|| CashFlow {
+            income: monthly_cash_flow.iter().map(|(_, flow)| flow.income).sum(),
+            expenses: monthly_cash_flow.iter().map(|(_, flow)| flow.expenses).sum(),
+        });
+
+    Some(Dashboard {
+        total: rows.iter().map(|row| row.stats.total).sum(),
+        total_change: year_change(None),
+        years,
+        year,
+        rows,
+        outliers,
+        charts: build_charts(series, year),
+        forecast: forecast(series).filter(|forecast| forecast.through.year == year),
+        real_terms: None,
+        monthly_cash_flow: if cash_flow.is_some() { monthly_cash_flow } else { Vec::new() },
+        cash_flow,
+    })
+}
+
+// reads the workbook of the destination folder, in real terms with the CPI table from config
+pub fn load_dashboard(destination_path: &str, year: Option<i64>, real_terms: bool) -> Result<Dashboard, String> {
+    let (series, real_terms) = load_series(destination_path, real_terms)?;
+    let mut dashboard = build_dashboard(&series, year).ok_or("No expenses in the spreadsheet yet".to_string())?;
+    dashboard.real_terms = real_terms;
+    Ok(dashboard)
+}
+
+// series of the workbook in the destination folder, as booked or in real terms
+pub fn load_series(destination_path: &str, real_terms: bool) -> Result<(ExpenseSeries, Option<RealTerms>), String> {
+    let workbook_path = xls_current_workbook_path(destination_path).ok_or("No spreadsheet found in destination folder")?;
+    let series = ExpenseSeries::from_workbook(workbook_path.to_str().ok_or("Invalid spreadsheet path")?)
+        .ok_or("Cannot read the spreadsheet")?;
+    if !real_terms {
+        return Ok((series, None));
+    }
+    let (series, real_terms) = restate_configured(&series, destination_path, &load_config(destination_path))?;
+    Ok((series, Some(real_terms)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::charts::ChartKind;
+    use crate::stats::series::YearMonth;
+
+    fn series() -> ExpenseSeries {
+        let mut series = ExpenseSeries::default();
+        for month in 1..=6 {
+            series.insert("Groceries", YearMonth::new(2023, month), 100.0);
+            series.insert("Rent", YearMonth::new(2023, month), 500.0);
+        }
+        for month in 1..=5 {
+            series.insert("Groceries", YearMonth::new(2024, month), 100.0);
+            series.insert("Rent", YearMonth::new(2024, month), 550.0);
+        }
+        series.insert("Groceries", YearMonth::new(2024, 6), 900.0);
+        series.insert("Rent", YearMonth::new(2024, 6), 550.0);
+        series
+    }
+
+    #[test]
+    fn test_build_dashboard() {
+        let dashboard = build_dashboard(&series(), None).unwrap();
+        assert_eq!(dashboard.years, vec![2023, 2024]);
+        assert_eq!(dashboard.year, 2024); // latest by default
+
+        assert_eq!(dashboard.top_categories(1)[0].stats.category, "Rent");
+        assert_eq!(dashboard.top_categories(5).len(), 2);
+        let rent = &dashboard.rows[0];
+        assert_eq!(rent.year_change.unwrap().percent(), Some(10.0));
+        assert!(!rent.outlier);
+        assert!(dashboard.rows[1].outlier); // June groceries
+        assert_eq!(dashboard.outliers.len(), 1);
+        assert_eq!(dashboard.total, 3300.0 + 1400.0);
+        assert_eq!(dashboard.charts.len(), ChartKind::ALL.len());
+        assert_eq!(dashboard.forecast.unwrap().total.spent, 3300.0 + 1400.0);
+        assert_eq!(dashboard.cash_flow, None);
+        assert!(dashboard.monthly_cash_flow.is_empty());
+    }
+
+    #[test]
+    fn test_build_dashboard_with_income() {
+        let mut series = series();
+        series.insert_income(YearMonth::new(2024, 1), 1000.0);
+        series.insert_income(YearMonth::new(2023, 1), 5000.0);
+        let dashboard = build_dashboard(&series, None).unwrap();
+        let cash_flow = dashboard.cash_flow.unwrap();
+        assert_eq!(cash_flow.income, 1000.0);
+        assert_eq!(cash_flow.expenses, dashboard.total);
+        assert_eq!(dashboard.monthly_cash_flow.len(), 6);
+        assert_eq!(dashboard.monthly_cash_flow[0].1.net(), 350.0);
+    }
+
+    #[test]
+    fn test_build_dashboard_for_a_year() {
+        let dashboard = build_dashboard(&series(), Some(2023)).unwrap();
+        assert_eq!(dashboard.year, 2023);
+        assert_eq!(dashboard.rows[0].year_change, None); // nothing before 2023
+        assert!(dashboard.outliers.is_empty());
+        assert_eq!(dashboard.forecast, None); // only the latest year has a rest to forecast
+
+        // unknown year falls back to the latest
+        assert_eq!(build_dashboard(&series(), Some(1999)).unwrap().year, 2024);
+        assert_eq!(build_dashboard(&ExpenseSeries::default(), None), None);
+    }
+}
@@ -0,0 +1,302 @@
+// what the rest of the latest year is likely to cost, per category and in total
+// three simple models, each easy to check by hand:
+//  trailing average  mean of the last 3 months, for every month left
+//  seasonal naive    the same month of the year before
+//  linear trend      straight line fitted through the last 12 months
+// every model is backtested on the past years: cut at the same month, forecast the rest of that
+// year and compare with what was really spent. the model that was off the least is the one used
+
+use std::collections::BTreeMap;
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+
+const TRAILING_MONTHS: usize = 3;
+const TREND_MONTHS: usize = 12;
+const MIN_TREND_MONTHS: usize = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForecastModel {
+    TrailingAverage,
+    SeasonalNaive,
+    LinearTrend,
+}
+
+impl ForecastModel {
+    pub const ALL: [ForecastModel; 3] = [ForecastModel::TrailingAverage, ForecastModel::SeasonalNaive, ForecastModel::LinearTrend];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ForecastModel::TrailingAverage => "trailing average",
+            ForecastModel::SeasonalNaive => "same month last year",
+            ForecastModel::LinearTrend => "linear trend",
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            ForecastModel::TrailingAverage => "mean of the last 3 months, for every month left",
+            ForecastModel::SeasonalNaive => "every month left costs what it did the year before",
+            ForecastModel::LinearTrend => "straight line through the last 12 months, never below 0",
+        }
+    }
+
+    // expected value of every target month, history holds months up to the cut only.
+    // None when the history doesn't have what the model needs
+    fn predict(&self, history: &BTreeMap<YearMonth, f64>, targets: &[YearMonth]) -> Option<Vec<f64>> {
+        match self {
+            ForecastModel::TrailingAverage => {
+                let last: Vec<f64> = history.values().rev().take(TRAILING_MONTHS).copied().collect();
+                if last.is_empty() {
+                    return None;
+                }
+                let mean = last.iter().fold(0.0, |total, value| total + value) / last.len() as f64;
+                Some(vec![mean; targets.len()])
+            }
+            ForecastModel::SeasonalNaive => targets
+                .iter()
+                .map(|month| history.get(&YearMonth::new(month.year - 1, month.month)).copied())
+                .collect(),
+            ForecastModel::LinearTrend => {
+                let points: Vec<(f64, f64)> = history
+                    .iter()
+                    .rev()
+                    .take(TREND_MONTHS)
+                    .map(|(month, value)| (month_index(*month), *value))
+                    .collect();
+                if points.len() < MIN_TREND_MONTHS {
+                    return None;
+                }
+                let (slope, intercept) = least_squares(&points);
+                Some(targets.iter().map(|month| (intercept + slope * month_index(*month)).max(0.0)).collect())
+            }
+        }
+    }
+}
+
+// one model for one category (or the total)
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModelForecast {
+    pub model: ForecastModel,
+    pub remaining: Option<f64>, // expected for the months left
+    pub error: Option<f64>, // mean absolute error of the remaining total on past years
+    pub backtest_years: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SeriesForecast {
+    pub name: String, // category, or "Total"
+    pub spent: f64, // in the year so far
+    pub models: Vec<ModelForecast>, // in ForecastModel::ALL order
+    pub chosen: Option<ForecastModel>, // smallest backtest error, trailing average without a backtest
+}
+
+impl SeriesForecast {
+    pub fn chosen_forecast(&self) -> Option<&ModelForecast> {
+        self.models.iter().find(|forecast| Some(forecast.model) == self.chosen)
+    }
+
+    pub fn expected_remaining(&self) -> Option<f64> {
+        self.chosen_forecast()?.remaining
+    }
+
+    // spent so far and the expected rest
+    pub fn expected_year(&self) -> Option<f64> {
+        self.expected_remaining().map(|remaining| self.spent + remaining)
+    }
+
+    //  Groceries: 1200.00 spent, 1650.00 expected for the rest of the year (same month last year, off by 120.00 on 2 past years)
+    pub fn describe(&self) -> String {
+        let Some(forecast) = self.chosen_forecast() else {
+            return format!("{}: {:.2} spent, not enough history for a forecast", self.name, self.spent);
+        };
+        let backtest = match forecast.error {
+            Some(error) => format!(", off by {:.2} on {} past years", error, forecast.backtest_years),
+            None => ", no past years to check it on".to_string(),
+        };
+        format!(
+            "{}: {:.2} spent, {:.2} expected for the rest of the year ({}{})",
+            self.name,
+            self.spent,
+            forecast.remaining.unwrap_or_default(),
+            forecast.model.name(),
+            backtest
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Forecast {
+    pub through: YearMonth, // last month with data, the forecast is for the months after it
+    pub categories: Vec<SeriesForecast>, // categories with spend in the year, biggest expected year first
+    pub total: SeriesForecast,
+}
+
+// rest of the latest year, None without data or when December is already filled in
+pub fn forecast(series: &ExpenseSeries) -> Option<Forecast> {
+    let through = *series.months().last()?;
+    if through.month == 12 {
+        return None;
+    }
+
+    let mut categories: Vec<SeriesForecast> = series
+        .categories()
+        .iter()
+        .filter_map(|category| {
+            let history = series.category(category)?;
+            history.keys().any(|month| month.year == through.year).then(|| series_forecast(category, history, through))
+        })
+        .collect();
+    categories.sort_by(|a, b| b.expected_year().unwrap_or(b.spent).total_cmp(&a.expected_year().unwrap_or(a.spent)));
+
+    Some(Forecast {
+        through,
+        categories,
+        total: series_forecast("Total", &series.monthly_totals(), through),
+    })
+}
+
+fn series_forecast(name: &str, history: &BTreeMap<YearMonth, f64>, through: YearMonth) -> SeriesForecast {
+    let spent = history
+        .range(YearMonth::new(through.year, 1)..=through)
+        .fold(0.0, |total, (_, value)| total + value);
+
+    let models: Vec<ModelForecast> = ForecastModel::ALL
+        .iter()
+        .map(|model| {
+            let (error, backtest_years) = backtest(*model, history, through);
+            ModelForecast { model: *model, remaining: remaining(*model, history, through), error, backtest_years }
+        })
+        .collect();
+
+    let chosen = models
+        .iter()
+        .filter(|forecast| forecast.remaining.is_some() && forecast.error.is_some())
+        .min_by(|a, b| a.error.unwrap_or_default().total_cmp(&b.error.unwrap_or_default()))
+        .or_else(|| models.iter().find(|forecast| forecast.model == ForecastModel::TrailingAverage && forecast.remaining.is_some()))
+        .map(|forecast| forecast.model);
+
+    SeriesForecast { name: name.to_string(), spent, models, chosen }
+}
+
+// expected sum of the months after the cut, up to December
+fn remaining(model: ForecastModel, history: &BTreeMap<YearMonth, f64>, through: YearMonth) -> Option<f64> {
+    let targets: Vec<YearMonth> = (through.month + 1..=12).map(|month| YearMonth::new(through.year, month)).collect();
+    let known: BTreeMap<YearMonth, f64> = history.range(..=through).map(|(month, value)| (*month, *value)).collect();
+    model
+        .predict(&known, &targets)
+        .map(|values| values.iter().fold(0.0, |total, value| total + value))
+}
+
+// every earlier year with all of its remaining months known is cut at the same month and
+// forecast, returns the mean absolute error of the remaining total and how many years were checked
+fn backtest(model: ForecastModel, history: &BTreeMap<YearMonth, f64>, through: YearMonth) -> (Option<f64>, usize) {
+    let mut years: Vec<i64> = history.keys().map(|month| month.year).filter(|year| *year < through.year).collect();
+    years.dedup();
+
+    let errors: Vec<f64> = years
+        .into_iter()
+        .filter_map(|year| {
+            let cut = YearMonth::new(year, through.month);
+            let actual: Vec<f64> = (through.month + 1..=12)
+                .map(|month| history.get(&YearMonth::new(year, month)).copied())
+                .collect::<Option<Vec<f64>>>()?;
+            let expected = remaining(model, history, cut)?;
+            Some((expected - actual.iter().fold(0.0, |total, value| total + value)).abs())
+        })
+        .collect();
+
+    if errors.is_empty() {
+        return (None, 0);
+    }
+    (Some(errors.iter().fold(0.0, |total, value| total + value) / errors.len() as f64), errors.len())
+}
+
+fn month_index(month: YearMonth) -> f64 {
+    (month.year * 12 + month.month as i64 - 1) as f64
+}
+
+// slope and intercept of the line closest to the points
+fn least_squares(points: &[(f64, f64)]) -> (f64, f64) {
+    let count = points.len() as f64;
+    let mean_x = points.iter().fold(0.0, |total, (x, _)| total + x) / count;
+    let mean_y = points.iter().fold(0.0, |total, (_, y)| total + y) / count;
+    let covariance = points.iter().fold(0.0, |total, (x, y)| total + (x - mean_x) * (y - mean_y));
+    let variance = points.iter().fold(0.0, |total, (x, _)| total + (x - mean_x).powi(2));
+    let slope = if variance == 0.0 { 0.0 } else { covariance / variance };
+    (slope, mean_y - slope * mean_x)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(values: &[(i64, u32, f64)]) -> BTreeMap<YearMonth, f64> {
+        values.iter().map(|(year, month, value)| (YearMonth::new(*year, *month), *value)).collect()
+    }
+
+    #[test]
+    fn test_models() {
+        let known = history(&[(2023, 11, 10.0), (2023, 12, 20.0), (2024, 1, 30.0), (2024, 2, 40.0)]);
+        let targets = [YearMonth::new(2024, 3), YearMonth::new(2024, 12)];
+
+        assert_eq!(ForecastModel::TrailingAverage.predict(&known, &targets), Some(vec![30.0, 30.0]));
+        // nothing for March 2023
+        assert_eq!(ForecastModel::SeasonalNaive.predict(&known, &targets), None);
+        assert_eq!(ForecastModel::SeasonalNaive.predict(&known, &targets[1..]), Some(vec![20.0]));
+        let trend = ForecastModel::LinearTrend.predict(&known, &targets).unwrap();
+        assert!((trend[0] - 50.0).abs() < 1e-9 && (trend[1] - 140.0).abs() < 1e-9);
+
+        // falling line stops at 0
+        let falling = history(&[(2024, 1, 30.0), (2024, 2, 20.0), (2024, 3, 10.0)]);
+        assert_eq!(ForecastModel::LinearTrend.predict(&falling, &[YearMonth::new(2024, 6)]), Some(vec![0.0]));
+        assert_eq!(ForecastModel::LinearTrend.predict(&falling.clone().into_iter().take(2).collect::<BTreeMap<_, _>>(), &targets), None);
+    }
+
+    #[test]
+    fn test_forecast_picks_the_best_backtested_model() {
+        // December costs twice as much every year, only the seasonal model knows
+        let mut series = ExpenseSeries::default();
+        for year in 2022..=2023 {
+            for month in 1..=12 {
+                series.insert("Gifts", YearMonth::new(year, month), if month == 12 { 200.0 } else { 100.0 });
+            }
+        }
+        for month in 1..=10 {
+            series.insert("Gifts", YearMonth::new(2024, month), 100.0);
+        }
+
+        let forecast = forecast(&series).unwrap();
+        assert_eq!(forecast.through, YearMonth::new(2024, 10));
+        let gifts = &forecast.categories[0];
+        assert_eq!(gifts.spent, 1000.0);
+        assert_eq!(gifts.chosen, Some(ForecastModel::SeasonalNaive));
+        assert_eq!(gifts.expected_remaining(), Some(300.0));
+        assert_eq!(gifts.expected_year(), Some(1300.0));
+
+        let seasonal = gifts.chosen_forecast().unwrap();
+        assert_eq!((seasonal.error, seasonal.backtest_years), (Some(0.0), 1)); // 2022 has no year before it
+        let trailing = &gifts.models[0];
+        assert_eq!((trailing.remaining, trailing.error), (Some(200.0), Some(100.0)));
+        assert_eq!(
+            gifts.describe(),
+            "Gifts: 1000.00 spent, 300.00 expected for the rest of the year (same month last year, off by 0.00 on 1 past years)"
+        );
+        assert_eq!(forecast.total.expected_remaining(), Some(300.0));
+    }
+
+    #[test]
+    fn test_forecast_without_history() {
+        let mut series = ExpenseSeries::default();
+        assert_eq!(forecast(&series), None);
+
+        series.insert("Rent", YearMonth::new(2024, 3), 500.0);
+        let rent = &forecast(&series).unwrap().categories[0];
+        assert_eq!(rent.chosen, Some(ForecastModel::TrailingAverage)); // nothing to backtest
+        assert_eq!(rent.expected_remaining(), Some(4500.0));
+        assert!(rent.describe().ends_with("(trailing average, no past years to check it on)"));
+
+        // a full year leaves nothing to forecast
+        series.insert("Rent", YearMonth::new(2024, 12), 500.0);
+        assert_eq!(forecast(&series), None);
+    }
+}
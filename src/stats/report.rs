@@ -0,0 +1,267 @@
+// statistics of one year as a markdown file, for reading outside the app or pasting into notes:
+// totals, the per category table, unusual months and the forecast with how each model did
+// on past years. a report in real terms says which prices and index it was restated with.
+// with income in the workbook there's a cash flow table too
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::stats::cashflow::CashFlow;
+use crate::stats::dashboard::{load_dashboard, Dashboard};
+use crate::stats::forecast::{Forecast, ForecastModel, SeriesForecast};
+use crate::stats::summary::Delta;
+
+pub const REPORTS_FOLDER: &str = "reports"; // in the destination folder
+
+pub fn report_markdown(dashboard: &Dashboard) -> String {
+    let title = match &dashboard.real_terms {
+        Some(real_terms) => format!("# Expenses {}, {}", dashboard.year, real_terms.label()),
+        None => format!("# Expenses {}", dashboard.year),
+    };
+    let mut lines = vec![title, String::new()];
+
+    let mut total = format!("Spent **{:.2}** in {}", dashboard.total, dashboard.year);
+    if let Some(delta) = dashboard.total_change {
+        total.push_str(&format!(", {} vs the same months of {}", change(delta), dashboard.year - 1));
+    }
+    lines.push(total);
+
+    if let Some(cash_flow) = dashboard.cash_flow {
+        lines.extend([
+            String::new(),
+            "## Cash flow".to_string(),
+            String::new(),
+            "| Month | Income | Expenses | Net | Savings rate |".to_string(),
+            "|---|---:|---:|---:|---:|".to_string(),
+        ]);
+        for (month, flow) in &dashboard.monthly_cash_flow {
+            lines.push(cash_flow_row(&month.label(), flow));
+        }
+        lines.push(cash_flow_row(&format!("**{}**", dashboard.year), &cash_flow));
+    }
+
+    if let Some(real_terms) = &dashboard.real_terms {
+        lines.extend([String::new(), "## Prices".to_string(), String::new()]);
+        lines.extend(real_terms.explanation());
+    }
+
+    lines.extend([
+        String::new(),
+        "## Categories".to_string(),
+        String::new(),
+        "| Category | Total | Mean | Median | Std dev | Min | Max | Share | vs last year |".to_string(),
+        "|---|---:|---:|---:|---:|---:|---:|---:|---:|".to_string(),
+    ]);
+    for row in &dashboard.rows {
+        let stats = &row.stats;
+        lines.push(format!(
+            "| {} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.2} | {:.0}% | {} |",
+            cell(&stats.category),
+            stats.total,
+            stats.mean,
+            stats.median,
+            stats.std_dev,
+            stats.min,
+            stats.max,
+            stats.share * 100.0,
+            row.year_change.map(change).unwrap_or_default()
+        ));
+    }
+
+    if !dashboard.outliers.is_empty() {
+        lines.extend([String::new(), "## Unusual months".to_string(), String::new()]);
+        lines.extend(dashboard.outliers.iter().map(|outlier| format!("- {}", outlier.describe())));
+    }
+
+    if let Some(forecast) = &dashboard.forecast {
+        lines.extend(forecast_section(forecast));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn forecast_section(forecast: &Forecast) -> Vec<String> {
+    let mut lines = vec![
+        String::new(),
+        format!("## Forecast for the rest of {}", forecast.through.year),
+        String::new(),
+        format!("Data up to {}. Every model was checked on the past years, cut at the same month:", forecast.through.label()),
+        String::new(),
+    ];
+    lines.extend(ForecastModel::ALL.iter().map(|model| format!("- *{}*: {}", model.name(), model.explanation())));
+    lines.extend([
+        String::new(),
+        "The model that was off the least is used, the trailing average when there is no past year to check on.".to_string(),
+        String::new(),
+        "| | Spent | Expected rest | Expected year | Model | Error on past years |".to_string(),
+        "|---|---:|---:|---:|---|---:|".to_string(),
+    ]);
+    for series in std::iter::once(&forecast.total).chain(&forecast.categories) {
+        lines.push(forecast_row(series));
+    }
+
+    lines.extend([
+        String::new(),
+        "### All models".to_string(),
+        String::new(),
+        format!("| | {} |", ForecastModel::ALL.map(|model| model.name()).join(" | ")),
+        format!("|---|{}", "---:|".repeat(ForecastModel::ALL.len())),
+    ]);
+    for series in std::iter::once(&forecast.total).chain(&forecast.categories) {
+        let models: Vec<String> = series
+            .models
+            .iter()
+            .map(|model| match (model.remaining, model.error) {
+                (Some(remaining), Some(error)) => format!("{:.2} (±{:.2})", remaining, error),
+                (Some(remaining), None) => format!("{:.2}", remaining),
+                (None, _) => "-".to_string(),
+            })
+            .collect();
+        lines.push(format!("| {} | {} |", cell(&series.name), models.join(" | ")));
+    }
+    lines
+}
+
+fn forecast_row(series: &SeriesForecast) -> String {
+    let Some(chosen) = series.chosen_forecast() else {
+        return format!("| {} | {:.2} | - | - | not enough history | - |", cell(&series.name), series.spent);
+    };
+    format!(
+        "| {} | {:.2} | {:.2} | {:.2} | {} | {} |",
+        cell(&series.name),
+        series.spent,
+        chosen.remaining.unwrap_or_default(),
+        series.expected_year().unwrap_or_default(),
+        chosen.model.name(),
+        chosen.error.map(|error| format!("{:.2}", error)).unwrap_or_else(|| "-".to_string())
+    )
+}
+
+fn cash_flow_row(label: &str, flow: &CashFlow) -> String {
+    format!(
+        "| {} | {:.2} | {:.2} | {:+.2} | {} |",
+        label,
+        flow.income,
+        flow.expenses,
+        flow.net(),
+        flow.savings_rate().map(|rate| format!("{:.0}%", rate * 100.0)).unwrap_or_else(|| "-".to_string())
+    )
+}
+
+//  +12.5%, "new" when nothing was spent the year before
+fn change(delta: Delta) -> String {
+    match delta.percent() {
+        Some(percent) => format!("{:+.1}%", percent),
+        None => "new".to_string(),
+    }
+}
+
+// category names go into table cells
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+//  <destination>/reports/report_2024.md, report_2024_real.md in real terms
+pub fn default_report_path(destination_path: &str, year: i64, real_terms: bool) -> PathBuf {
+    let suffix = if real_terms { "_real" } else { "" };
+    Path::new(destination_path).join(REPORTS_FOLDER).join(format!("report_{}{}.md", year, suffix))
+}
+
+// report of the workbook in the destination folder, year defaults to the latest one.
+// output defaults to the reports folder, returns where the report was written
+pub fn export_report(destination_path: &str, year: Option<i64>, output: Option<&Path>, real_terms: bool) -> Result<PathBuf, String> {
+    let dashboard = load_dashboard(destination_path, year, real_terms)?;
+    if year.is_some_and(|year| year != dashboard.year) {
+        return Err(format!("No expenses in {}", year.unwrap_or_default()));
+    }
+    let output = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| default_report_path(destination_path, dashboard.year, real_terms));
+    write_report(&dashboard, &output)?;
+    Ok(output)
+}
+
+pub fn write_report(dashboard: &Dashboard, output: &Path) -> Result<(), String> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Cannot create {}: {}", parent.display(), e))?;
+    }
+    fs::write(output, report_markdown(dashboard)).map_err(|e| format!("Cannot write {}: {}", output.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::file_handlers::xls_handlers::RES_WORKBOOK_NAME;
+    use crate::stats::dashboard::build_dashboard;
+    use crate::stats::series::{ExpenseSeries, YearMonth};
+    use crate::utils::config::{save_config, AppConfig};
+
+    #[test]
+    fn test_report_markdown() {
+        let mut series = ExpenseSeries::default();
+        for month in 1..=12 {
+            series.insert("Rent | flat", YearMonth::new(2023, month), 500.0);
+        }
+        for month in 1..=3 {
+            series.insert("Rent | flat", YearMonth::new(2024, month), 550.0);
+        }
+        let report = report_markdown(&build_dashboard(&series, None).unwrap());
+
+        assert!(report.starts_with("# Expenses 2024\n\nSpent **1650.00** in 2024, +10.0% vs the same months of 2023\n"));
+        assert!(report.contains("| Rent \\| flat | 1650.00 | 550.00 | 550.00 | 0.00 | 550.00 | 550.00 | 100% | +10.0% |"));
+        assert!(report.contains("## Forecast for the rest of 2024\n\nData up to March 2024."));
+        // the trailing average was off by 0 on 2023, same as last year
+        assert!(report.contains("| Total | 1650.00 | 4950.00 | 6600.00 | trailing average | 0.00 |"));
+        assert!(report.contains("| Rent \\| flat | 4950.00 (±0.00) | 4500.00 | "));        assert!(!report.contains("## Cash flow"));
+    }
+
+    #[test]
+    fn test_report_cash_flow() {
+        let mut series = ExpenseSeries::default();
+        series.insert("Rent", YearMonth::new(2024, 1), 500.0);
+        series.insert("Rent", YearMonth::new(2024, 2), 500.0);
+        series.insert_income(YearMonth::new(2024, 1), 2000.0);
+        let report = report_markdown(&build_dashboard(&series, None).unwrap());
+        assert!(report.contains(
+            "## Cash flow\n\n| Month | Income | Expenses | Net | Savings rate |\n|---|---:|---:|---:|---:|\n\
+             | January 2024 | 2000.00 | 500.00 | +1500.00 | 75% |\n\
+             | February 2024 | 0.00 | 500.00 | -500.00 | - |\n\
+             | **2024** | 2000.00 | 1000.00 | +1000.00 | 50% |\n"
+        ));
+    }
+
+    #[test]
+    fn test_export_report() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let path = export_report(destination, None, None, false).unwrap();
+        assert_eq!(path, temp_dir.path().join(REPORTS_FOLDER).join("report_2024.md"));
+        assert!(fs::read_to_string(&path).unwrap().contains("| Groceries | 100.00 |"));
+
+        let output = temp_dir.path().join("notes").join("expenses.md");
+        assert_eq!(export_report(destination, Some(2024), Some(&output), false).unwrap(), output);
+        assert!(export_report(destination, Some(1999), None, false).is_err());
+    }
+
+    #[test]
+    fn test_export_report_in_real_terms() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+        fs::write(temp_dir.path().join("cpi.csv"), "month,index\n2024-01,100\n2024-03,105\n").unwrap();
+        save_config(destination, &AppConfig { cpi_table: Some("cpi.csv".to_string()), ..AppConfig::default() }).unwrap();
+
+        let path = export_report(destination, None, None, true).unwrap();
+        assert_eq!(path, temp_dir.path().join(REPORTS_FOLDER).join("report_2024_real.md"));
+        let report = fs::read_to_string(&path).unwrap();
+        assert!(report.starts_with("# Expenses 2024, in prices of March 2024\n"));
+        assert!(report.contains(
+            "## Prices\n\nAmounts are in prices of March 2024: every month is multiplied by 105.00 (consumer price index of March 2024) \
+             divided by the index of its own month, from cpi.csv."
+        ));
+        assert!(report.contains("| Groceries | 105.00 |"));
+    }
+}
@@ -0,0 +1,181 @@
+// expenses of the whole workbook as one monthly time series per category
+// a month counts once any category of its year has a number in it, empty cells of such
+// a month are 0 (nothing spent). months without any number are not in the series at all,
+// neither are categories before the year they were added to the header.
+// income of the income area is kept apart, one total per month over all sources
+
+use std::collections::BTreeMap;
+
+use crate::file_handlers::xls_handlers::{xls_read_sheet, xls_year_blocks, SheetCells};
+use crate::utils::transaction_utils::month_name_from_number;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct YearMonth {
+    pub year: i64,
+    pub month: u32, // 1 - 12
+}
+
+impl YearMonth {
+    pub fn new(year: i64, month: u32) -> YearMonth {
+        YearMonth { year, month }
+    }
+
+    pub fn previous(&self) -> YearMonth {
+        match self.month {
+            1 => YearMonth::new(self.year - 1, 12),
+            month => YearMonth::new(self.year, month - 1),
+        }
+    }
+
+    //  "2024-05" or "2024-5", None for anything else
+    pub fn parse(text: &str) -> Option<YearMonth> {
+        let (year, month) = text.trim().split_once('-')?;
+        let month = month.parse::<u32>().ok().filter(|month| (1..=12).contains(month))?;
+        Some(YearMonth::new(year.parse().ok()?, month))
+    }
+
+    //  May 2024
+    pub fn label(&self) -> String {
+        format!("{} {}", month_name_from_number(self.month).unwrap_or("?"), self.year)
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExpenseSeries {
+    categories: Vec<String>, // order of first appearance in the workbook
+    values: BTreeMap<String, BTreeMap<YearMonth, f64>>,
+    income: BTreeMap<YearMonth, f64>,
+}
+
+impl ExpenseSeries {
+    pub fn from_workbook(path_to_workbook: &str) -> Option<ExpenseSeries> {
+        xls_read_sheet(path_to_workbook).map(|cells| ExpenseSeries::from_sheet(&cells))
+    }
+
+    pub fn from_sheet(cells: &SheetCells) -> ExpenseSeries {
+        let mut series = ExpenseSeries::default();
+        for block in xls_year_blocks(cells) {
+            for (index, row) in block.months.iter().enumerate() {
+                if row.iter().all(|value| value.is_none()) {
+                    continue; // nothing entered for the month (yet)
+                }
+                let month = YearMonth::new(block.year, index as u32 + 1);
+                for (category, value) in block.categories.iter().zip(row) {
+                    series.insert(category, month, value.unwrap_or(0.0));
+                }
+            }
+            for (index, row) in block.income.iter().enumerate() {
+                if row.iter().all(|value| value.is_none()) {
+                    continue;
+                }
+                series.insert_income(YearMonth::new(block.year, index as u32 + 1), row.iter().flatten().sum());
+            }
+        }
+        series
+    }
+
+    // same category twice in a year block is added up
+    pub fn insert(&mut self, category: &str, month: YearMonth, value: f64) {
+        if !self.values.contains_key(category) {
+            self.categories.push(category.to_string());
+        }
+        *self.values.entry(category.to_string()).or_default().entry(month).or_insert(0.0) += value;
+    }
+
+    pub fn insert_income(&mut self, month: YearMonth, value: f64) {
+        *self.income.entry(month).or_insert(0.0) += value;
+    }
+
+    // income of every month with any, over all sources
+    pub fn income(&self) -> &BTreeMap<YearMonth, f64> {
+        &self.income
+    }
+
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+
+    pub fn category(&self, category: &str) -> Option<&BTreeMap<YearMonth, f64>> {
+        self.values.get(category)
+    }
+
+    pub fn value(&self, category: &str, month: YearMonth) -> Option<f64> {
+        self.values.get(category)?.get(&month).copied()
+    }
+
+    // months with data, oldest first
+    pub fn months(&self) -> Vec<YearMonth> {
+        self.monthly_totals().into_keys().collect()
+    }
+
+    pub fn years(&self) -> Vec<i64> {
+        let mut years: Vec<i64> = self.months().iter().map(|month| month.year).collect();
+        years.dedup();
+        years
+    }
+
+    // everything spent in a month, over all categories
+    pub fn monthly_totals(&self) -> BTreeMap<YearMonth, f64> {
+        let mut totals = BTreeMap::new();
+        for values in self.values.values() {
+            for (month, value) in values {
+                *totals.entry(*month).or_insert(0.0) += value;
+            }
+        }
+        totals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::file_handlers::xls_handlers::CellValue;
+
+    #[test]
+    fn test_from_workbook() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let workbook_path = temp_dir.path().join("workbook.xlsx");
+        write_test_workbook(&workbook_path);
+
+        let series = ExpenseSeries::from_workbook(workbook_path.to_str().unwrap()).unwrap();
+        assert_eq!(series.categories(), ["Groceries", "Sweets"]);
+        assert_eq!(series.months(), vec![YearMonth::new(2024, 1)]);
+        assert_eq!(series.value("Groceries", YearMonth::new(2024, 1)), Some(100.0));
+        // empty cell in a month with data is nothing spent
+        assert_eq!(series.value("Sweets", YearMonth::new(2024, 1)), Some(0.0));
+        assert_eq!(series.value("Sweets", YearMonth::new(2024, 2)), None);
+    }
+
+    #[test]
+    fn test_from_sheet_with_two_years() {
+        let mut cells = SheetCells::new();
+        // 2023 at B2 with Groceries, 2024 at B17 with Groceries and Rent
+        cells.insert((1, 1), CellValue::Number(2023.0));
+        cells.insert((1, 2), CellValue::Text("Groceries".to_string()));
+        cells.insert((13, 2), CellValue::Number(80.0)); // December 2023
+        cells.insert((16, 1), CellValue::Number(2024.0));
+        cells.insert((16, 2), CellValue::Text("Groceries".to_string()));
+        cells.insert((16, 3), CellValue::Text("Rent".to_string()));
+        cells.insert((17, 2), CellValue::Number(90.0)); // January 2024
+        cells.insert((17, 3), CellValue::Number(500.0));
+
+        let series = ExpenseSeries::from_sheet(&cells);
+        assert_eq!(series.years(), vec![2023, 2024]);
+        assert_eq!(series.months(), vec![YearMonth::new(2023, 12), YearMonth::new(2024, 1)]);
+        assert_eq!(series.category("Rent").unwrap().len(), 1);
+        assert_eq!(series.monthly_totals()[&YearMonth::new(2024, 1)], 590.0);
+    }
+
+    #[test]
+    fn test_year_month() {
+        assert_eq!(YearMonth::new(2024, 1).previous(), YearMonth::new(2023, 12));
+        assert_eq!(YearMonth::new(2024, 5).previous().label(), "April 2024");
+        assert_eq!(YearMonth::parse("2024-05"), Some(YearMonth::new(2024, 5)));
+        assert_eq!(YearMonth::parse("2024-13"), None);
+    }
+}
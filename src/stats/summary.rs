@@ -0,0 +1,378 @@
+// descriptive statistics over the expense series: per category mean, median, standard
+// deviation, min, max and share of total, plus month-over-month and year-over-year changes
+// and months far off what a category usually costs
+
+use std::collections::BTreeMap;
+
+use crate::stats::series::{ExpenseSeries, YearMonth};
+use crate::utils::transaction_utils::month_name_from_number;
+
+// robust z-score: distance from the typical value in median absolute deviations (MAD), so a
+// single typo doesn't drag the mean and standard deviation along with it
+const OUTLIER_SCORE: f64 = 3.5; // this many scaled MADs from typical is an outlier
+const MAD_SCALE: f64 = 1.4826; // MAD * this is the standard deviation of normally distributed data
+const OUTLIER_MIN_MONTHS: usize = 4; // fewer other months say nothing about what is usual
+const SEASONAL_MIN_YEARS: usize = 2; // same month of this many other years makes the month's own typical
+// spread never goes below this part of typical, otherwise a category that costs the same every
+// month (rent) flags any change. with OUTLIER_SCORE it means about 1.9x typical or 0.1x of it
+const MIN_RELATIVE_SPREAD: f64 = 0.25;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryStats {
+    pub category: String,
+    pub months: usize, // months with data the numbers are based on
+    pub total: f64,
+    pub mean: f64, // per month
+    pub median: f64,
+    pub std_dev: f64, // sample standard deviation, 0 with less than 2 months
+    pub min: f64,
+    pub max: f64,
+    pub share: f64, // of everything spent in the same months, 0 - 1
+}
+
+// change between two periods
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Delta {
+    pub previous: f64,
+    pub current: f64,
+}
+
+impl Delta {
+    pub fn change(&self) -> f64 {
+        self.current - self.previous
+    }
+
+    // None when nothing was spent before
+    pub fn percent(&self) -> Option<f64> {
+        (self.previous != 0.0).then(|| self.change() / self.previous * 100.0)
+    }
+}
+
+// statistics of every category, biggest spend first. year limits them to a single year
+pub fn category_stats(series: &ExpenseSeries, year: Option<i64>) -> Vec<CategoryStats> {
+    let in_period = |month: &YearMonth| year.is_none_or(|year| month.year == year);
+    let grand_total: f64 = series.monthly_totals().iter().filter(|(month, _)| in_period(month)).map(|(_, total)| total).sum();
+
+    let mut stats: Vec<CategoryStats> = series
+        .categories()
+        .iter()
+        .filter_map(|category| {
+            let values: Vec<f64> = series
+                .category(category)?
+                .iter()
+                .filter(|(month, _)| in_period(month))
+                .map(|(_, value)| *value)
+                .collect();
+            if values.is_empty() {
+                return None;
+            }
+            let total: f64 = values.iter().sum();
+            Some(CategoryStats {
+                category: category.clone(),
+                months: values.len(),
+                total,
+                mean: mean(&values),
+                median: median(&values),
+                std_dev: std_dev(&values),
+                min: values.iter().copied().fold(f64::INFINITY, f64::min),
+                max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                share: if grand_total != 0.0 { total / grand_total } else { 0.0 },
+            })
+        })
+        .collect();
+
+    stats.sort_by(|a, b| b.total.total_cmp(&a.total));
+    stats
+}
+
+// every month against the month before it, for one category or all of them (None)
+// months right after a gap without data are left out
+pub fn month_over_month(series: &ExpenseSeries, category: Option<&str>) -> Vec<(YearMonth, Delta)> {
+    let values = values_of(series, category);
+    values
+        .iter()
+        .filter_map(|(month, current)| {
+            let previous = values.get(&month.previous())?;
+            Some((*month, Delta { previous: *previous, current: *current }))
+        })
+        .collect()
+}
+
+// every year against the year before it, for one category or all of them (None)
+// only months both years have data for are compared, so a running year is compared
+// with the same months of the previous one
+pub fn year_over_year(series: &ExpenseSeries, category: Option<&str>) -> Vec<(i64, Delta)> {
+    let values = values_of(series, category);
+    let mut years: Vec<i64> = values.keys().map(|month| month.year).collect();
+    years.dedup();
+
+    years
+        .into_iter()
+        .filter_map(|year| {
+            let mut delta = Delta { previous: 0.0, current: 0.0 };
+            let mut compared = false;
+            for month in 1..=12 {
+                let current = values.get(&YearMonth::new(year, month));
+                let previous = values.get(&YearMonth::new(year - 1, month));
+                if let (Some(current), Some(previous)) = (current, previous) {
+                    delta.current += current;
+                    delta.previous += previous;
+                    compared = true;
+                }
+            }
+            compared.then_some((year, delta))
+        })
+        .collect()
+}
+
+// month in which a category cost much more or less than usual
+#[derive(Clone, Debug, PartialEq)]
+pub struct Outlier {
+    pub category: String,
+    pub month: YearMonth,
+    pub value: f64,
+    pub typical: f64, // median of the same month in other years when seasonal, of all other months otherwise
+    pub seasonal: bool,
+    pub score: f64, // robust z-score, negative = less than typical
+}
+
+impl Outlier {
+    // value as a multiple of typical, None when typical is 0
+    pub fn ratio(&self) -> Option<f64> {
+        (self.typical != 0.0).then(|| self.value / self.typical)
+    }
+
+    //  Restaurants 3.1x typical for October: 1250.00 in 2024, usually 402.00
+    //  Groceries 4.0x typical month: 400.00 in April 2024, usually 100.00
+    pub fn describe(&self) -> String {
+        let Some(ratio) = self.ratio() else {
+            return format!("{} {:.2} in {}, usually nothing", self.category, self.value, self.month.label());
+        };
+        if self.seasonal {
+            format!(
+                "{} {:.1}x typical for {}: {:.2} in {}, usually {:.2}",
+                self.category,
+                ratio,
+                month_name_from_number(self.month.month).unwrap_or_default(),
+                self.value,
+                self.month.year,
+                self.typical
+            )
+        } else {
+            format!(
+                "{} {:.1}x typical month: {:.2} in {}, usually {:.2}",
+                self.category,
+                ratio,
+                self.value,
+                self.month.label(),
+                self.typical
+            )
+        }
+    }
+}
+
+// the month of a category against every other month of it. months that cost more in every year
+// (December presents) are compared with the same month of the other years once there are enough
+// of them, the spread always comes from all other months
+pub fn month_outlier(category: &str, values: &BTreeMap<YearMonth, f64>, month: YearMonth) -> Option<Outlier> {
+    let value = *values.get(&month)?;
+    let others: Vec<f64> = values.iter().filter(|(other, _)| **other != month).map(|(_, value)| *value).collect();
+    if others.len() < OUTLIER_MIN_MONTHS {
+        return None;
+    }
+    let same_month: Vec<f64> = values
+        .iter()
+        .filter(|(other, _)| other.month == month.month && **other != month)
+        .map(|(_, value)| *value)
+        .collect();
+
+    let seasonal = same_month.len() >= SEASONAL_MIN_YEARS;
+    let typical = if seasonal { median(&same_month) } else { median(&others) };
+    let spread = (mad(&others) * MAD_SCALE).max(typical.abs() * MIN_RELATIVE_SPREAD);
+    if spread <= 0.0 {
+        return None;
+    }
+    let score = (value - typical) / spread;
+    (score.abs() > OUTLIER_SCORE).then(|| Outlier { category: category.to_string(), month, value, typical, seasonal, score })
+}
+
+// unusual months (of the year, or all) of every category, oldest first
+pub fn outliers(series: &ExpenseSeries, year: Option<i64>) -> Vec<Outlier> {
+    let mut outliers: Vec<Outlier> = series
+        .categories()
+        .iter()
+        .filter_map(|category| Some((category, series.category(category)?)))
+        .flat_map(|(category, values)| {
+            values
+                .keys()
+                .filter(|month| year.is_none_or(|year| month.year == year))
+                .filter_map(|month| month_outlier(category, values, *month))
+                .collect::<Vec<Outlier>>()
+        })
+        .collect();
+    outliers.sort_by_key(|outlier| outlier.month);
+    outliers
+}
+
+fn values_of(series: &ExpenseSeries, category: Option<&str>) -> BTreeMap<YearMonth, f64> {
+    match category {
+        Some(category) => series.category(category).cloned().unwrap_or_default(),
+        None => series.monthly_totals(),
+    }
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+// median absolute deviation from the median
+pub fn mad(values: &[f64]) -> f64 {
+    let middle = median(values);
+    let deviations: Vec<f64> = values.iter().map(|value| (value - middle).abs()).collect();
+    median(&deviations)
+}
+
+pub fn std_dev(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance = values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Groceries every month of 2023 and January - March 2024, Rent from 2024 on
+    fn series() -> ExpenseSeries {
+        let mut series = ExpenseSeries::default();
+        for month in 1..=12 {
+            series.insert("Groceries", YearMonth::new(2023, month), 100.0);
+        }
+        for (month, value) in [(1, 120.0), (2, 80.0), (3, 160.0)] {
+            series.insert("Groceries", YearMonth::new(2024, month), value);
+            series.insert("Rent", YearMonth::new(2024, month), 500.0);
+        }
+        series
+    }
+
+    #[test]
+    fn test_category_stats() {
+        let stats = category_stats(&series(), Some(2024));
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats[0].category, "Rent"); // biggest first
+
+        let groceries = &stats[1];
+        assert_eq!(groceries.months, 3);
+        assert_eq!(groceries.total, 360.0);
+        assert_eq!(groceries.mean, 120.0);
+        assert_eq!(groceries.median, 120.0);
+        assert_eq!(groceries.std_dev, 40.0);
+        assert_eq!((groceries.min, groceries.max), (80.0, 160.0));
+        assert_eq!(groceries.share, 360.0 / 1860.0);
+
+        // all years: Rent didn't exist in 2023, Groceries has 15 months
+        let stats = category_stats(&series(), None);
+        assert_eq!(stats[0].category, "Groceries");
+        assert_eq!(stats[0].months, 15);
+        assert_eq!(stats[1].months, 3);
+    }
+
+    #[test]
+    fn test_month_over_month() {
+        let deltas = month_over_month(&series(), Some("Groceries"));
+        assert_eq!(deltas.len(), 14);
+        let (month, delta) = deltas.last().unwrap();
+        assert_eq!(*month, YearMonth::new(2024, 3));
+        assert_eq!(delta.change(), 80.0);
+        assert_eq!(delta.percent(), Some(100.0));
+
+        // January 2024 against December 2023, totals of everything
+        let totals = month_over_month(&series(), None);
+        let january = totals.iter().find(|(month, _)| *month == YearMonth::new(2024, 1)).unwrap();
+        assert_eq!(january.1, Delta { previous: 100.0, current: 620.0 });
+    }
+
+    #[test]
+    fn test_year_over_year() {
+        // only January - March of 2023 are compared with the running year
+        let deltas = year_over_year(&series(), Some("Groceries"));
+        assert_eq!(deltas, vec![(2024, Delta { previous: 300.0, current: 360.0 })]);
+        assert_eq!(deltas[0].1.percent(), Some(20.0));
+
+        // nothing to compare Rent with
+        assert!(year_over_year(&series(), Some("Rent")).is_empty());
+    }
+
+    #[test]
+    fn test_outliers() {
+        let mut series = series();
+        series.insert("Groceries", YearMonth::new(2024, 4), 400.0);
+
+        let outliers = outliers(&series, Some(2024));
+        assert_eq!(outliers.len(), 1); // 160 in March is a lot more, but not unusual
+        assert_eq!(outliers[0].month, YearMonth::new(2024, 4));
+        assert_eq!(outliers[0].category, "Groceries");
+        assert_eq!(outliers[0].describe(), "Groceries 4.0x typical month: 400.00 in April 2024, usually 100.00");
+
+        // Rent never changes, 2023 had nothing unusual
+        assert!(super::outliers(&series, Some(2023)).is_empty());
+
+        // a typo, 1250 instead of 12.50
+        series.insert("Coffee", YearMonth::new(2024, 1), 12.5);
+        series.insert("Coffee", YearMonth::new(2024, 2), 11.0);
+        series.insert("Coffee", YearMonth::new(2024, 3), 14.0);
+        series.insert("Coffee", YearMonth::new(2024, 4), 13.0);
+        series.insert("Coffee", YearMonth::new(2024, 5), 1250.0);
+        let coffee = month_outlier("Coffee", series.category("Coffee").unwrap(), YearMonth::new(2024, 5)).unwrap();
+        assert!(coffee.score > 100.0);
+        assert!(month_outlier("Coffee", series.category("Coffee").unwrap(), YearMonth::new(2024, 3)).is_none());
+    }
+
+    #[test]
+    fn test_seasonal_outliers() {
+        let mut series = ExpenseSeries::default();
+        for year in 2021..=2023 {
+            for month in 1..=12 {
+                series.insert("Heating", YearMonth::new(year, month), if month == 12 { 300.0 } else { 50.0 });
+            }
+        }
+        series.insert("Heating", YearMonth::new(2024, 7), 300.0);
+        series.insert("Heating", YearMonth::new(2024, 12), 310.0);
+        let values = series.category("Heating").unwrap();
+
+        // December always costs that much
+        assert_eq!(month_outlier("Heating", values, YearMonth::new(2024, 12)), None);
+        let july = month_outlier("Heating", values, YearMonth::new(2024, 7)).unwrap();
+        assert!(july.seasonal);
+        assert_eq!(july.describe(), "Heating 6.0x typical for July: 300.00 in 2024, usually 50.00");
+    }
+
+    #[test]
+    fn test_median_and_std_dev() {
+        assert_eq!(median(&[3.0, 1.0, 2.0, 10.0]), 2.5);
+        assert_eq!(median(&[]), 0.0);
+        assert_eq!(std_dev(&[5.0]), 0.0);
+        assert_eq!(mad(&[1.0, 1.0, 2.0, 2.0, 4.0, 6.0, 9.0]), 1.0);
+        assert!((std_dev(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]) - 2.138).abs() < 0.001);
+    }
+}
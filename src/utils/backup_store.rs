@@ -0,0 +1,353 @@
+// content addressed storage for backups:
+//  backup/objects/<sha256>.xlsx     workbook, stored once no matter how many backups share it
+//  backup/objects/<sha256>.xlsx.gz  same, compressed (compress_backups in config.json)
+//  backup/index.jsonl               one line per backup: name, timestamp, hash, size
+// the index is the only source of backup times, file modification times are not used
+// (copies and sync tools reset them). backups of older versions (loose backup_*.xlsx files)
+// are moved into the store the first time it is used
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+const OBJECTS_FOLDER: &str = "objects";
+const INDEX_FILE: &str = "index.jsonl";
+const TMP_FOLDER: &str = "tmp"; // next to backup folder, cleared before every update
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BackupRecord {
+    pub name: String, // backup_20240602_184000.xlsx, what the user sees
+    pub timestamp: NaiveDateTime, // UTC
+    pub hash: String, // sha256 of the workbook
+    pub size: u64, // workbook size, before compression
+}
+
+// all backups in the order they were taken, broken lines are skipped
+pub fn load_backup_index(backup_folder: &Path) -> Vec<BackupRecord> {
+    match fs::read_to_string(backup_folder.join(INDEX_FILE)) {
+        Ok(data) => data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter_map(|line| match serde_json::from_str(line) {
+                Ok(record) => Some(record),
+                Err(e) => {
+                    println!("Skipping broken backup index line: {}", e);
+                    None
+                }
+            })
+            .collect(),
+        Err(_) => Vec::new(), // no backups yet
+    }
+}
+
+// newest backup by its recorded time, later index lines win ties
+pub fn latest_backup_record(backup_folder: &Path) -> Option<BackupRecord> {
+    load_backup_index(backup_folder)
+        .into_iter()
+        .enumerate()
+        .max_by(|(a_index, a), (b_index, b)| a.timestamp.cmp(&b.timestamp).then(a_index.cmp(b_index)))
+        .map(|(_, record)| record)
+}
+
+// stores the workbook as a new backup, unless it is identical to the newest backup
+// returns the backup holding the workbook either way
+pub fn store_backup(backup_folder: &Path, workbook_path: &Path, timestamp: NaiveDateTime, compress: bool) -> io::Result<BackupRecord> {
+    let hash = file_hash(workbook_path)?;
+    if let Some(latest) = latest_backup_record(backup_folder).filter(|latest| latest.hash == hash) {
+        return Ok(latest);
+    }
+    add_backup(backup_folder, workbook_path, &hash, None, timestamp, compress)
+}
+
+// removes backups from the index, objects no other backup uses are deleted
+pub fn remove_backups(backup_folder: &Path, names: &[String]) -> io::Result<()> {
+    if names.is_empty() {
+        return Ok(());
+    }
+    let index = load_backup_index(backup_folder);
+    let (removed, kept): (Vec<BackupRecord>, Vec<BackupRecord>) = index.into_iter().partition(|record| names.contains(&record.name));
+    save_backup_index(backup_folder, &kept)?;
+
+    for record in removed {
+        if !kept.iter().any(|other| other.hash == record.hash) {
+            for path in object_paths(backup_folder, &record.hash) {
+                if path.exists() {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// size the backup's object takes on disk
+pub fn stored_size(backup_folder: &Path, record: &BackupRecord) -> u64 {
+    object_paths(backup_folder, &record.hash)
+        .iter()
+        .find_map(|path| fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .unwrap_or(0)
+}
+
+// path calamine can open: the object itself or a decompressed copy in tmp folder
+pub fn backup_readable_path(backup_folder: &Path, record: &BackupRecord) -> io::Result<PathBuf> {
+    let [plain_path, compressed_path] = object_paths(backup_folder, &record.hash);
+    if plain_path.exists() {
+        return Ok(plain_path);
+    }
+
+    let tmp_folder = backup_folder.parent().unwrap_or(backup_folder).join(TMP_FOLDER);
+    fs::create_dir_all(&tmp_folder)?;
+    let readable_path = tmp_folder.join(format!("backup_{}.xlsx", record.hash));
+    if !readable_path.exists() {
+        let mut decoder = GzDecoder::new(BufReader::new(File::open(&compressed_path)?));
+        let mut output = BufWriter::new(File::create(&readable_path)?);
+        io::copy(&mut decoder, &mut output)?;
+        output.flush()?;
+    }
+    Ok(readable_path)
+}
+
+// moves loose backup_*.xlsx files (from before the store existed) into it
+pub fn migrate_loose_backups(backup_folder: &Path) -> io::Result<()> {
+    let Ok(entries) = fs::read_dir(backup_folder) else {
+        return Ok(()); // no backup folder yet
+    };
+    let mut loose: Vec<(PathBuf, NaiveDateTime)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "xlsx"))
+        .filter_map(|path| {
+            let timestamp = loose_backup_timestamp(&path)?;
+            Some((path, timestamp))
+        })
+        .collect();
+    loose.sort_by_key(|(_, timestamp)| *timestamp);
+
+    for (path, timestamp) in loose {
+        let hash = file_hash(&path)?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        // keeps its name, pinned.txt refers to it
+        add_backup(backup_folder, &path, &hash, Some(name), timestamp, false)?;
+        fs::remove_file(&path)?;
+    }
+    Ok(())
+}
+
+// when a loose backup was taken: from the name backup_<timestamp>.xlsx (or backup_<timestamp>_1.xlsx),
+// modification time for anything else, it is all there is for those
+fn loose_backup_timestamp(backup_path: &Path) -> Option<NaiveDateTime> {
+    let from_name = backup_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix("backup_"))
+        .and_then(|name| name.get(..15))
+        .and_then(|stamp| NaiveDateTime::parse_from_str(stamp, "%Y%m%d_%H%M%S").ok());
+
+    from_name.or_else(|| {
+        let modified = fs::metadata(backup_path).and_then(|metadata| metadata.modified()).ok()?;
+        Some(DateTime::<Utc>::from(modified).naive_utc())
+    })
+}
+
+fn add_backup(backup_folder: &Path, workbook_path: &Path, hash: &str, name: Option<String>, timestamp: NaiveDateTime, compress: bool) -> io::Result<BackupRecord> {
+    let objects_folder = backup_folder.join(OBJECTS_FOLDER);
+    fs::create_dir_all(&objects_folder)?;
+
+    // identical workbook already stored (plain or compressed), nothing to copy
+    let [plain_path, compressed_path] = object_paths(backup_folder, hash);
+    if !plain_path.exists() && !compressed_path.exists() {
+        // written under a temporary name first, so an object is never half written
+        let partial_path = objects_folder.join(format!("{}.partial", hash));
+        if compress {
+            let mut encoder = GzEncoder::new(BufWriter::new(File::create(&partial_path)?), Compression::default());
+            io::copy(&mut BufReader::new(File::open(workbook_path)?), &mut encoder)?;
+            encoder.finish()?.flush()?;
+            fs::rename(&partial_path, &compressed_path)?;
+        } else {
+            fs::copy(workbook_path, &partial_path)?;
+            fs::rename(&partial_path, &plain_path)?;
+        }
+    }
+
+    let index = load_backup_index(backup_folder);
+    let name = name
+        .filter(|name| !index.iter().any(|record| &record.name == name))
+        .unwrap_or_else(|| unique_name(&index, timestamp));
+    let record = BackupRecord {
+        name,
+        timestamp,
+        hash: hash.to_string(),
+        size: fs::metadata(workbook_path)?.len(),
+    };
+
+    let line = serde_json::to_string(&record).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut file = OpenOptions::new().create(true).append(true).open(backup_folder.join(INDEX_FILE))?;
+    writeln!(file, "{}", line)?;
+    Ok(record)
+}
+
+// backup_<timestamp>.xlsx, two backups within a second get backup_<timestamp>_1.xlsx
+fn unique_name(index: &[BackupRecord], timestamp: NaiveDateTime) -> String {
+    let stamp = timestamp.format("%Y%m%d_%H%M%S");
+    let mut name = format!("backup_{}.xlsx", stamp);
+    let mut counter = 1;
+    while index.iter().any(|record| record.name == name) {
+        name = format!("backup_{}_{}.xlsx", stamp, counter);
+        counter += 1;
+    }
+    name
+}
+
+// rewrites the whole index through a temporary file
+fn save_backup_index(backup_folder: &Path, index: &[BackupRecord]) -> io::Result<()> {
+    let partial_path = backup_folder.join(format!("{}.partial", INDEX_FILE));
+    let mut file = BufWriter::new(File::create(&partial_path)?);
+    for record in index {
+        let line = serde_json::to_string(record).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        writeln!(file, "{}", line)?;
+    }
+    file.flush()?;
+    drop(file);
+    fs::rename(partial_path, backup_folder.join(INDEX_FILE))
+}
+
+fn object_paths(backup_folder: &Path, hash: &str) -> [PathBuf; 2] {
+    let objects_folder = backup_folder.join(OBJECTS_FOLDER);
+    [objects_folder.join(format!("{}.xlsx", hash)), objects_folder.join(format!("{}.xlsx.gz", hash))]
+}
+
+fn file_hash(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut BufReader::new(File::open(path)?), &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use tempfile::tempdir;
+
+    fn at(day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 5, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    // destination/backup folder and a workbook file with the given content
+    fn setup(content: &str) -> (tempfile::TempDir, PathBuf, PathBuf) {
+        let temp_dir = tempdir().unwrap();
+        let backup_folder = temp_dir.path().join("backup");
+        fs::create_dir(&backup_folder).unwrap();
+        let workbook = temp_dir.path().join("expensesGenerated.xlsx");
+        fs::write(&workbook, content).unwrap();
+        (temp_dir, backup_folder, workbook)
+    }
+
+    #[test]
+    fn test_identical_backups_are_skipped() {
+        let (_temp_dir, backup_folder, workbook) = setup("first");
+
+        let first = store_backup(&backup_folder, &workbook, at(1, 10), false).unwrap();
+        assert_eq!(first.name, "backup_20240501_100000.xlsx");
+        assert_eq!(first.size, 5);
+
+        // nothing changed since
+        assert_eq!(store_backup(&backup_folder, &workbook, at(2, 10), false).unwrap(), first);
+
+        fs::write(&workbook, "second").unwrap();
+        let second = store_backup(&backup_folder, &workbook, at(3, 10), false).unwrap();
+        // back to the first content: new backup, same object
+        fs::write(&workbook, "first").unwrap();
+        let third = store_backup(&backup_folder, &workbook, at(4, 10), false).unwrap();
+
+        assert_eq!(third.hash, first.hash);
+        assert_ne!(second.hash, first.hash);
+        assert_eq!(load_backup_index(&backup_folder).len(), 3);
+        assert_eq!(fs::read_dir(backup_folder.join(OBJECTS_FOLDER)).unwrap().count(), 2);
+        assert_eq!(latest_backup_record(&backup_folder), Some(third));
+    }
+
+    #[test]
+    fn test_latest_ignores_index_order() {
+        let (_temp_dir, backup_folder, workbook) = setup("first");
+        store_backup(&backup_folder, &workbook, at(5, 10), false).unwrap();
+        fs::write(&workbook, "older").unwrap();
+        store_backup(&backup_folder, &workbook, at(1, 10), false).unwrap(); // clock went back
+
+        assert_eq!(latest_backup_record(&backup_folder).unwrap().timestamp, at(5, 10));
+    }
+
+    #[test]
+    fn test_same_second_gets_unique_name() {
+        let (_temp_dir, backup_folder, workbook) = setup("first");
+        store_backup(&backup_folder, &workbook, at(1, 10), false).unwrap();
+        fs::write(&workbook, "second").unwrap();
+        let second = store_backup(&backup_folder, &workbook, at(1, 10), false).unwrap();
+
+        assert_eq!(second.name, "backup_20240501_100000_1.xlsx");
+        assert_eq!(latest_backup_record(&backup_folder).unwrap(), second);
+    }
+
+    #[test]
+    fn test_compressed_backup() {
+        let (_temp_dir, backup_folder, workbook) = setup(&"workbook data ".repeat(100));
+        let record = store_backup(&backup_folder, &workbook, at(1, 10), true).unwrap();
+
+        assert!(backup_folder.join(OBJECTS_FOLDER).join(format!("{}.xlsx.gz", record.hash)).exists());
+        assert!(stored_size(&backup_folder, &record) < record.size);
+
+        let readable = backup_readable_path(&backup_folder, &record).unwrap();
+        assert!(readable.starts_with(backup_folder.parent().unwrap().join(TMP_FOLDER)));
+        assert_eq!(fs::read_to_string(readable).unwrap(), "workbook data ".repeat(100));
+    }
+
+    #[test]
+    fn test_remove_backups_keeps_shared_objects() {
+        let (_temp_dir, backup_folder, workbook) = setup("first");
+        let first = store_backup(&backup_folder, &workbook, at(1, 10), false).unwrap();
+        fs::write(&workbook, "second").unwrap();
+        let second = store_backup(&backup_folder, &workbook, at(2, 10), false).unwrap();
+        fs::write(&workbook, "first").unwrap();
+        let third = store_backup(&backup_folder, &workbook, at(3, 10), false).unwrap();
+
+        remove_backups(&backup_folder, &[first.name.clone(), second.name.clone()]).unwrap();
+
+        assert_eq!(load_backup_index(&backup_folder), vec![third.clone()]);
+        // first and third share the object, it stays
+        assert!(backup_readable_path(&backup_folder, &third).unwrap().exists());
+        assert_eq!(fs::read_dir(backup_folder.join(OBJECTS_FOLDER)).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_migrate_loose_backups() {
+        let (_temp_dir, backup_folder, _) = setup("");
+        fs::write(backup_folder.join("backup_20240502_100000.xlsx"), "newer").unwrap();
+        fs::write(backup_folder.join("backup_20240501_100000.xlsx"), "older").unwrap();
+        fs::write(backup_folder.join("notes.txt"), "not a backup").unwrap();
+
+        migrate_loose_backups(&backup_folder).unwrap();
+
+        let index = load_backup_index(&backup_folder);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index[0].name, "backup_20240501_100000.xlsx");
+        assert_eq!(index[1].timestamp, at(2, 10));
+        assert!(!backup_folder.join("backup_20240501_100000.xlsx").exists());
+        assert!(backup_folder.join("notes.txt").exists());
+        assert_eq!(fs::read_to_string(backup_readable_path(&backup_folder, &index[1]).unwrap()).unwrap(), "newer");
+    }
+
+    #[test]
+    fn test_loose_backup_timestamp() {
+        let expected = Some(at(2, 10));
+        assert_eq!(loose_backup_timestamp(Path::new("backup/backup_20240502_100000.xlsx")), expected);
+        assert_eq!(loose_backup_timestamp(Path::new("backup/backup_20240502_100000_1.xlsx")), expected);
+        // neither a backup name nor an existing file
+        assert_eq!(loose_backup_timestamp(Path::new("backup/copy.xlsx")), None);
+    }
+}
@@ -0,0 +1,318 @@
+// backups of the spreadsheet (backup_<timestamp>.xlsx in the backup store) for the GUI and CLI:
+// listing, read only copies, diff against the current workbook and restore
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDateTime;
+
+use crate::file_handlers::xls_handlers::{
+    xls_cell_label, xls_current_workbook_path, xls_is_total_cell, xls_months_with_data, xls_read_sheet, CellValue, SheetCells, RES_WORKBOOK_NAME,
+};
+use crate::utils::backup_store::{backup_readable_path, load_backup_index, migrate_loose_backups};
+use crate::utils::folder_file_utils::{
+    partial_file_path, pinned_backups, prepare_folder_structure, replace_with_partial_file, BACKUP_FOLDER, TMP_FOLDER,
+};
+use crate::utils::external_edits::{check_before_write, record_workbook_hash};
+use crate::utils::workbook_lock::WorkbookLock;
+
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub hash: String, // same hash, same workbook
+    pub timestamp: NaiveDateTime, // UTC, from the backup index
+    pub size: u64, // bytes, uncompressed
+    pub months: BTreeMap<i64, Vec<String>>, // years with months that have data
+    pub pinned: bool, // never deleted by retention
+}
+
+impl BackupInfo {
+    //  backup_20240602_184000.xlsx  2024-06-02 18:40  12.3 KB  2023: Jan-Dec, 2024: Jan-May  pinned
+    pub fn summary(&self) -> String {
+        let years: Vec<String> = self
+            .months
+            .iter()
+            .map(|(year, months)| match (months.first(), months.last()) {
+                (Some(first), Some(last)) if first == last => format!("{}: {}", year, short_month(first)),
+                (Some(first), Some(last)) => format!("{}: {}-{}", year, short_month(first), short_month(last)),
+                _ => format!("{}: empty", year),
+            })
+            .collect();
+
+        format!(
+            "{}  {}  {}  {}{}",
+            self.file_name,
+            self.timestamp.format("%Y-%m-%d %H:%M"),
+            format_size(self.size),
+            years.join(", "),
+            if self.pinned { "  pinned" } else { "" }
+        )
+    }
+}
+
+// single cell which differs between a backup and the current workbook
+#[derive(Clone, Debug, PartialEq)]
+pub struct CellDiff {
+    pub row: u32,
+    pub col: u32,
+    pub label: String, // "March 2024 Groceries"
+    pub backup: Option<CellValue>,
+    pub current: Option<CellValue>,
+}
+
+impl CellDiff {
+    //  March 2024 Groceries: 12.50 -> 40.00 (backup -> current)
+    pub fn describe(&self) -> String {
+        format!("{}: {} -> {}", self.label, describe_value(&self.backup), describe_value(&self.current))
+    }
+}
+
+// backups in the destination folder, newest first
+pub fn list_backups(destination_path: &str) -> Vec<BackupInfo> {
+    let backup_folder = Path::new(destination_path).join(BACKUP_FOLDER);
+    if let Err(e) = migrate_loose_backups(&backup_folder) {
+        println!("Error moving old backups into the backup store: {}", e);
+    }
+    let pinned = pinned_backups(&backup_folder);
+
+    let mut backups: Vec<BackupInfo> = load_backup_index(&backup_folder)
+        .into_iter()
+        .map(|record| {
+            let months = backup_readable_path(&backup_folder, &record)
+                .ok()
+                .and_then(|path| path.to_str().and_then(xls_read_sheet))
+                .map(|cells| xls_months_with_data(&cells))
+                .unwrap_or_default();
+            BackupInfo {
+                pinned: pinned.contains(&record.name),
+                file_name: record.name,
+                hash: record.hash,
+                timestamp: record.timestamp,
+                size: record.size,
+                months,
+            }
+        })
+        .collect();
+
+    backups.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.file_name.cmp(&a.file_name)));
+    backups
+}
+
+// readable path of a backup by its name, names not in the backup index are refused
+pub fn find_backup(destination_path: &str, file_name: &str) -> Result<PathBuf, String> {
+    let backup_folder = Path::new(destination_path).join(BACKUP_FOLDER);
+    if let Err(e) = migrate_loose_backups(&backup_folder) {
+        println!("Error moving old backups into the backup store: {}", e);
+    }
+    let record = load_backup_index(&backup_folder)
+        .into_iter()
+        .find(|record| record.name == file_name)
+        .ok_or(format!("Backup {} not found", file_name))?;
+    backup_readable_path(&backup_folder, &record).map_err(|e| format!("Cannot read backup {}: {}", file_name, e))
+}
+
+// read only copy of a backup in tmp folder, so opening it can't change the backup itself
+pub fn open_backup_read_only(destination_path: &str, file_name: &str) -> Result<PathBuf, String> {
+    let backup_path = find_backup(destination_path, file_name)?;
+    let tmp_folder = Path::new(destination_path).join(TMP_FOLDER);
+    fs::create_dir_all(&tmp_folder).map_err(|e| format!("Cannot create tmp folder: {}", e))?;
+
+    let view_path = tmp_folder.join(format!("view_{}", file_name));
+    if view_path.exists() {
+        // an older read only copy, has to be writable to be removed everywhere
+        if let Ok(metadata) = fs::metadata(&view_path) {
+            let mut permissions = metadata.permissions();
+            #[allow(clippy::permissions_set_readonly_false)]
+            permissions.set_readonly(false);
+            let _ = fs::set_permissions(&view_path, permissions);
+        }
+        fs::remove_file(&view_path).map_err(|e| format!("Cannot replace old copy: {}", e))?;
+    }
+
+    fs::copy(&backup_path, &view_path).map_err(|e| format!("Cannot copy backup: {}", e))?;
+    let mut permissions = fs::metadata(&view_path).map_err(|e| e.to_string())?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(&view_path, permissions).map_err(|e| format!("Cannot make the copy read only: {}", e))?;
+    Ok(view_path)
+}
+
+// cells which differ between the backup and the current workbook
+pub fn diff_backup(destination_path: &str, file_name: &str) -> Result<Vec<CellDiff>, String> {
+    let backup_path = find_backup(destination_path, file_name)?;
+    let current_path = xls_current_workbook_path(destination_path).ok_or("No spreadsheet found in destination folder")?;
+
+    let backup_cells = read_cells(&backup_path)?;
+    let current_cells = read_cells(&current_path)?;
+    Ok(diff_cells(&backup_cells, &current_cells))
+}
+
+pub fn diff_cells(backup: &SheetCells, current: &SheetCells) -> Vec<CellDiff> {
+    let mut positions: Vec<&(u32, u32)> = backup.keys().chain(current.keys()).collect();
+    positions.sort();
+    positions.dedup();
+
+    positions
+        .into_iter()
+        .filter(|position| !same_value(backup.get(position), current.get(position)))
+        // totals follow the data, only the data is worth listing
+        .filter(|&&(row, col)| !xls_is_total_cell(current, row, col) && !xls_is_total_cell(backup, row, col))
+        .map(|&(row, col)| CellDiff {
+            row,
+            col,
+            label: xls_cell_label(current, row, col)
+                .or_else(|| xls_cell_label(backup, row, col))
+                .unwrap_or_else(|| format!("row {} col {}", row + 1, col + 1)),
+            backup: backup.get(&(row, col)).cloned(),
+            current: current.get(&(row, col)).cloned(),
+        })
+        .collect()
+}
+
+// makes the backup the current workbook, the replaced workbook becomes a backup itself
+// the backup is staged next to the workbook first and renamed into place, so the current
+// workbook is never a half written file
+pub fn restore_backup(destination_path: &str, file_name: &str) -> Result<(), String> {
+    let _lock = WorkbookLock::acquire(destination_path)?;
+    // changes made outside the app end up in a backup too, only an open workbook is a problem
+    check_before_write(destination_path, RES_WORKBOOK_NAME, true)?;
+    let backup_path = find_backup(destination_path, file_name)?;
+    read_cells(&backup_path)?; // refuse broken backups before touching anything
+
+    let result_path = Path::new(destination_path).join(RES_WORKBOOK_NAME);
+    let staging_path = partial_file_path(&result_path);
+    fs::copy(&backup_path, &staging_path).map_err(|e| format!("Cannot copy backup: {}", e))?;
+
+    // backs up the current workbook (old backups may be removed here, the one being
+    // restored is already staged)
+    if let Err(e) = prepare_folder_structure(destination_path, RES_WORKBOOK_NAME) {
+        let _ = fs::remove_file(&staging_path);
+        return Err(format!("Cannot back up the current workbook: {}", e));
+    }
+
+    replace_with_partial_file(&staging_path, &result_path).map_err(|e| format!("Cannot restore backup: {}", e))?;
+    if let Err(e) = record_workbook_hash(destination_path, RES_WORKBOOK_NAME) {
+        println!("Error recording workbook hash: {}", e);
+    }
+    Ok(())
+}
+
+fn read_cells(path: &Path) -> Result<SheetCells, String> {
+    path.to_str()
+        .and_then(xls_read_sheet)
+        .ok_or(format!("Cannot read {}", path.display()))
+}
+
+// numbers are compared in cents
+fn same_value(a: Option<&CellValue>, b: Option<&CellValue>) -> bool {
+    match (a, b) {
+        (Some(CellValue::Number(a)), Some(CellValue::Number(b))) => (a * 100.0).round() == (b * 100.0).round(),
+        _ => a == b,
+    }
+}
+
+fn describe_value(value: &Option<CellValue>) -> String {
+    match value {
+        Some(CellValue::Number(number)) => format!("{:.2}", number),
+        Some(CellValue::Text(text)) => text.clone(),
+        None => "empty".to_string(),
+    }
+}
+
+fn short_month(month: &str) -> &str {
+    month.get(..3).unwrap_or(month)
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_handlers::xls_handlers::test_support::write_test_workbook;
+    use crate::file_handlers::xls_handlers::xls_perform_cells_update;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    // destination with one backup (the test workbook) and a current workbook with
+    // January sweets filled in
+    fn destination_with_backup() -> (tempfile::TempDir, String) {
+        let temp_dir = tempdir().unwrap();
+        write_test_workbook(&temp_dir.path().join(RES_WORKBOOK_NAME));
+
+        let mut cells = HashMap::new();
+        cells.insert((2, 3), Some(7.5));
+        let backup_name = xls_perform_cells_update(&cells, temp_dir.path().to_str().unwrap()).unwrap();
+        (temp_dir, backup_name)
+    }
+
+    #[test]
+    fn test_list_backups() {
+        let (temp_dir, backup_name) = destination_with_backup();
+        let backups = list_backups(temp_dir.path().to_str().unwrap());
+
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].file_name, backup_name);
+        assert!(backups[0].size > 0);
+        assert_eq!(backups[0].months.get(&2024), Some(&vec!["January".to_string()]));
+        assert!(backups[0].summary().ends_with("2024: Jan"));
+
+        assert!(list_backups(tempdir().unwrap().path().to_str().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn test_diff_backup() {
+        let (temp_dir, backup_name) = destination_with_backup();
+        let diff = diff_backup(temp_dir.path().to_str().unwrap(), &backup_name).unwrap();
+
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].describe(), "January 2024 Sweets: empty -> 7.50");
+    }
+
+    #[test]
+    fn test_restore_backup() {
+        let (temp_dir, backup_name) = destination_with_backup();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        restore_backup(destination, &backup_name).unwrap();
+        assert!(diff_backup(destination, &backup_name).unwrap().is_empty());
+        assert!(!partial_file_path(&temp_dir.path().join(RES_WORKBOOK_NAME)).exists());
+
+        // the replaced workbook was kept as a backup
+        let backups = list_backups(destination);
+        assert_eq!(backups.len(), 2);
+        let replaced = backups.iter().find(|backup| backup.file_name != backup_name).unwrap();
+        assert_eq!(diff_backup(destination, &replaced.file_name).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_open_backup_read_only() {
+        let (temp_dir, backup_name) = destination_with_backup();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        let view_path = open_backup_read_only(destination, &backup_name).unwrap();
+        assert!(fs::metadata(&view_path).unwrap().permissions().readonly());
+        // opening again replaces the old copy
+        assert_eq!(open_backup_read_only(destination, &backup_name).unwrap(), view_path);
+    }
+
+    #[test]
+    fn test_find_backup_refuses_other_paths() {
+        let (temp_dir, _) = destination_with_backup();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        assert!(find_backup(destination, "../expensesGenerated.xlsx").is_err());
+        assert!(find_backup(destination, "backup_19990101_000000.xlsx").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(12_595), "12.3 KB");
+    }
+}
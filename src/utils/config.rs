@@ -0,0 +1,158 @@
+// user configuration stored next to the spreadsheet as config.json
+// missing file or missing fields fall back to defaults, so old folders keep working.
+// a file that doesn't parse is never saved over, the user's rules and budgets are in it
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+use crate::import::recurring::RecurringExpense;
+use crate::import::rules::CategoryRule;
+use crate::stats::budgets::Budget;
+use crate::utils::retention::RetentionPolicy;
+
+const CONFIG_FILE_NAME: &str = "config.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    // ordered, the first matching rule wins
+    pub rules: Vec<CategoryRule>,
+    // learned suggestions at or above this confidence are applied without asking
+    pub suggestion_auto_apply_confidence: f64,
+    // same amount booked this many days apart is treated as a likely duplicate
+    pub duplicate_date_window_days: i64,
+    // which backups in backup folder are kept
+    pub backup_retention: RetentionPolicy,
+    // backups are stored gzip compressed, smaller but unpacked into tmp folder to be read
+    pub compress_backups: bool,
+    // workbook gets a "Summary" sheet with totals, averages and charts, rewritten on every update
+    pub summary_sheet: bool,
+    // monthly amount per category, the import reports months that go over
+    pub budgets: Vec<Budget>,
+    // workbook gets a "Budget" sheet with budget, actual and variance of every month
+    pub budget_sheet: bool,
+    // CSV of month and consumer price index for statistics in real terms, relative to the destination folder
+    pub cpi_table: Option<String>,
+    // "2024-05", amounts in real terms are restated to prices of this month, latest month of the table if not set
+    pub cpi_base_month: Option<String>,
+    // rent, subscriptions and the like, added to every imported month they are due in
+    pub recurring: Vec<RecurringExpense>,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            rules: Vec::new(),
+            suggestion_auto_apply_confidence: 0.9,
+            duplicate_date_window_days: 3,
+            backup_retention: RetentionPolicy::default(),
+            compress_backups: false,
+            summary_sheet: false,
+            budgets: Vec::new(),
+            budget_sheet: false,
+            cpi_table: None,
+            cpi_base_month: None,
+            recurring: Vec::new(),
+        }
+    }
+}
+
+// reads config.json from the destination folder, defaults when there is none yet
+pub fn read_config(destination_path: &str) -> Result<AppConfig, String> {
+    let config_path = Path::new(destination_path).join(CONFIG_FILE_NAME);
+
+    match fs::read_to_string(&config_path) {
+        Ok(data) => serde_json::from_str(&data).map_err(|e| format!("Error parsing {}: {}", CONFIG_FILE_NAME, e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(AppConfig::default()), // no config yet
+        // not utf-8 (saved by some editor in latin-1), unreadable, ... still the user's config
+        Err(e) => Err(format!("Error reading {}: {}", CONFIG_FILE_NAME, e)),
+    }
+}
+
+// same, but a broken file is reported and read as defaults. for reading only, it is not
+// saved over anyway
+pub fn load_config(destination_path: &str) -> AppConfig {
+    read_config(destination_path).unwrap_or_else(|e| {
+        println!("{}, using defaults", e);
+        AppConfig::default()
+    })
+}
+
+// writes config.json into the destination folder, refused when the file there doesn't parse
+pub fn save_config(destination_path: &str, config: &AppConfig) -> io::Result<()> {
+    let config_path = Path::new(destination_path).join(CONFIG_FILE_NAME);
+    if let Err(e) = read_config(destination_path) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{}, fix it before anything is saved to it", e)));
+    }
+    let data = serde_json::to_string_pretty(config).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(config_path, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::import::rules::RuleCondition;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_load_config_defaults_when_missing() {
+        let temp_dir = tempdir().unwrap();
+        let config = load_config(temp_dir.path().to_str().unwrap());
+        assert_eq!(config, AppConfig::default());
+    }
+
+    #[test]
+    fn test_save_and_load_config() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        let config = AppConfig {
+            rules: vec![CategoryRule {
+                condition: RuleCondition::DescriptionContains { pattern: "bakery".to_string() },
+                category: "Sweets".to_string(),
+            }],
+            ..AppConfig::default()
+        };
+        save_config(destination, &config).unwrap();
+
+        assert_eq!(load_config(destination), config);
+    }
+
+    #[test]
+    fn test_missing_fields_use_defaults() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE_NAME), r#"{ "rules": [] }"#).unwrap();
+        let config = load_config(temp_dir.path().to_str().unwrap());
+        assert_eq!(config.suggestion_auto_apply_confidence, 0.9);
+        assert_eq!(config.backup_retention, RetentionPolicy::default());
+        assert!(!config.compress_backups);
+    }
+
+    #[test]
+    fn test_config_not_utf8() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        // rule for "Café" saved in latin-1
+        let data = b"{ \"rules\": [{ \"condition\": { \"DescriptionContains\": { \"pattern\": \"Caf\xe9\" } }, \"category\": \"Sweets\" }] }";
+        fs::write(temp_dir.path().join(CONFIG_FILE_NAME), data).unwrap();
+
+        assert!(read_config(destination).unwrap_err().starts_with("Error reading config.json"));
+        assert!(save_config(destination, &AppConfig::default()).is_err());
+        assert_eq!(fs::read(temp_dir.path().join(CONFIG_FILE_NAME)).unwrap(), data.to_vec());
+    }
+
+    #[test]
+    fn test_load_config_broken_file() {
+        let temp_dir = tempdir().unwrap();
+        fs::write(temp_dir.path().join(CONFIG_FILE_NAME), "{ not json").unwrap();
+        let config = load_config(temp_dir.path().to_str().unwrap());
+        assert_eq!(config, AppConfig::default());
+        assert!(read_config(temp_dir.path().to_str().unwrap()).unwrap_err().starts_with("Error parsing config.json"));
+
+        // the broken file is kept as it is
+        assert!(save_config(temp_dir.path().to_str().unwrap(), &AppConfig::default()).is_err());
+        assert_eq!(fs::read_to_string(temp_dir.path().join(CONFIG_FILE_NAME)).unwrap(), "{ not json");
+    }
+}
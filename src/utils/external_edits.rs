@@ -0,0 +1,141 @@
+// notices when the workbook is touched outside the app before it is rewritten:
+//  open in Excel (~$expenses.xlsx) or LibreOffice (.~lock.expenses.xlsx#) -> refused, their next
+//  save would overwrite whatever the app writes
+//  changed since the app last wrote it -> asked first. whatever the update can't copy over is
+//  refused even with force, see xls_carry_over
+// hash of the workbook is recorded after every write. folders without one (older versions, just
+// migrated) compare with the newest backup, without any backup the workbook is unknown and asked
+// about once
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use sha2::{Digest, Sha256};
+
+use crate::utils::backup_store::latest_backup_record;
+use crate::utils::folder_file_utils::BACKUP_FOLDER;
+
+pub const WORKBOOK_HASH_FILE: &str = ".expenses.sha256"; // workbook as the app last wrote it
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExternalEdit {
+    OpenElsewhere(String), // lock file name
+    ChangedSinceLastWrite,
+    NotRecorded, // never written by this version of the app, no backup to compare with
+}
+
+impl ExternalEdit {
+    pub fn describe(&self) -> String {
+        match self {
+            ExternalEdit::OpenElsewhere(lock_file) => {
+                format!("The spreadsheet is open in another program ({}), close it first", lock_file)
+            }
+            ExternalEdit::ChangedSinceLastWrite => {
+                "The spreadsheet was edited outside the app since the last import".to_string()
+            }
+            ExternalEdit::NotRecorded => {
+                "The spreadsheet wasn't written by the app yet".to_string()
+            }
+        }
+    }
+
+    // the user can decide to write anyway, an open workbook never
+    pub fn can_force(&self) -> bool {
+        !matches!(self, ExternalEdit::OpenElsewhere(_))
+    }
+}
+
+// what happened to the workbook since the app last wrote it, None if nothing
+pub fn detect_external_edit(destination_path: &str, workbook_name: &str) -> Option<ExternalEdit> {
+    let lock_files = [format!("~${}", workbook_name), format!(".~lock.{}#", workbook_name)];
+    if let Some(lock_file) = lock_files.into_iter().find(|name| Path::new(destination_path).join(name).exists()) {
+        return Some(ExternalEdit::OpenElsewhere(lock_file));
+    }
+
+    let current = workbook_hash(&Path::new(destination_path).join(workbook_name)).ok()?;
+    match fs::read_to_string(Path::new(destination_path).join(WORKBOOK_HASH_FILE)) {
+        Ok(recorded) => (recorded.trim() != current).then_some(ExternalEdit::ChangedSinceLastWrite),
+        Err(_) => match latest_backup_record(&Path::new(destination_path).join(BACKUP_FOLDER)) {
+            Some(backup) => (backup.hash != current).then_some(ExternalEdit::ChangedSinceLastWrite),
+            None => Some(ExternalEdit::NotRecorded),
+        },
+    }
+}
+
+// Err with the reason when the workbook shouldn't be written, force accepts external changes
+// (never an open workbook)
+pub fn check_before_write(destination_path: &str, workbook_name: &str, force: bool) -> Result<(), String> {
+    match detect_external_edit(destination_path, workbook_name) {
+        Some(edit) if force && edit.can_force() => Ok(()),
+        Some(edit) => Err(edit.describe()),
+        None => Ok(()),
+    }
+}
+
+// remembers the workbook as the app wrote it
+pub fn record_workbook_hash(destination_path: &str, workbook_name: &str) -> io::Result<()> {
+    let hash = workbook_hash(&Path::new(destination_path).join(workbook_name))?;
+    fs::write(Path::new(destination_path).join(WORKBOOK_HASH_FILE), hash)
+}
+
+fn workbook_hash(path: &Path) -> io::Result<String> {
+    Ok(format!("{:x}", Sha256::digest(fs::read(path)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::backup_store::store_backup;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_changed_since_last_write() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("expenses.xlsx"), "written by the app").unwrap();
+
+        // nothing recorded yet and no backup, asked once
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), Some(ExternalEdit::NotRecorded));
+        assert!(check_before_write(destination, "expenses.xlsx", false).is_err());
+        assert!(check_before_write(destination, "expenses.xlsx", true).is_ok());
+
+        record_workbook_hash(destination, "expenses.xlsx").unwrap();
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), None);
+
+        fs::write(temp_dir.path().join("expenses.xlsx"), "edited by hand").unwrap();
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), Some(ExternalEdit::ChangedSinceLastWrite));
+        assert!(check_before_write(destination, "expenses.xlsx", false).is_err());
+        assert!(check_before_write(destination, "expenses.xlsx", true).is_ok());
+    }
+
+    #[test]
+    fn test_no_hash_compares_with_newest_backup() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        let workbook_path = temp_dir.path().join("expenses.xlsx");
+        let backup_folder = temp_dir.path().join(BACKUP_FOLDER);
+        fs::create_dir_all(&backup_folder).unwrap();
+        fs::write(&workbook_path, "as migrated").unwrap();
+        let timestamp = chrono::NaiveDate::from_ymd_opt(2024, 6, 2).unwrap().and_hms_opt(18, 40, 0).unwrap();
+        store_backup(&backup_folder, &workbook_path, timestamp, false).unwrap();
+
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), None);
+        fs::write(&workbook_path, "edited by hand after the upgrade").unwrap();
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), Some(ExternalEdit::ChangedSinceLastWrite));
+    }
+
+    #[test]
+    fn test_open_in_office() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("expenses.xlsx"), "workbook").unwrap();
+
+        fs::write(temp_dir.path().join("~$expenses.xlsx"), "").unwrap();
+        assert_eq!(detect_external_edit(destination, "expenses.xlsx"), Some(ExternalEdit::OpenElsewhere("~$expenses.xlsx".to_string())));
+        fs::remove_file(temp_dir.path().join("~$expenses.xlsx")).unwrap();
+
+        fs::write(temp_dir.path().join(".~lock.expenses.xlsx#"), "").unwrap();
+        // force doesn't help, the other program would overwrite the update
+        assert!(check_before_write(destination, "expenses.xlsx", true).is_err());
+    }
+}
@@ -1,18 +1,26 @@
 // created to help xls_handler
 
-use std::fs;
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
 use std::path::{ Path, PathBuf };
-use std::io;
-use std::time::{SystemTime, UNIX_EPOCH};
-use chrono::{DateTime, Utc};
+use std::io::{self, Write};
+
+use crate::utils::backup_store::{
+    backup_readable_path, latest_backup_record, load_backup_index, migrate_loose_backups, remove_backups, store_backup, stored_size,
+};
+use crate::utils::config::load_config;
+use crate::utils::external_edits::record_workbook_hash;
+use crate::utils::retention::{backups_to_delete, BackupEntry, Clock, RetentionPolicy, SystemClock};
 
 // Constants for folder names
-const TMP_FOLDER: &str = "tmp";
-const BACKUP_FOLDER: &str = "backup";
-const MAX_BACKUPS: usize = 3; // including ds store))
+pub const TMP_FOLDER: &str = "tmp";
+pub const BACKUP_FOLDER: &str = "backup";
+const PINNED_BACKUPS_FILE: &str = "pinned.txt"; // in backup folder, one file name per line
+const LEGACY_WORKBOOK_NAME: &str = "expensesGenerated.xlsx"; // written by older versions on every update
 
-// Function to set up and clean the folder structure
-pub fn prepare_folder_structure(base_path: &str, res_file:&str) -> io::Result<()> {
+// Function to set up and clean the folder structure, res_file is backed up
+// returns name of the backup holding res_file as it is now, None when there is no res_file
+pub fn prepare_folder_structure(base_path: &str, res_file:&str) -> io::Result<Option<String>> {
 
     let base_folder = Path::new(base_path);
     let tmp_folder = base_folder.join(TMP_FOLDER);
@@ -22,39 +30,115 @@ pub fn prepare_folder_structure(base_path: &str, res_file:&str) -> io::Result<()
     ensure_folder_exists(&base_folder)?;
     ensure_folder_exists(&tmp_folder)?;
     ensure_folder_exists(&backup_folder)?;
+    migrate_to_single_workbook(base_path, res_file)?;
 
     // Clear the tmp folder it also checks if folder exists
     clear_tmp_folder(&tmp_folder)?;
 
     // Manage the backup folder (delete old backups) it also checks if folder exists
-    manage_backups(&backup_folder, &base_folder.join(res_file))?;
+    let config = load_config(base_path);
+    manage_backups(&backup_folder, &base_folder.join(res_file), &config.backup_retention, config.compress_backups, &SystemClock)
+}
 
-    Ok(())
+// older versions read the latest backup and wrote expensesGenerated.xlsx, which the next
+// update moved into backup folder. now res_file is the one workbook the user edits and the
+// app updates, backups are only snapshots of it. it is created from the newest of the two
+pub fn migrate_to_single_workbook(base_path: &str, res_file: &str) -> io::Result<()> {
+    let workbook_path = Path::new(base_path).join(res_file);
+    if workbook_path.exists() {
+        return Ok(());
+    }
+
+    let legacy_path = Path::new(base_path).join(LEGACY_WORKBOOK_NAME);
+    if legacy_path.exists() {
+        println!("Moving {} to {}", legacy_path.display(), workbook_path.display());
+        fs::rename(legacy_path, workbook_path)?;
+        return record_workbook_hash(base_path, res_file);
+    }
+
+    if let Some((name, backup_path)) = get_latest_backup_entry(base_path) {
+        println!("Creating {} from backup {}", workbook_path.display(), name);
+        let partial_path = partial_file_path(&workbook_path);
+        fs::copy(backup_path, &partial_path)?;
+        replace_with_partial_file(&partial_path, &workbook_path)?;
+        return record_workbook_hash(base_path, res_file);
+    }
+    Ok(()) // nothing to start from yet
 }
 
 
-// This one is AI generated
-// returns path to latest backup
+// returns path to latest backup, by the time recorded in the backup index
 pub fn get_latest_backup(destination_path: &str) -> Option<PathBuf> {
-    let backup_dir = Path::new(destination_path).join(BACKUP_FOLDER);
-
-    // Read the directory and collect the latest file
-    let latest_backup = fs::read_dir(backup_dir)
-        .ok()?
-        .filter_map(Result::ok) // Ignore any errors while reading directory entries
-        .filter( |entry| {
-            entry.file_type().map(|ft| ft.is_file()).unwrap_or(false)
-            && entry.file_name().to_string_lossy().ends_with(".xlsx") // only look for .xlsx backups
-        }) // Filter only files
-        .filter_map(|entry| {
-            let path = entry.path();
-            let metadata = entry.metadata().ok()?;
-            Some((path, metadata.modified().ok()?))
-        })
-        .max_by_key(|(_, modified_time)| *modified_time) // Get the entry with the latest modification time
-        .map(|(path, _)| path); // Extract the path
+    get_latest_backup_entry(destination_path).map(|(_, path)| path)
+}
+
+// name and readable path of the latest backup
+pub fn get_latest_backup_entry(destination_path: &str) -> Option<(String, PathBuf)> {
+    let backup_folder = Path::new(destination_path).join(BACKUP_FOLDER);
+    if let Err(e) = migrate_loose_backups(&backup_folder) {
+        println!("Error moving old backups into the backup store: {}", e);
+    }
+
+    let record = latest_backup_record(&backup_folder)?;
+    match backup_readable_path(&backup_folder, &record) {
+        Ok(path) => Some((record.name, path)),
+        Err(e) => {
+            println!("Error reading backup {}: {}", record.name, e);
+            None
+        }
+    }
+}
+
+// marks the backup so retention never deletes it
+pub fn pin_backup(backup_folder: &Path, name: &str) -> io::Result<()> {
+    if pinned_backups(backup_folder).contains(name) {
+        return Ok(());
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(backup_folder.join(PINNED_BACKUPS_FILE))?;
+    writeln!(file, "{}", name)
+}
+
+// file names of pinned backups
+pub fn pinned_backups(backup_folder: &Path) -> HashSet<String> {
+    fs::read_to_string(backup_folder.join(PINNED_BACKUPS_FILE))
+        .map(|data| data.lines().map(|line| line.trim().to_string()).filter(|line| !line.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+// opens the file with the app the system uses for it (Excel, Numbers, LibreOffice...)
+pub fn open_with_default_app(path: &Path) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    command.arg(path).spawn().map(|_| ())
+}
 
-    latest_backup
+// where a file is written before it replaces target, same folder so the rename
+// can't cross file systems
+pub fn partial_file_path(target: &Path) -> PathBuf {
+    let name = target.file_name().unwrap_or_default().to_string_lossy();
+    target.with_file_name(format!(".partial_{}", name))
+}
+
+// puts a completely written file in place of target: flushed to disk first, then renamed,
+// so target is always either the old or the new file
+pub fn replace_with_partial_file(partial: &Path, target: &Path) -> io::Result<()> {
+    fs::File::open(partial)?.sync_all()?;
+    fs::rename(partial, target)?;
+
+    // the rename itself is only on disk once the folder is synced (folders can't be opened on windows)
+    #[cfg(unix)]
+    {
+        let folder = target.parent().filter(|folder| !folder.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        fs::File::open(folder)?.sync_all()?;
+    }
+    Ok(())
 }
 
 // Utility function to ensure a folder exists, creating it if necessary
@@ -78,27 +162,20 @@ fn clear_tmp_folder(tmp_folder: &Path) -> io::Result<()> {
     Ok(())
 }
 
-// Function to manage backup files, deleting the oldest if more than MAX_BACKUPS exist
-fn manage_backups(backup_folder: &Path, old_file_path: &Path) -> io::Result<()> {
+// Function to manage backup files, old ones are deleted by the retention policy
+// returns name of the backup holding old_file_path
+fn manage_backups(backup_folder: &Path, old_file_path: &Path, retention: &RetentionPolicy, compress: bool, clock: &dyn Clock) -> io::Result<Option<String>> {
 
     ensure_folder_exists(backup_folder)?;
+    migrate_loose_backups(backup_folder)?;
 
     // Check if the old file exists before proceeding with storing another backup
+    // the file itself stays where it is, it is updated in place
+    let mut backup_name = None;
     if old_file_path.exists() {
-        // Construct the backup filename with timestamp or unique identifier
-        let timestamp = get_minimalistic_timestamp();
-        let backup_file_name = format!("backup_{}.xlsx", timestamp);
-        let backup_file_path = backup_folder.join(backup_file_name);
-
-        // Copy the new main file to the backup folder
-        match fs::copy(old_file_path, &backup_file_path) {
-            Ok(_) => {
-                // Only remove the old file if the copy was successful
-                if let Err(e) = fs::remove_file(old_file_path) {
-                    println!("Error removing old file: {}", e);
-                    return Err(e);
-                }
-            },
+        // nothing is stored when the file is the same as the latest backup
+        match store_backup(backup_folder, old_file_path, clock.now(), compress) {
+            Ok(record) => backup_name = Some(record.name),
             Err(e) => {
                 println!("Error copying file to backup: {}", e);
                 return Err(e);
@@ -106,43 +183,24 @@ fn manage_backups(backup_folder: &Path, old_file_path: &Path) -> io::Result<()>
         }
     }
 
-    // Collect all backup files
-    let mut backup_files: Vec<_> = fs::read_dir(backup_folder)?
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file()
-            && path.file_name().unwrap_or_default().to_string_lossy().ends_with(".xlsx") // count only .xlsx files
-            )
+    // backups sharing a stored file count its size once, for the newest of them
+    let pinned = pinned_backups(backup_folder);
+    let mut index = load_backup_index(backup_folder);
+    index.sort_by_key(|record| std::cmp::Reverse(record.timestamp));
+    let mut counted: HashSet<String> = HashSet::new();
+    let backups: Vec<BackupEntry> = index
+        .iter()
+        .map(|record| BackupEntry {
+            name: record.name.clone(),
+            timestamp: record.timestamp,
+            size: if counted.insert(record.hash.clone()) { stored_size(backup_folder, record) } else { 0 },
+            pinned: pinned.contains(&record.name),
+        })
         .collect();
 
-    // Sort the files by modification time (oldest first)
-    backup_files.sort_by_key(|path| {
-        fs::metadata(path)
-            .and_then(|metadata| metadata.modified())
-            .unwrap_or(SystemTime::UNIX_EPOCH)
-    });
-
-    // If there are more than MAX_BACKUPS, delete the oldest
-    while backup_files.len() > MAX_BACKUPS {
-        if let Some(oldest_backup) = backup_files.first() {
-            fs::remove_file(oldest_backup)?;
-            backup_files.remove(0);
-        }
-    }
-
-    Ok(())
-}
-
-
-// Function to format the current time as a minimalistic timestamp
-fn get_minimalistic_timestamp() -> String {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-
-    let seconds = now.as_secs();
-    let date_time: DateTime<Utc> = DateTime::from_timestamp(seconds as i64, 0).expect("DateTimeErr");
-    date_time.format("%Y%m%d_%H%M%S").to_string()
+    // delete whatever the retention policy doesn't keep (never the newest one, which is backup_name)
+    remove_backups(backup_folder, &backups_to_delete(&backups, retention, clock))?;
+    Ok(backup_name)
 }
 
 
@@ -196,45 +254,36 @@ mod tests {
         assert_eq!(entries.len(), 0);
     }
 
-    // Test that old backups are deleted if there are more than MAX_BACKUPS
+    // Test that old backups are deleted if there are more than keep_last (3 by default)
     #[test]
     fn test_backup_management() {
         let temp_dir = tempdir().unwrap();
         let base_path = temp_dir.path().to_str().unwrap();
         let backup_folder = temp_dir.path().join(BACKUP_FOLDER);
-        let file4_path = backup_folder.join("backup_4.xlsx");
 
         // Specify a dummy resource file name it is not going to be used in this test
         let resource_file_name = "res_file.xlsx";
 
-        // Create backup folder and add 5 backup files
+        // Create backup folder and add 5 backup files from before the backup store
         fs::create_dir(&backup_folder).unwrap();
         for i in 0..5 {
             let mut file = File::create(backup_folder.join(format!("backup_{}.xlsx", i))).unwrap();
-            file.set_len(1).unwrap();
-            write!(file, "backup data").unwrap();
+            write!(file, "backup data {}", i).unwrap();
             std::thread::sleep(Duration::from_millis(200));
         }
 
         // Run the function
         prepare_folder_structure(base_path, &resource_file_name).unwrap();
 
-        // Check that only the newest MAX_BACKUPS files remain
-        let entries: Vec<_> = fs::read_dir(backup_folder).unwrap().collect();
-        assert_eq!(entries.len(), MAX_BACKUPS);
-
-        // Check that the remaining files are the newest ones
-        let backup_files: Vec<_> = entries.into_iter().map(|entry| entry.unwrap().path()).collect();
-        let filenames: Vec<_> = backup_files.iter().map(|path| path.file_name().unwrap().to_str().unwrap()).collect();
-        assert!(filenames.contains(&"backup_2.xlsx"));
-        assert!(filenames.contains(&"backup_3.xlsx"));
-        assert!(filenames.contains(&"backup_4.xlsx"));
+        // Check that only the newest keep_last backups remain, moved into the store
+        let names: Vec<String> = load_backup_index(&backup_folder).into_iter().map(|record| record.name).collect();
+        assert_eq!(names, vec!["backup_2.xlsx", "backup_3.xlsx", "backup_4.xlsx"]);
+        assert!(!backup_folder.join("backup_4.xlsx").exists());
 
         // Call the function and get the latest backup file
-        let latest_backup = get_latest_backup(temp_dir.path().to_str().unwrap());
-
-        // Assert that the latest backup is indeed file3
-        assert_eq!(latest_backup, Some(file4_path));
+        let (name, latest_backup) = get_latest_backup_entry(base_path).unwrap();
+        assert_eq!(name, "backup_4.xlsx");
+        assert_eq!(fs::read_to_string(latest_backup).unwrap(), "backup data 4");
     }
 
     #[test]
@@ -250,24 +299,96 @@ mod tests {
         fs::create_dir(&backup_folder).unwrap();
 
         // Call manage_backups to create a backup
-        let result = manage_backups(&backup_folder, &old_file_path);
+        let result = manage_backups(&backup_folder, &old_file_path, &RetentionPolicy::default(), false, &SystemClock);
         assert!(result.is_ok());
 
-        // Check if the backup file was created
-        let backup_files: Vec<_> = fs::read_dir(&backup_folder)
-            .unwrap()
-            .filter_map(Result::ok)
-            .map(|entry| entry.path())
-            .collect();
-
-        assert!(!backup_files.is_empty(), "Backup files should exist.");
+        // Check if the backup was created
+        assert_eq!(load_backup_index(&backup_folder).len(), 1, "Backup should exist.");
 
-        // Ensure the old file has been deleted
-        assert!(!old_file_path.exists(), "Old file should have been removed.");
+        // the old file stays until an update replaces it
+        assert!(old_file_path.exists(), "Old file should not have been removed.");
 
-        // Optionally, you can check the content of the backup file
-        let backup_file_path = backup_folder.join(backup_files[0].file_name().unwrap());
-        let content = fs::read_to_string(backup_file_path).unwrap();
+        // Check the content of the backup file
+        let content = fs::read_to_string(get_latest_backup(temp_dir.path().to_str().unwrap()).unwrap()).unwrap();
         assert_eq!(content, "Test data", "Backup file content should match the old file.");
+
+        // the same file again is not stored twice
+        manage_backups(&backup_folder, &old_file_path, &RetentionPolicy::default(), false, &SystemClock).unwrap();
+        assert_eq!(load_backup_index(&backup_folder).len(), 1);
+    }
+
+    #[test]
+    fn test_replace_with_partial_file() {
+        let temp_dir = tempdir().unwrap();
+        let target = temp_dir.path().join("expensesGenerated.xlsx");
+        fs::write(&target, "old").unwrap();
+
+        let partial = partial_file_path(&target);
+        assert_eq!(partial, temp_dir.path().join(".partial_expensesGenerated.xlsx"));
+        fs::write(&partial, "new").unwrap();
+        replace_with_partial_file(&partial, &target).unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert!(!partial.exists());
+    }
+
+    #[test]
+    fn test_migrate_to_single_workbook() {
+        // workbook written by an older version becomes the workbook
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join(LEGACY_WORKBOOK_NAME), "generated").unwrap();
+        migrate_to_single_workbook(base_path, "expenses.xlsx").unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("expenses.xlsx")).unwrap(), "generated");
+        assert!(!temp_dir.path().join(LEGACY_WORKBOOK_NAME).exists());
+
+        // only backups: the latest one is copied, it stays a backup
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path().to_str().unwrap();
+        let backup_folder = temp_dir.path().join(BACKUP_FOLDER);
+        fs::create_dir(&backup_folder).unwrap();
+        fs::write(backup_folder.join("backup_20240501_100000.xlsx"), "older").unwrap();
+        fs::write(backup_folder.join("backup_20240502_100000.xlsx"), "newer").unwrap();
+        migrate_to_single_workbook(base_path, "expenses.xlsx").unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("expenses.xlsx")).unwrap(), "newer");
+        assert_eq!(load_backup_index(&backup_folder).len(), 2);
+
+        // an existing workbook is left alone
+        fs::write(temp_dir.path().join(LEGACY_WORKBOOK_NAME), "generated").unwrap();
+        migrate_to_single_workbook(base_path, "expenses.xlsx").unwrap();
+        assert_eq!(fs::read_to_string(temp_dir.path().join("expenses.xlsx")).unwrap(), "newer");
+    }
+
+    #[test]
+    fn test_compressed_backups() {
+        let temp_dir = tempdir().unwrap();
+        let base_path = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join("config.json"), r#"{ "compress_backups": true }"#).unwrap();
+        fs::write(temp_dir.path().join("res_file.xlsx"), "workbook ".repeat(50)).unwrap();
+
+        prepare_folder_structure(base_path, "res_file.xlsx").unwrap();
+
+        let latest_backup = get_latest_backup(base_path).unwrap();
+        assert!(latest_backup.starts_with(temp_dir.path().join(TMP_FOLDER)));
+        assert_eq!(fs::read_to_string(latest_backup).unwrap(), "workbook ".repeat(50));
+    }
+
+    #[test]
+    fn test_pinned_backups_survive_retention() {
+        let temp_dir = tempdir().unwrap();
+        let backup_folder = temp_dir.path().join(BACKUP_FOLDER);
+        fs::create_dir(&backup_folder).unwrap();
+        for day in 1..=4 {
+            fs::write(backup_folder.join(format!("backup_202405{:02}_100000.xlsx", day)), format!("day {}", day)).unwrap();
+        }
+        pin_backup(&backup_folder, "backup_20240501_100000.xlsx").unwrap();
+        pin_backup(&backup_folder, "backup_20240501_100000.xlsx").unwrap(); // only once in the file
+        assert_eq!(pinned_backups(&backup_folder).len(), 1);
+
+        let policy = RetentionPolicy { keep_last: 1, ..RetentionPolicy::default() };
+        manage_backups(&backup_folder, &temp_dir.path().join("missing.xlsx"), &policy, false, &SystemClock).unwrap();
+
+        let names: Vec<String> = load_backup_index(&backup_folder).into_iter().map(|record| record.name).collect();
+        assert_eq!(names, vec!["backup_20240501_100000.xlsx", "backup_20240504_100000.xlsx"]);
     }
 }
@@ -0,0 +1,266 @@
+// which backups to keep, configured in config.json:
+//  "backup_retention": { "keep_last": 3, "keep_daily": 7, "keep_weekly": 4, "keep_monthly": 12,
+//                        "keep_yearly": 5, "max_total_bytes": 50000000, "protect_new_year_backups": true }
+// keep_daily = 7 keeps the newest backup of each of the last 7 days (counted from now), same
+// for weeks, months and years (grandfather-father-son). max_total_bytes then drops the oldest
+// kept backups until the rest fits. pinned backups (taken before the first import into a new
+// year) are never deleted
+
+use std::collections::HashSet;
+use chrono::{Datelike, Duration, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// source of the current time, so the policy can be tested
+pub trait Clock {
+    fn now(&self) -> NaiveDateTime; // UTC, same as backup timestamps
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveDateTime {
+        Utc::now().naive_utc()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetentionPolicy {
+    pub keep_last: usize, // at least the newest one is always kept
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+    pub keep_monthly: usize,
+    pub keep_yearly: usize,
+    pub max_total_bytes: Option<u64>, // pinned backups count, but are never deleted
+    pub protect_new_year_backups: bool,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        // same as the old fixed MAX_BACKUPS = 3
+        RetentionPolicy {
+            keep_last: 3,
+            keep_daily: 0,
+            keep_weekly: 0,
+            keep_monthly: 0,
+            keep_yearly: 0,
+            max_total_bytes: None,
+            protect_new_year_backups: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BackupEntry {
+    pub name: String,
+    pub timestamp: NaiveDateTime,
+    pub size: u64,
+    pub pinned: bool,
+}
+
+// names of backups the policy doesn't keep
+pub fn backups_to_delete(backups: &[BackupEntry], policy: &RetentionPolicy, clock: &dyn Clock) -> Vec<String> {
+    let mut sorted: Vec<&BackupEntry> = backups.iter().collect();
+    // newest first, name breaks ties (backup_x_1 is newer than backup_x)
+    sorted.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.name.cmp(&a.name)));
+
+    let now = clock.now();
+    let is_pinned = |backup: &BackupEntry| policy.protect_new_year_backups && backup.pinned;
+    let mut keep: HashSet<&str> = HashSet::new();
+
+    for backup in sorted.iter().take(policy.keep_last.max(1)) {
+        keep.insert(&backup.name);
+    }
+    for backup in sorted.iter().filter(|backup| is_pinned(backup)) {
+        keep.insert(&backup.name);
+    }
+
+    // newest backup of every period within the window
+    let generations = [
+        (policy.keep_daily, Period::Day),
+        (policy.keep_weekly, Period::Week),
+        (policy.keep_monthly, Period::Month),
+        (policy.keep_yearly, Period::Year),
+    ];
+    for (count, period) in generations {
+        let periods = last_periods(&now, count, period);
+        let mut seen: HashSet<(i32, u32)> = HashSet::new();
+        for backup in &sorted {
+            let backup_period = period_of(&backup.timestamp, period);
+            if periods.contains(&backup_period) && seen.insert(backup_period) {
+                keep.insert(&backup.name);
+            }
+        }
+    }
+
+    // size limit: oldest kept backups go first, the newest one and pinned ones stay
+    if let Some(max_total_bytes) = policy.max_total_bytes {
+        let mut total: u64 = sorted.iter().filter(|backup| keep.contains(backup.name.as_str())).map(|backup| backup.size).sum();
+        for backup in sorted.iter().skip(1).rev() {
+            if total <= max_total_bytes {
+                break;
+            }
+            if keep.contains(backup.name.as_str()) && !is_pinned(backup) {
+                keep.remove(backup.name.as_str());
+                total -= backup.size;
+            }
+        }
+    }
+
+    sorted
+        .iter()
+        .filter(|backup| !keep.contains(backup.name.as_str()))
+        .map(|backup| backup.name.clone())
+        .collect()
+}
+
+#[derive(Clone, Copy)]
+enum Period {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+// calendar period the time falls into, as (year, number within the year)
+fn period_of(time: &NaiveDateTime, period: Period) -> (i32, u32) {
+    match period {
+        Period::Day => (time.year(), time.ordinal()),
+        Period::Week => (time.iso_week().year(), time.iso_week().week()),
+        Period::Month => (time.year(), time.month()),
+        Period::Year => (time.year(), 0),
+    }
+}
+
+// current period and the count - 1 periods before it: today, yesterday... / this month, last month...
+fn last_periods(now: &NaiveDateTime, count: usize, period: Period) -> Vec<(i32, u32)> {
+    (0..count as i64)
+        .map(|back| match period {
+            Period::Day => period_of(&(*now - Duration::days(back)), period),
+            Period::Week => period_of(&(*now - Duration::weeks(back)), period),
+            Period::Month => {
+                // months differ in length, so they are counted instead of subtracted
+                let months = now.year() as i64 * 12 + now.month0() as i64 - back;
+                (months.div_euclid(12) as i32, months.rem_euclid(12) as u32 + 1)
+            }
+            Period::Year => (now.year() - back as i32, 0),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    struct FixedClock(NaiveDateTime);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> NaiveDateTime {
+            self.0
+        }
+    }
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+    }
+
+    fn backup(timestamp: NaiveDateTime) -> BackupEntry {
+        BackupEntry {
+            name: format!("backup_{}.xlsx", timestamp.format("%Y%m%d_%H%M%S")),
+            timestamp,
+            size: 100,
+            pinned: false,
+        }
+    }
+
+    fn policy() -> RetentionPolicy {
+        RetentionPolicy { keep_last: 1, ..RetentionPolicy::default() }
+    }
+
+    // clock on Wednesday 2024-05-15 noon
+    fn clock() -> FixedClock {
+        FixedClock(at(2024, 5, 15, 12))
+    }
+
+    #[test]
+    fn test_keep_last() {
+        let backups: Vec<BackupEntry> = (1..=5).map(|day| backup(at(2024, 5, day, 10))).collect();
+        let policy = RetentionPolicy { keep_last: 3, ..RetentionPolicy::default() };
+
+        let deleted = backups_to_delete(&backups, &policy, &clock());
+        assert_eq!(deleted, vec![backups[1].name.clone(), backups[0].name.clone()]);
+
+        // the newest backup survives even with keep_last = 0
+        let policy = RetentionPolicy { keep_last: 0, ..RetentionPolicy::default() };
+        assert_eq!(backups_to_delete(&backups, &policy, &clock()).len(), 4);
+    }
+
+    #[test]
+    fn test_keep_daily() {
+        let backups = vec![
+            backup(at(2024, 5, 15, 9)),
+            backup(at(2024, 5, 15, 8)), // same day, older
+            backup(at(2024, 5, 14, 20)),
+            backup(at(2024, 5, 13, 20)),
+            backup(at(2024, 5, 10, 20)), // outside of 3 days
+        ];
+        let policy = RetentionPolicy { keep_daily: 3, ..policy() };
+
+        let deleted = backups_to_delete(&backups, &policy, &clock());
+        assert_eq!(deleted, vec![backups[1].name.clone(), backups[4].name.clone()]);
+    }
+
+    #[test]
+    fn test_keep_weekly_monthly_yearly() {
+        let backups = vec![
+            backup(at(2024, 5, 14, 9)),  // this week
+            backup(at(2024, 5, 8, 9)),   // last week
+            backup(at(2024, 5, 7, 9)),   // last week, older
+            backup(at(2024, 4, 2, 9)),   // last month
+            backup(at(2024, 3, 30, 9)),  // two months ago
+            backup(at(2023, 12, 31, 9)), // last year
+            backup(at(2021, 6, 1, 9)),   // too old for everything
+        ];
+        let policy = RetentionPolicy { keep_weekly: 2, keep_monthly: 2, keep_yearly: 2, ..policy() };
+
+        let deleted = backups_to_delete(&backups, &policy, &clock());
+        assert_eq!(deleted, vec![backups[2].name.clone(), backups[4].name.clone(), backups[6].name.clone()]);
+    }
+
+    #[test]
+    fn test_months_across_year_boundary() {
+        assert_eq!(last_periods(&at(2024, 2, 10, 0), 3, Period::Month), vec![(2024, 2), (2024, 1), (2023, 12)]);
+
+        // March 31st minus 31 days would skip February
+        let backups = vec![backup(at(2024, 3, 31, 9)), backup(at(2024, 2, 29, 9))];
+        let policy = RetentionPolicy { keep_monthly: 2, ..policy() };
+        assert!(backups_to_delete(&backups, &policy, &FixedClock(at(2024, 3, 31, 12))).is_empty());
+    }
+
+    #[test]
+    fn test_max_total_bytes() {
+        let backups: Vec<BackupEntry> = (1..=5).map(|day| backup(at(2024, 5, day, 10))).collect();
+        let policy = RetentionPolicy { keep_last: 5, max_total_bytes: Some(250), ..RetentionPolicy::default() };
+
+        let deleted = backups_to_delete(&backups, &policy, &clock());
+        assert_eq!(deleted, vec![backups[2].name.clone(), backups[1].name.clone(), backups[0].name.clone()]);
+
+        // the newest backup is kept even when it alone is too big
+        let policy = RetentionPolicy { keep_last: 5, max_total_bytes: Some(10), ..RetentionPolicy::default() };
+        assert_eq!(backups_to_delete(&backups, &policy, &clock()).len(), 4);
+    }
+
+    #[test]
+    fn test_pinned_backups_are_never_deleted() {
+        let mut backups: Vec<BackupEntry> = (1..=4).map(|day| backup(at(2024, 5, day, 10))).collect();
+        backups[0].pinned = true;
+        let policy = RetentionPolicy { max_total_bytes: Some(100), ..policy() };
+
+        let deleted = backups_to_delete(&backups, &policy, &clock());
+        assert_eq!(deleted, vec![backups[2].name.clone(), backups[1].name.clone()]);
+
+        let policy = RetentionPolicy { protect_new_year_backups: false, ..policy };
+        assert_eq!(backups_to_delete(&backups, &policy, &clock()).len(), 3);
+    }
+}
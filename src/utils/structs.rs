@@ -1,9 +1,44 @@
 use std::collections::HashMap;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 
 // struct (me) that is used to correctly locate and insert data into excel
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MonthExpenses {
     pub year: i64, // because of Microsoft Excel
     pub month: String,
     pub expenses_data: HashMap<String, f64>,
-}
\ No newline at end of file
+    // by income source, written into the income area of the year block. money in is positive
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub income_data: HashMap<String, f64>,
+}
+
+// direction of the money flow as reported by the bank
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CreditDebit {
+    Credit, // money in (refunds, income)
+    Debit,  // money out (spending)
+}
+
+// single booked entry extracted from a bank statement
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Transaction {
+    pub booking_date: NaiveDate,
+    pub amount: f64, // always positive, the sign is kept in credit_debit
+    pub credit_debit: CreditDebit,
+    pub description: String, // remittance info
+    pub counterparty: Option<String>,
+    pub category: Option<String>, // None until the transaction is categorised
+}
+
+// spreadsheet cell written during an update, previous value is kept so it can be undone
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub row: u32, // zero based, like in calamine and xlsxwriter
+    pub col: u32,
+    pub year: i64,
+    pub month: String,
+    pub category: String,
+    pub previous: Option<f64>, // None if the cell was empty
+    pub value: f64,
+}
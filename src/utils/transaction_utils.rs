@@ -0,0 +1,147 @@
+// helpers shared by the bank statement importers (camt.053, MT940)
+
+use std::collections::{BTreeMap, HashMap};
+use chrono::Datelike;
+
+use crate::utils::structs::{CreditDebit, MonthExpenses, Transaction};
+
+// bucket for transactions that don't have a category yet
+pub const UNCATEGORISED: &str = "Uncategorised";
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+// 1 -> "January", names match the ones used in the spreadsheet
+pub fn month_name_from_number(month: u32) -> Option<&'static str> {
+    if (1..=12).contains(&month) {
+        return Some(MONTH_NAMES[month as usize - 1]);
+    }
+    None
+}
+
+// "January" -> 1
+pub fn month_number_from_name(month: &str) -> Option<u32> {
+    MONTH_NAMES.iter().position(|name| *name == month).map(|i| i as u32 + 1)
+}
+
+// groups transactions into one MonthExpenses per month (ordered by date)
+// debit entries are spending, credit entries are refunds and reduce the category total.
+// credits that look like income are left out by the preview before they get here
+pub fn transactions_into_me_structs(transactions: &[Transaction]) -> Vec<MonthExpenses> {
+    let mut by_month: BTreeMap<(i64, u32), HashMap<String, f64>> = BTreeMap::new();
+
+    for transaction in transactions {
+        let key = (transaction.booking_date.year() as i64, transaction.booking_date.month());
+        let category = transaction.category.clone().unwrap_or_else(|| UNCATEGORISED.to_string());
+
+        let signed_amount = match transaction.credit_debit {
+            CreditDebit::Debit => transaction.amount,
+            CreditDebit::Credit => -transaction.amount,
+        };
+
+        *by_month.entry(key).or_default().entry(category).or_insert(0.0) += signed_amount;
+    }
+
+    by_month
+        .into_iter()
+        .map(|((year, month), mut expenses_data)| {
+            // Round each total to two decimal places, same as .txt import
+            for value in expenses_data.values_mut() {
+                *value = (*value * 100.0).round() / 100.0;
+            }
+            MonthExpenses {
+                year,
+                month: month_name_from_number(month).unwrap_or_default().to_string(),
+                expenses_data,
+                income_data: HashMap::new(),
+            }
+        })
+        .collect()
+}
+
+// moves income sources out of the expenses, with the sign turned around: credits (salary)
+// are positive income, debits booked on an income source reduce it
+pub fn split_income(me: &mut MonthExpenses, income_sources: &[String]) {
+    for source in income_sources {
+        if let Some(value) = me.expenses_data.remove(source) {
+            *me.income_data.entry(source.clone()).or_insert(0.0) -= value;
+        }
+    }
+}
+
+// bank statements use both "12.50" and "12,50"
+pub fn parse_statement_amount(amount: &str) -> Option<f64> {
+    amount.trim().replace(',', ".").parse::<f64>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn transaction(date: (i32, u32, u32), amount: f64, credit_debit: CreditDebit, category: Option<&str>) -> Transaction {
+        Transaction {
+            booking_date: NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap(),
+            amount,
+            credit_debit,
+            description: "test".to_string(),
+            counterparty: None,
+            category: category.map(|c| c.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_month_name_conversions() {
+        assert_eq!(month_name_from_number(1), Some("January"));
+        assert_eq!(month_name_from_number(12), Some("December"));
+        assert_eq!(month_name_from_number(0), None);
+        assert_eq!(month_name_from_number(13), None);
+        assert_eq!(month_number_from_name("October"), Some(10));
+        assert_eq!(month_number_from_name("Octobre"), None);
+    }
+
+    #[test]
+    fn test_transactions_into_me_structs() {
+        let transactions = vec![
+            transaction((2024, 2, 3), 10.0, CreditDebit::Debit, Some("Groceries")),
+            transaction((2024, 1, 5), 12.5, CreditDebit::Debit, Some("Groceries")),
+            transaction((2024, 1, 9), 2.25, CreditDebit::Credit, Some("Groceries")),
+            transaction((2024, 1, 20), 7.1, CreditDebit::Debit, None),
+        ];
+
+        let result = transactions_into_me_structs(&transactions);
+        assert_eq!(result.len(), 2);
+
+        // months come out in chronological order
+        assert_eq!(result[0].month, "January");
+        assert_eq!(result[0].year, 2024);
+        assert_eq!(result[0].expenses_data.get("Groceries"), Some(&10.25));
+        assert_eq!(result[0].expenses_data.get(UNCATEGORISED), Some(&7.1));
+
+        assert_eq!(result[1].month, "February");
+        assert_eq!(result[1].expenses_data.get("Groceries"), Some(&10.0));
+    }
+
+    #[test]
+    fn test_split_income() {
+        let transactions = vec![
+            transaction((2024, 1, 25), 3000.0, CreditDebit::Credit, Some("Salary")),
+            transaction((2024, 1, 26), 50.0, CreditDebit::Debit, Some("Salary")), // corrected payroll
+            transaction((2024, 1, 5), 12.5, CreditDebit::Debit, Some("Groceries")),
+        ];
+        let mut me = transactions_into_me_structs(&transactions).remove(0);
+        split_income(&mut me, &["Salary".to_string(), "Interest".to_string()]);
+
+        assert_eq!(me.income_data, HashMap::from([("Salary".to_string(), 2950.0)]));
+        assert_eq!(me.expenses_data, HashMap::from([("Groceries".to_string(), 12.5)]));
+    }
+
+    #[test]
+    fn test_parse_statement_amount() {
+        assert_eq!(parse_statement_amount("12,50"), Some(12.5));
+        assert_eq!(parse_statement_amount(" 1000.01 "), Some(1000.01));
+        assert_eq!(parse_statement_amount("abc"), None);
+    }
+}
@@ -0,0 +1,105 @@
+// advisory lock on a destination folder, so two app instances (or the GUI and the CLI)
+// never update the same workbook at once. the lock file holds pid and time of whoever
+// has it and is removed when the lock is dropped. a lock left behind by a crash is taken
+// over once it is older than STALE_LOCK_MINUTES, no update takes that long
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Duration, Utc};
+
+pub const LOCK_FILE_NAME: &str = ".expenses.lock";
+const STALE_LOCK_MINUTES: i64 = 10;
+
+#[derive(Debug)]
+pub struct WorkbookLock {
+    path: PathBuf,
+}
+
+impl WorkbookLock {
+    // fails right away when someone else holds the lock, there is no waiting
+    pub fn acquire(destination_path: &str) -> Result<WorkbookLock, String> {
+        fs::create_dir_all(destination_path).map_err(|e| format!("Cannot create destination folder: {}", e))?;
+        let path = Path::new(destination_path).join(LOCK_FILE_NAME);
+
+        match create_lock_file(&path) {
+            Ok(()) => return Ok(WorkbookLock { path }),
+            Err(e) if e.kind() != io::ErrorKind::AlreadyExists => return Err(format!("Cannot create lock file: {}", e)),
+            Err(_) => {}
+        }
+
+        let holder = fs::read_to_string(&path).unwrap_or_default();
+        if !is_stale(&holder, Utc::now()) {
+            return Err(format!("The spreadsheet is being updated by another instance ({}), try again later", holder.trim()));
+        }
+
+        println!("Taking over stale lock: {}", holder.trim());
+        let _ = fs::remove_file(&path);
+        // whoever else noticed the stale lock could be faster
+        create_lock_file(&path).map_err(|_| "The spreadsheet is being updated by another instance, try again later".to_string())?;
+        Ok(WorkbookLock { path })
+    }
+}
+
+impl Drop for WorkbookLock {
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            println!("Error removing lock file: {}", e);
+        }
+    }
+}
+
+// create_new makes taking the lock a single step, only one instance can succeed
+fn create_lock_file(path: &Path) -> io::Result<()> {
+    let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    writeln!(file, "{} {}", std::process::id(), Utc::now().to_rfc3339())?;
+    file.sync_all()
+}
+
+//  "12345 2024-06-02T18:40:00+00:00", a lock without readable time is left alone
+fn is_stale(holder: &str, now: DateTime<Utc>) -> bool {
+    holder
+        .split_whitespace()
+        .nth(1)
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok())
+        .is_some_and(|taken| now - taken.with_timezone(&Utc) > Duration::minutes(STALE_LOCK_MINUTES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_lock_is_exclusive() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+
+        let lock = WorkbookLock::acquire(destination).unwrap();
+        assert!(temp_dir.path().join(LOCK_FILE_NAME).exists());
+        assert!(WorkbookLock::acquire(destination).is_err());
+
+        drop(lock);
+        assert!(!temp_dir.path().join(LOCK_FILE_NAME).exists());
+        assert!(WorkbookLock::acquire(destination).is_ok());
+    }
+
+    #[test]
+    fn test_stale_lock_is_taken_over() {
+        let temp_dir = tempdir().unwrap();
+        let destination = temp_dir.path().to_str().unwrap();
+        fs::write(temp_dir.path().join(LOCK_FILE_NAME), "1 2024-06-02T18:40:00+00:00\n").unwrap();
+
+        let _lock = WorkbookLock::acquire(destination).unwrap();
+        let holder = fs::read_to_string(temp_dir.path().join(LOCK_FILE_NAME)).unwrap();
+        assert!(holder.starts_with(&std::process::id().to_string()));
+    }
+
+    #[test]
+    fn test_is_stale() {
+        let now = DateTime::parse_from_rfc3339("2024-06-02T18:40:00+00:00").unwrap().with_timezone(&Utc);
+        assert!(!is_stale("1 2024-06-02T18:35:00+00:00", now));
+        assert!(is_stale("1 2024-06-02T18:20:00+00:00", now));
+        assert!(!is_stale("garbage", now));
+    }
+}